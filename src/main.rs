@@ -1,15 +1,68 @@
-mod parser;
-
-use crate::parser::Element;
+use std::sync::Arc;
+use testgrammar::parser::{self, Element};
 
 fn main() {
-    let path = "./g2.txt";
+    // --grammar path：文法文件，.y、.g4、.ebnf 等格式按扩展名和内容识别
+    let args: Vec<String> = std::env::args().collect();
+    let path = args
+        .iter()
+        .position(|arg| arg == "--grammar")
+        .and_then(|i| args.get(i + 1))
+        .map_or("./g2.txt", String::as_str);
     let mut grammar = parser::Grammar::new();
-    grammar.grammar_load(path);
+    if let Err(e) = grammar.grammar_load(path) {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    }
 
-    let mut lr1 = parser::LR1Parser::new();
-    lr1.compute_lr1_item_sets(&grammar);
-    lr1.construct_parsing_table(&grammar);
+    // --generate out.rs：根据文法中的语义动作生成带类型的分析器模块
+    if let Some(out) = args
+        .iter()
+        .position(|arg| arg == "--generate")
+        .and_then(|i| args.get(i + 1))
+    {
+        match parser::generate(&grammar) {
+            Ok(code) => std::fs::write(out, code).expect("Unable to write generated parser"),
+            Err(e) => eprintln!("error: {}", e),
+        }
+        return;
+    }
+
+    // --export native|bison|ebnf|html out：把文法导出为其他格式，html 为各规则的铁路图
+    if let Some(i) = args.iter().position(|arg| arg == "--export") {
+        let (Some(format), Some(out)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("usage: --export native|bison|ebnf|html <out>");
+            std::process::exit(1);
+        };
+        let text = match format.as_str() {
+            "native" => parser::to_native(&grammar),
+            "bison" => parser::to_bison(&grammar),
+            "ebnf" => parser::to_ebnf(&grammar),
+            "html" => parser::railroad_html(&grammar),
+            _ => {
+                eprintln!("unknown export format `{}`", format);
+                std::process::exit(1);
+            }
+        };
+        std::fs::write(out, text).expect("Unable to write exported grammar");
+        return;
+    }
+
+    // --ambiguity N：在长度不超过 N 的句子中寻找有多棵语法树的句子
+    if let Some(i) = args.iter().position(|arg| arg == "--ambiguity") {
+        let Some(max_len) = args.get(i + 1).and_then(|v| v.parse().ok()) else {
+            eprintln!("usage: --ambiguity <max length>");
+            std::process::exit(1);
+        };
+        let ambiguities = grammar.ambiguities(max_len, 10);
+        if ambiguities.is_empty() {
+            println!("no ambiguous sentence up to length {}", max_len);
+        }
+        for ambiguity in ambiguities {
+            print!("{}", ambiguity);
+        }
+        return;
+    }
 
     #[rustfmt::skip]
     let input:Vec<Element> = vec![
@@ -34,8 +87,73 @@ fn main() {
     .map(|e| Element::Terminal(e.to_string()))
     .collect();
 
-    let tmp = lr1.construct_tree(&input);
-    print!("{}", tmp);
-}
+    // --ll1：改用 LL(1) 预测分析，先在标准错误中列出文法的 LL(1) 冲突
+    if args.iter().any(|arg| arg == "--ll1") {
+        let ll1 = parser::LL1Parser::new(&grammar);
+        for conflict in ll1.conflicts() {
+            eprintln!("{}", conflict);
+        }
+        print!("{}", ll1.construct_tree(&input));
+        return;
+    }
+
+    // --earley：用 Earley 分析器分析，文法不必是 LR(1) 的，输出共享压缩分析森林
+    if args.iter().any(|arg| arg == "--earley") {
+        print!(
+            "{}",
+            parser::EarleyParser::new(&grammar).construct_forest(&input)
+        );
+        return;
+    }
 
+    let mut lr1 = parser::LR1Parser::new();
+    lr1.compute_lr1_item_sets(&grammar);
+    lr1.construct_parsing_table(&grammar);
+
+    // --report out.output：类似 bison -v，把项集族、转移、动作和冲突写到文本文件
+    if let Some(out) = args
+        .iter()
+        .position(|arg| arg == "--report")
+        .and_then(|i| args.get(i + 1))
+    {
+        match lr1.report(&grammar) {
+            Ok(text) => std::fs::write(out, text).expect("Unable to write automaton report"),
+            Err(e) => eprintln!("error: {}", e),
+        }
+        return;
+    }
+
+    // --conflicts：列出分析表中的冲突，并给出每个冲突的反例
+    if args.iter().any(|arg| arg == "--conflicts") {
+        for counterexample in lr1.counterexamples(&grammar) {
+            println!("{}", counterexample);
+        }
+        return;
+    }
 
+    // --glr：按允许多个动作的分析表做 GLR 分析，输出共享压缩分析森林
+    if args.iter().any(|arg| arg == "--glr") {
+        let tables = parser::GLRTables::new(&lr1, &grammar);
+        eprintln!(
+            "{} conflicting cells in the action table",
+            tables.conflicts().len()
+        );
+        print!(
+            "{}",
+            parser::GLRDriver::new(Arc::new(tables)).construct_forest(&input)
+        );
+        return;
+    }
+
+    let mut driver = parser::LR1Driver::new(Arc::new(lr1.into_tables()));
+    if args.iter().any(|arg| arg == "--repair") {
+        driver.set_recovery(parser::Recovery::Repair);
+    }
+
+    let mut tmp = driver.construct_tree(&input);
+    // --simplify：按 %inline 和 %flatten 化简语法树
+    if args.iter().any(|arg| arg == "--simplify") {
+        tmp.tree = parser::TreeSimplifier::new(&grammar).simplify(tmp.tree);
+    }
+    print!("{}", tmp);
+}
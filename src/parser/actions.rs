@@ -0,0 +1,243 @@
+use crate::parser::lr1_parser::{NodeKind, ParseTables, ProdId, TreeNode, ERROR_TOKEN};
+use crate::parser::types::{Element, Token};
+use std::sync::Arc;
+
+/// 分析栈中的一项：文法符号的值，或错误恢复时跳过的输入
+#[derive(Debug, Clone)]
+pub enum Slot<V> {
+    Value(V),
+    Skipped(Vec<V>),
+}
+
+/// 移进和归约时执行的语义动作，分析栈中保存的是 `Value`
+pub trait Actions {
+    type Value: Clone;
+
+    /// 移进一个记号。错误恢复时插入的记号 `token.is_missing()` 为真
+    fn shift(&mut self, token: &Token) -> Self::Value;
+    /// 按编号为 `prod` 的产生式归约，`children` 与产生式右部一一对应（ε 产生式为空）
+    fn reduce(&mut self, prod: ProdId, children: Vec<Self::Value>) -> Self::Value;
+    /// 分析没有干净地结束（未被接受，或还剩有被跳过的输入）时，
+    /// 由栈中剩下的项构造结果
+    fn recovered(&mut self, accepted: bool, slots: Vec<Slot<Self::Value>>) -> Self::Value;
+
+    /// 同 `reduce`，但保留夹在子项之间被跳过的输入。默认丢掉它们
+    fn reduce_slots(&mut self, prod: ProdId, children: Vec<Slot<Self::Value>>) -> Self::Value {
+        let children = children
+            .into_iter()
+            .filter_map(|slot| match slot {
+                Slot::Value(v) => Some(v),
+                Slot::Skipped(_) => None,
+            })
+            .collect();
+        self.reduce(prod, children)
+    }
+    /// 用 `error` 产生式恢复时移进 `error`，`_discarded` 是为此弹出的栈项和丢弃的记号
+    fn error(&mut self, token: &Token, _discarded: Vec<Slot<Self::Value>>) -> Self::Value {
+        self.shift(token)
+    }
+    /// 回退到分区时，从栈顶往下的 `_slots` 中取回分区之后读入的 `_count` 个记号，
+    /// 它们将作为被跳过的输入。默认直接丢弃
+    fn rewind<'a>(
+        &mut self,
+        _slots: impl Iterator<Item = &'a Slot<Self::Value>>,
+        _count: usize,
+    ) -> Vec<Self::Value>
+    where
+        Self::Value: 'a,
+    {
+        Vec::new()
+    }
+}
+
+/// 默认的语义动作：构造 `TreeNode` 语法树
+#[derive(Debug, Clone)]
+pub struct TreeBuilder {
+    tables: Arc<ParseTables>,
+}
+impl TreeBuilder {
+    pub fn new(tables: Arc<ParseTables>) -> Self {
+        Self { tables }
+    }
+}
+
+impl Actions for TreeBuilder {
+    type Value = TreeNode;
+
+    fn shift(&mut self, token: &Token) -> TreeNode {
+        let mut node = TreeNode::leaf(token.kind.clone());
+        if token.is_missing() {
+            node.kind = NodeKind::Missing;
+        }
+        node
+    }
+    fn reduce(&mut self, prod: ProdId, children: Vec<TreeNode>) -> TreeNode {
+        let (head, _) = self.tables.production(prod);
+        let mut nodes = Vec::with_capacity(children.len());
+        for child in children {
+            match child.children {
+                Some(grandchildren) if self.tables.is_generated(&child.element) => {
+                    nodes.extend(grandchildren)
+                }
+                _ => nodes.push(child),
+            }
+        }
+        TreeNode::new(head.clone(), nodes)
+    }
+    /// 被接受时把首尾被跳过的输入并入根节点；否则把剩下的节点放在一个 Error 根节点下
+    fn recovered(&mut self, accepted: bool, slots: Vec<Slot<TreeNode>>) -> TreeNode {
+        let mut nodes: Vec<TreeNode> = slots.into_iter().map(into_node).collect();
+        let roots: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].kind != NodeKind::Skipped)
+            .collect();
+        match (accepted, roots.as_slice()) {
+            (true, &[index]) if nodes[index].children.is_some() => {
+                let after = nodes.split_off(index + 1);
+                let mut root = nodes.pop().unwrap();
+                let children = root.children.as_mut().unwrap();
+                children.splice(0..0, nodes);
+                children.extend(after);
+                root
+            }
+            _ => TreeNode {
+                element: self
+                    .tables
+                    .start_symbol
+                    .clone()
+                    .unwrap_or(Element::NotTerminal(ERROR_TOKEN.to_string())),
+                children: Some(nodes),
+                kind: NodeKind::Error,
+            },
+        }
+    }
+
+    fn reduce_slots(&mut self, prod: ProdId, children: Vec<Slot<TreeNode>>) -> TreeNode {
+        self.reduce(prod, children.into_iter().map(into_node).collect())
+    }
+    fn error(&mut self, token: &Token, discarded: Vec<Slot<TreeNode>>) -> TreeNode {
+        TreeNode {
+            element: token.kind.clone(),
+            children: Some(discarded.into_iter().map(into_node).collect()),
+            kind: NodeKind::Error,
+        }
+    }
+    fn rewind<'a>(
+        &mut self,
+        slots: impl Iterator<Item = &'a Slot<TreeNode>>,
+        count: usize,
+    ) -> Vec<TreeNode> {
+        let mut leaves = Vec::new();
+        for slot in slots {
+            match slot {
+                Slot::Value(node) => take_input_rev(node, &mut leaves, count),
+                Slot::Skipped(nodes) => nodes
+                    .iter()
+                    .rev()
+                    .for_each(|node| take_input_rev(node, &mut leaves, count)),
+            }
+        }
+        leaves.reverse();
+        leaves
+    }
+}
+
+fn into_node(slot: Slot<TreeNode>) -> TreeNode {
+    match slot {
+        Slot::Value(node) => node,
+        Slot::Skipped(nodes) => TreeNode {
+            element: Element::NotTerminal(ERROR_TOKEN.to_string()),
+            children: Some(nodes),
+            kind: NodeKind::Skipped,
+        },
+    }
+}
+
+/// 从右往左收集 `node` 中由输入记号产生的叶子，直到凑够 `count` 个
+fn take_input_rev(node: &TreeNode, leaves: &mut Vec<TreeNode>, count: usize) {
+    if leaves.len() >= count {
+        return;
+    }
+    match &node.children {
+        Some(children) => {
+            for child in children.iter().rev() {
+                take_input_rev(child, leaves, count);
+            }
+        }
+        None => {
+            let generated = ["ε", ERROR_TOKEN, "#"].map(|v| Element::Terminal(v.to_string()));
+            if node.kind != NodeKind::Missing && !generated.contains(&node.element) {
+                leaves.push(TreeNode::leaf(node.element.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::driver::LR1Driver;
+    use crate::parser::types::Span;
+    use crate::parser::{Grammar, LR1Parser};
+
+    const CALC: &str =
+        "%token num\nCompUnit': E ;\nE: E '+' T | T ;\nT: T '*' F | F ;\nF: num | '(' E ')' ;";
+
+    /// 直接求值而不构造语法树
+    struct Calc {
+        tables: Arc<ParseTables>,
+    }
+
+    impl Actions for Calc {
+        type Value = Option<i64>;
+
+        fn shift(&mut self, token: &Token) -> Option<i64> {
+            token.text.parse().ok()
+        }
+        fn reduce(&mut self, prod: ProdId, children: Vec<Option<i64>>) -> Option<i64> {
+            let id = |head, alternative| self.tables.production_id(head, alternative);
+            match children.as_slice() {
+                [a, _, b] if Some(prod) == id("E", 0) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+                [a, _, b] if Some(prod) == id("T", 0) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+                [_, v, _] if Some(prod) == id("F", 1) => *v,
+                [v] => *v,
+                _ => None,
+            }
+        }
+        fn recovered(&mut self, _accepted: bool, slots: Vec<Slot<Option<i64>>>) -> Option<i64> {
+            slots.into_iter().find_map(|slot| match slot {
+                Slot::Value(v) => v,
+                Slot::Skipped(_) => None,
+            })
+        }
+    }
+
+    fn eval(source: &str) -> (Option<i64>, usize) {
+        let grammar: Grammar = CALC.parse().unwrap();
+        let tables = Arc::new(LR1Parser::from_grammar(&grammar).into_tables());
+        let tokens: Vec<Token> = source
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| {
+                let kind = match c {
+                    '0'..='9' => "num".to_string(),
+                    _ => format!("'{}'", c),
+                };
+                Token::new(Element::Terminal(kind), &c.to_string(), Span::new(i, i + 1))
+            })
+            .collect();
+        let calc = Calc {
+            tables: tables.clone(),
+        };
+        let outcome =
+            LR1Driver::with_actions(tables, calc).construct_tree_from_tokens(tokens, source);
+        (outcome.tree, outcome.errors.len())
+    }
+
+    #[test]
+    fn custom_actions_evaluate_without_a_tree() {
+        assert_eq!(eval("2 * (3 + 4)"), (Some(14), 0));
+        assert_eq!(eval("1 + 2 * 3"), (Some(7), 0));
+        // 插入的 ')' 同样交给 shift，求值照常进行
+        assert_eq!(eval("(2 + 3"), (Some(5), 1));
+    }
+}
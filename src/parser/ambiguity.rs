@@ -0,0 +1,241 @@
+use crate::parser::earley::EarleyParser;
+use crate::parser::lr1_parser::{TreeNode, ERROR_TOKEN};
+use crate::parser::transform::eliminate_epsilon;
+use crate::parser::types::Element;
+use crate::parser::Grammar;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt::Display;
+
+/// 取语法树时最多展开的棵数，去掉 EBNF 展开后相同的树后从中选两棵
+const TREE_LIMIT: usize = 8;
+
+/// 一个有多棵语法树的句子
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    pub sentence: Vec<Element>,
+    /// 语法树的数目，循环文法有无穷多棵时为 None
+    pub count: Option<u128>,
+    /// 其中两棵不同的语法树；无穷多棵树但去掉环后只剩一棵时只有一棵
+    pub trees: Vec<TreeNode>,
+}
+
+impl Display for Ambiguity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sentence: Vec<String> = self.sentence.iter().map(|v| v.to_string()).collect();
+        match self.count {
+            Some(count) => writeln!(
+                f,
+                "ambiguous sentence ({} parse trees): {}",
+                count,
+                sentence.join(" ")
+            )?,
+            None => writeln!(
+                f,
+                "ambiguous sentence (infinitely many parse trees): {}",
+                sentence.join(" ")
+            )?,
+        }
+        for (i, tree) in self.trees.iter().enumerate() {
+            writeln!(f, "  parse tree {}:", i + 1)?;
+            write!(f, "{}", tree)?;
+        }
+        Ok(())
+    }
+}
+
+impl Grammar {
+    /// 在长度不超过 `max_len` 的句子中寻找有多棵语法树的句子，按长度从短到长最多返回 `limit` 个。
+    /// 没有找到并不说明文法无歧义，只说明更短的句子都没有歧义；
+    /// 找到了则说明对应的冲突不只是 LR(1) 的限制，而是文法本身有歧义
+    pub fn ambiguities(&self, max_len: usize, limit: usize) -> Vec<Ambiguity> {
+        let earley = EarleyParser::new(self);
+        let mut result = Vec::new();
+        if limit == 0 {
+            return result;
+        }
+        // 句子是边产生边检查的，找够了就不再产生更长的句子
+        for sentence in self.sentences(max_len) {
+            let forest = earley.construct_forest(&sentence).tree;
+            let count = forest.tree_count();
+            if count.is_some_and(|count| count < 2) {
+                continue;
+            }
+            let mut trees: Vec<TreeNode> = Vec::new();
+            for tree in forest.trees(TREE_LIMIT) {
+                if trees.len() < 2 && !trees.contains(&tree) {
+                    trees.push(tree);
+                }
+            }
+            if trees.len() == 2 || count.is_none() {
+                result.push(Ambiguity {
+                    sentence,
+                    count,
+                    trees,
+                });
+                if result.len() >= limit {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// 开始符号能推出的、长度不超过 `max_len` 的句子，按长度从短到长逐个产生。
+    /// 不考虑含 `error` 的产生式
+    fn sentences(&self, max_len: usize) -> Sentences {
+        let epsilon = Element::Terminal("ε".to_string());
+        let error = Element::Terminal(ERROR_TOKEN.to_string());
+        // 在消除了 ε 产生式的文法上推导：除了开始符号推出空串，每个非终结符至少推出一个记号，
+        // 句型不会比要找的句子更长，`S: S S | ;` 这样的文法也只有有限个句型
+        let productions: Vec<(Element, Vec<Element>)> = eliminate_epsilon(self)
+            .grammar
+            .productions()
+            .into_iter()
+            .filter(|(_, body)| !body.contains(&error))
+            .map(|(head, body)| (head, body.into_iter().filter(|v| *v != epsilon).collect()))
+            .collect();
+
+        // 每个非终结符推出的最短句子的长度
+        let mut min_len: HashMap<Element, usize> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (head, body) in &productions {
+                let len = body.iter().try_fold(0, |total, symbol| match symbol {
+                    Element::Terminal(_) => Some(total + 1),
+                    Element::NotTerminal(_) => Some(total + min_len.get(symbol)?),
+                });
+                if let Some(len) = len.filter(|&len| min_len.get(head).is_none_or(|&v| len < v)) {
+                    min_len.insert(head.clone(), len);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut by_head: HashMap<Element, Vec<Vec<Element>>> = HashMap::new();
+        for (head, body) in productions {
+            by_head.entry(head).or_default().push(body);
+        }
+        let mut sentences = Sentences {
+            by_head,
+            min_len,
+            max_len,
+            queue: BinaryHeap::new(),
+            forms: HashMap::new(),
+            visited: HashSet::new(),
+        };
+        sentences.push(vec![self.start_symbol.clone()]);
+        sentences
+    }
+}
+
+/// 从开始符号做最左推导产生句子。展开非终结符时句型至少推出的长度不会变短，
+/// 所以按这个长度从小到大展开句型，得到的句子也是从短到长的
+struct Sentences {
+    by_head: HashMap<Element, Vec<Vec<Element>>>,
+    min_len: HashMap<Element, usize>,
+    max_len: usize,
+    /// 待展开的句型：（至少推出的长度，编号），长度相同时先进先出
+    queue: BinaryHeap<Reverse<(usize, usize)>>,
+    forms: HashMap<usize, Vec<Element>>,
+    visited: HashSet<Vec<Element>>,
+}
+
+impl Sentences {
+    /// 句型至少推出的长度，含推不出句子的非终结符时为 None
+    fn bound(&self, form: &[Element]) -> Option<usize> {
+        form.iter().try_fold(0, |total, symbol| match symbol {
+            Element::Terminal(_) => Some(total + 1),
+            Element::NotTerminal(_) => Some(total + self.min_len.get(symbol)?),
+        })
+    }
+    fn push(&mut self, form: Vec<Element>) {
+        let Some(len) = self.bound(&form).filter(|&len| len <= self.max_len) else {
+            return;
+        };
+        if self.visited.insert(form.clone()) {
+            let id = self.visited.len();
+            self.queue.push(Reverse((len, id)));
+            self.forms.insert(id, form);
+        }
+    }
+}
+
+impl Iterator for Sentences {
+    type Item = Vec<Element>;
+    fn next(&mut self) -> Option<Vec<Element>> {
+        while let Some(Reverse((_, id))) = self.queue.pop() {
+            let form = self.forms.remove(&id).unwrap();
+            let Some(i) = form
+                .iter()
+                .position(|v| matches!(v, Element::NotTerminal(_)))
+            else {
+                return Some(form);
+            };
+            let bodies = self.by_head.get(&form[i]).cloned().unwrap_or_default();
+            for body in bodies {
+                self.push([&form[..i], &body, &form[i + 1..]].concat());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dangling_else() -> Grammar {
+        "%token c x\nCompUnit': S ;\nS: 'if' c S | 'if' c S 'else' S | x ;"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn sentences_come_shortest_first() {
+        let lengths: Vec<usize> = dangling_else().sentences(7).map(|v| v.len()).collect();
+        assert_eq!(lengths.first(), Some(&1));
+        assert!(lengths.windows(2).all(|v| v[0] <= v[1]));
+        assert_eq!(lengths.last(), Some(&7));
+    }
+
+    #[test]
+    fn dangling_else_is_ambiguous() {
+        let grammar = dangling_else();
+        assert!(grammar.ambiguities(6, 10).is_empty());
+
+        let found = grammar.ambiguities(7, 1);
+        assert_eq!(found.len(), 1);
+        let ambiguity = &found[0];
+        assert_eq!(
+            ambiguity.sentence,
+            Element::terminals("'if' c 'if' c x 'else' x")
+        );
+        assert_eq!(ambiguity.count, Some(2));
+        assert_eq!(ambiguity.trees.len(), 2);
+        assert_ne!(ambiguity.trees[0], ambiguity.trees[1]);
+    }
+
+    #[test]
+    fn nullable_recursion_terminates() {
+        let grammar: Grammar = "CompUnit': S ;\nS: S S | 'a' | ;".parse().unwrap();
+        let lengths: Vec<usize> = grammar.sentences(4).map(|v| v.len()).collect();
+        assert_eq!(lengths, [0, 1, 2, 3, 4]);
+
+        // 空串和 'a' 都有无穷多棵语法树
+        let found = grammar.ambiguities(3, 2);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].sentence, []);
+        assert_eq!(found[1].sentence, Element::terminals("'a'"));
+        assert!(found.iter().all(|ambiguity| ambiguity.count.is_none()));
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let grammar = dangling_else();
+        assert_eq!(grammar.ambiguities(11, 1).len(), 1);
+        assert_eq!(grammar.ambiguities(11, 3).len(), 3);
+        assert!(grammar.ambiguities(11, 0).is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::parser::grammar::Grammar;
+use crate::parser::types::{Element, PHead};
+use std::collections::{HashMap, HashSet};
+
+/// 生成的代码默认通过这个路径使用本库
+pub const PARSER_PATH: &str = "::testgrammar::parser";
+
+/// `Value` 中内置的变体
+const BUILTIN_VARIANTS: [&str; 3] = ["Token", "Unit", "Error"];
+
+const KEYWORDS: [&str; 52] = [
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// 根据文法中的 `%type` 和 `=> { ... }` 语义动作生成带类型的分析器模块，
+/// 通过 `PARSER_PATH` 使用本库，见 `generate_in`。
+///
+/// 动作中用 `$1`、`$2` 引用右部符号的值：终结符是 `Token`，声明了 `%type` 的
+/// 非终结符是对应类型，其余是 `()`。生成的模块应放在 AST 所在模块之下
+/// （其中有 `use super::*`），运行时使用由同一文法构造的分析表。
+///
+/// 子节点中有错误恢复时插入的记号（`Token::is_missing`，文本为空）或 `Value::Error` 时，
+/// 不执行语义动作，产生式的值为 `Value::Error`
+pub fn generate(grammar: &Grammar) -> Result<String, String> {
+    generate_in(grammar, PARSER_PATH)
+}
+
+/// 同 `generate`，生成的代码通过 `parser_path` 使用本库的 `parser` 模块，
+/// 如在本库内部为 `crate::parser`
+pub fn generate_in(grammar: &Grammar, parser_path: &str) -> Result<String, String> {
+    let variants = variants(grammar);
+    let mut buffer = String::new();
+    buffer.push_str("// 由文法生成，不要手动修改\n");
+    buffer.push_str("#![allow(unused_imports, unused_variables, unused_braces, non_camel_case_types, clippy::let_unit_value, clippy::unit_arg)]\n");
+    buffer.push_str("use super::*;\n");
+    buffer.push_str(&format!(
+        "use {}::{{Actions, LR1Driver, ParseTables, ParserError, ProdId, Slot, Token}};\n",
+        parser_path
+    ));
+    buffer.push_str("use std::sync::Arc;\n\n");
+
+    buffer.push_str("#[derive(Debug, Clone)]\npub enum Value {\n");
+    buffer.push_str("    Token(Token),\n    Unit,\n    /// 错误恢复后无法构造的值\n    Error,\n");
+    for head in &grammar.head_list {
+        if let Some(ty) = grammar.type_list.get(head) {
+            buffer.push_str(&format!("    {}({}),\n", variants[head], ty));
+        }
+    }
+    buffer.push_str("}\n\n");
+
+    let productions = grammar.productions();
+    buffer.push_str("/// 每个产生式的左部，用来检查分析表是否由同一文法构造\n");
+    buffer.push_str("const HEADS: &[&str] = &[\n");
+    for (head, _) in &productions {
+        buffer.push_str(&format!("    {:?},\n", head.to_string()));
+    }
+    buffer.push_str("];\n\n");
+
+    buffer.push_str("pub struct GrammarActions;\n\n");
+    buffer.push_str("impl Actions for GrammarActions {\n    type Value = Value;\n\n");
+    buffer.push_str("    fn shift(&mut self, token: &Token) -> Value {\n        Value::Token(token.clone())\n    }\n");
+    buffer.push_str("    fn reduce(&mut self, prod: ProdId, children: Vec<Value>) -> Value {\n");
+    buffer.push_str("        if children.iter().any(|v| matches!(v, Value::Error) || matches!(v, Value::Token(t) if t.is_missing())) {\n");
+    buffer.push_str("            return Value::Error;\n        }\n");
+    buffer.push_str("        let mut children = children.into_iter();\n        match prod {\n");
+    for (id, (head, body)) in productions.iter().enumerate() {
+        let name = format!("`{}: {}`", head, join(body));
+        let arm = reduce_arm(grammar, &variants, head, body, grammar.action(id), &name)?;
+        if let Some(arm) = arm {
+            buffer.push_str(&format!(
+                "            // {}\n            {} => {{\n{}            }}\n",
+                name, id, arm
+            ));
+        }
+    }
+    buffer.push_str("            _ => Value::Unit,\n        }\n    }\n");
+    buffer.push_str(
+        "    fn recovered(&mut self, accepted: bool, slots: Vec<Slot<Value>>) -> Value {\n",
+    );
+    buffer.push_str("        let mut values = slots.into_iter().filter_map(|slot| match slot {\n");
+    buffer.push_str("            Slot::Value(v) => Some(v),\n            Slot::Skipped(_) => None,\n        });\n");
+    buffer.push_str("        match (accepted, values.next(), values.next()) {\n");
+    buffer.push_str("            (true, Some(v), None) => v,\n            _ => Value::Error,\n        }\n    }\n}\n\n");
+
+    buffer.push_str("fn token(value: Option<Value>) -> Token {\n    match value {\n");
+    buffer.push_str("        Some(Value::Token(v)) => v,\n        v => unreachable!(\"expected a token, found {:?}\", v),\n    }\n}\n\n");
+
+    let root = grammar
+        .pro_list
+        .get(&grammar.start_symbol)
+        .and_then(|bodies| bodies.first())
+        .and_then(|body| body.first())
+        .ok_or("the start symbol has no production")?;
+    let (root_type, root_value) = match grammar.type_list.get(root) {
+        Some(ty) => (ty.clone(), format!("Value::{}(v)", variants[root])),
+        None => ("()".to_string(), "Value::Unit".to_string()),
+    };
+    buffer.push_str("/// 分析 `tokens`，返回开始符号的值（分析失败时为 None）和分析中的错误\n");
+    buffer.push_str(&format!(
+        "pub fn parse(\n    tables: Arc<ParseTables>,\n    tokens: impl IntoIterator<Item = Token>,\n    source: &str,\n) -> (Option<{}>, Vec<ParserError>) {{\n",
+        root_type
+    ));
+    buffer.push_str("    for (id, head) in HEADS.iter().enumerate() {\n");
+    buffer.push_str("        assert_eq!(&tables.production(id).0.to_string(), head, \"parse tables were built from a different grammar\");\n    }\n");
+    buffer.push_str("    let outcome = LR1Driver::with_actions(tables, GrammarActions).construct_tree_from_tokens(tokens, source);\n");
+    let bind = if root_type == "()" { "()" } else { "v" };
+    buffer.push_str(&format!(
+        "    match outcome.tree {{\n        {} => (Some({}), outcome.errors),\n        _ => (None, outcome.errors),\n    }}\n}}\n",
+        root_value, bind
+    ));
+    Ok(buffer)
+}
+
+/// 一个产生式的归约代码：逐个取出子节点的值，再执行语义动作
+fn reduce_arm(
+    grammar: &Grammar,
+    variants: &HashMap<PHead, String>,
+    head: &PHead,
+    body: &[Element],
+    action: Option<&str>,
+    name: &str,
+) -> Result<Option<String>, String> {
+    let ty = grammar.type_list.get(head);
+    if ty.is_none() && action.is_none() {
+        return Ok(None);
+    }
+    let body: Vec<&Element> = body
+        .iter()
+        .filter(|symbol| **symbol != Element::Terminal("ε".to_string()))
+        .collect();
+
+    let mut arm = String::new();
+    for (i, symbol) in body.iter().enumerate() {
+        let value = match symbol {
+            Element::Terminal(_) => "token(children.next())".to_string(),
+            Element::NotTerminal(_) => match grammar.type_list.get(*symbol) {
+                Some(_) => format!(
+                    "match children.next() {{\n                    Some(Value::{}(v)) => v,\n                    _ => unreachable!(),\n                }}",
+                    variants[*symbol]
+                ),
+                None => "{\n                    children.next();\n                }".to_string(),
+            },
+        };
+        arm.push_str(&format!("                let v{} = {};\n", i + 1, value));
+    }
+
+    let code = match action {
+        Some(action) => Some(substitute(action, body.len(), name)?),
+        // 同 yacc：没有动作时 $$ = $1
+        None if body.first().and_then(|first| grammar.type_list.get(*first)) == ty => {
+            Some("v1".to_string())
+        }
+        None => {
+            return Err(format!(
+                "production {} needs an action to produce a value of type `{}`",
+                name,
+                ty.unwrap()
+            ))
+        }
+    };
+    match (ty, code) {
+        (Some(_), Some(code)) => arm.push_str(&format!(
+            "                Value::{}({{ {} }})\n",
+            variants[head], code
+        )),
+        (_, code) => {
+            arm.push_str(&format!(
+                "                let _ = {{ {} }};\n",
+                code.unwrap_or_default()
+            ));
+            arm.push_str("                Value::Unit\n");
+        }
+    }
+    Ok(Some(arm))
+}
+
+/// 把动作中的 `$n` 换成第 n 个子节点的变量名
+fn substitute(action: &str, len: usize, name: &str) -> Result<String, String> {
+    let mut code = String::new();
+    let mut chars = action.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' || !chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            code.push(c);
+            continue;
+        }
+        let mut n = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            n.push(digit);
+        }
+        match n.parse::<usize>() {
+            Ok(i) if (1..=len).contains(&i) => code.push_str(&format!("v{}", i)),
+            _ => return Err(format!("`${}` out of range in the action of {}", n, name)),
+        }
+    }
+    Ok(code)
+}
+
+/// 声明了 `%type` 的非终结符在 `Value` 中的变体名。符号名转成 Rust 标识符，如 `MulExp'` -> `MulExp_`；
+/// 与内置的变体、关键字或别的变体重名时在末尾加 `_`
+fn variants(grammar: &Grammar) -> HashMap<PHead, String> {
+    let mut used: HashSet<String> = BUILTIN_VARIANTS
+        .iter()
+        .chain(&KEYWORDS)
+        .map(|v| v.to_string())
+        .collect();
+    let mut variants = HashMap::new();
+    // 先按规则的顺序，再是没有规则的符号，保证生成的名字稳定
+    let mut rest: Vec<&PHead> = grammar
+        .type_list
+        .keys()
+        .filter(|v| !grammar.head_list.contains(v))
+        .collect();
+    rest.sort_by_key(|v| v.to_string());
+    let heads = grammar
+        .head_list
+        .iter()
+        .filter(|v| grammar.type_list.contains_key(*v));
+    for head in heads.chain(rest) {
+        let mut name: String = head
+            .to_string()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+        while !used.insert(name.clone()) {
+            name.push('_');
+        }
+        variants.insert(head.clone(), name);
+    }
+    variants
+}
+
+fn join(body: &[Element]) -> String {
+    let body: Vec<String> = body.iter().map(|symbol| symbol.to_string()).collect();
+    body.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant_names_do_not_collide() {
+        let grammar: Grammar = "%type <i64> Error type A' A_\nCompUnit': Error ;\nError: type ;\ntype: A' ;\nA': A_ ;\nA_: 'a' => { 1 } ;"
+            .parse()
+            .unwrap();
+        let variants = variants(&grammar);
+        let name = |v: &str| variants[&Element::NotTerminal(v.to_string())].as_str();
+        assert_eq!(name("Error"), "Error_");
+        assert_eq!(name("type"), "type_");
+        assert_eq!(name("A'"), "A_");
+        assert_eq!(name("A_"), "A__");
+
+        let code = generate_in(&grammar, "crate::parser").unwrap();
+        assert!(code.contains("use crate::parser::{"));
+        assert!(code.contains("    Error,\n    Error_(i64),\n"));
+    }
+}
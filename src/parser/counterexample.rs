@@ -0,0 +1,734 @@
+use crate::parser::earley::EarleyParser;
+use crate::parser::lr1_parser::{
+    Action, ActionSets, Conflict, LR1Item, LR1Parser, State, TreeNode,
+};
+use crate::parser::types::Element;
+use crate::parser::Grammar;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt::Display;
+
+/// 反例中的推导。只展开与冲突有关的部分，其余符号保持未展开
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Derivation {
+    /// 没有展开的符号
+    Symbol(Element),
+    /// 冲突发生的位置
+    Dot,
+    /// 非终结符按某个产生式展开，没有子项时推出 ε
+    Node(Element, Vec<Derivation>),
+}
+
+impl Derivation {
+    /// 推导得到的句型，冲突的位置为 None
+    pub fn frontier(&self) -> Vec<Option<Element>> {
+        fn walk(derivation: &Derivation, out: &mut Vec<Option<Element>>) {
+            match derivation {
+                Derivation::Symbol(symbol) => out.push(Some(symbol.clone())),
+                Derivation::Dot => out.push(None),
+                Derivation::Node(_, children) => children.iter().for_each(|v| walk(v, out)),
+            }
+        }
+        let mut out = Vec::new();
+        walk(self, &mut out);
+        out
+    }
+}
+
+impl Display for Derivation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Derivation::Symbol(symbol) => write!(f, "{}", symbol),
+            Derivation::Dot => write!(f, "•"),
+            Derivation::Node(head, children) if children.is_empty() => write!(f, "{} → ε", head),
+            Derivation::Node(head, children) => {
+                write!(f, "{} → [", head)?;
+                for child in children {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, " ]")
+            }
+        }
+    }
+}
+
+/// 一个冲突中两个动作的反例
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub conflict: Conflict,
+    pub actions: [Action; 2],
+    /// 两个动作各自的推导，没有找到到达冲突项目的路径时为 None
+    pub derivations: Option<[Derivation; 2]>,
+    /// 两个推导得到同一个句型，说明文法有歧义；否则只说明文法不是 LR(1) 的
+    pub unifying: bool,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = |action: &Action| match action {
+            Action::Shift(_) => "shift",
+            Action::Reduce(..) => "reduce",
+            Action::Accept => "accept",
+        };
+        writeln!(
+            f,
+            "{}/{} conflict in state {} on {}",
+            kind(&self.actions[0]),
+            kind(&self.actions[1]),
+            self.conflict.state,
+            self.conflict.lookahead
+        )?;
+        let label = |action: &Action| match action {
+            Action::Reduce(head, body) => {
+                let body: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+                format!("reduce derivation ({} → {})", head, body.join(" "))
+            }
+            action => format!("{} derivation", kind(action)),
+        };
+        let Some(derivations) = &self.derivations else {
+            return writeln!(f, "  no counterexample found");
+        };
+        if self.unifying {
+            writeln!(f, "  ambiguous example: {}", sentence(&derivations[0]))?;
+            for (action, derivation) in self.actions.iter().zip(derivations) {
+                writeln!(f, "    {}: {}", label(action), derivation)?;
+            }
+        } else {
+            for (action, derivation) in self.actions.iter().zip(derivations) {
+                writeln!(f, "  example: {}", sentence(derivation))?;
+                writeln!(f, "    {}: {}", label(action), derivation)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sentence(derivation: &Derivation) -> String {
+    let epsilon = Element::Terminal("ε".to_string());
+    let symbols: Vec<String> = derivation
+        .frontier()
+        .into_iter()
+        .filter(|symbol| symbol.as_ref() != Some(&epsilon))
+        .map(|symbol| symbol.map_or("•".to_string(), |v| v.to_string()))
+        .collect();
+    symbols.join(" ")
+}
+
+impl LR1Parser {
+    /// 为分析表中的每个冲突构造反例：一格中有多个动作时，第一个动作分别与其余动作各成一个反例，
+    /// 找不到反例的也在结果中。需要先构造项集族和分析表
+    pub fn counterexamples(&self, grammar: &Grammar) -> Vec<Counterexample> {
+        let search = Search::new(self, grammar);
+        let mut result = Vec::new();
+        for conflict in self.conflicts(grammar) {
+            for other in &conflict.actions[1..] {
+                let actions = [conflict.actions[0].clone(), other.clone()];
+                let (derivations, unifying) = match search.derivations(&conflict, &actions) {
+                    Some((derivations, unifying)) => (Some(derivations), unifying),
+                    None => (None, false),
+                };
+                result.push(Counterexample {
+                    conflict: conflict.clone(),
+                    actions,
+                    derivations,
+                    unifying,
+                });
+            }
+        }
+        result
+    }
+}
+
+/// 路径上的一步：越过一个符号转移到下一个状态，或在同一状态中展开点后的非终结符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Transition,
+    Production,
+}
+
+/// 从起始项目到目标项目的各步，以及路径经过的状态
+type Path = (Vec<(Step, LR1Item)>, Vec<State>);
+
+struct Search<'a> {
+    parser: &'a LR1Parser,
+    grammar: &'a Grammar,
+    action_sets: ActionSets,
+    start: LR1Item,
+    nullable: HashSet<Element>,
+    /// 每个非终结符能推出的最短终结符串
+    shortest: HashMap<Element, Vec<Element>>,
+}
+
+impl<'a> Search<'a> {
+    fn new(parser: &'a LR1Parser, grammar: &'a Grammar) -> Self {
+        let epsilon = Element::Terminal("ε".to_string());
+        let productions: Vec<(Element, Vec<Element>)> = grammar
+            .productions()
+            .into_iter()
+            .map(|(head, body)| (head, body.into_iter().filter(|v| *v != epsilon).collect()))
+            .collect();
+
+        let mut nullable = HashSet::new();
+        let mut shortest: HashMap<Element, Vec<Element>> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (head, body) in &productions {
+                let mut candidate = Vec::new();
+                for symbol in body {
+                    match symbol {
+                        Element::Terminal(_) => candidate.push(symbol.clone()),
+                        Element::NotTerminal(_) => match shortest.get(symbol) {
+                            Some(v) => candidate.extend(v.iter().cloned()),
+                            None => break,
+                        },
+                    }
+                }
+                let complete = body
+                    .iter()
+                    .all(|v| matches!(v, Element::Terminal(_)) || shortest.contains_key(v));
+                if complete && shortest.get(head).is_none_or(|v| candidate.len() < v.len()) {
+                    if candidate.is_empty() {
+                        nullable.insert(head.clone());
+                    }
+                    shortest.insert(head.clone(), candidate);
+                    changed = true;
+                }
+            }
+        }
+
+        let start = LR1Item {
+            head: grammar.start_symbol.clone(),
+            body: grammar.pro_list[&grammar.start_symbol][0].clone(),
+            dot: 0,
+            lookahead: Element::Terminal("#".to_string()),
+        };
+        Self {
+            parser,
+            grammar,
+            action_sets: parser.action_sets(grammar),
+            start,
+            nullable,
+            shortest,
+        }
+    }
+
+    /// 冲突中两个动作的推导，以及它们是否得到同一个句型
+    fn derivations(
+        &self,
+        conflict: &Conflict,
+        actions: &[Action; 2],
+    ) -> Option<([Derivation; 2], bool)> {
+        let state = conflict.state;
+        let lookahead = &conflict.lookahead;
+        // 先找归约（或接受）项目的最短路径，另一个项目沿同样的状态序列去找，两个推导因此有相同的前缀
+        let (first, second) = match actions {
+            [reduce @ (Action::Reduce(..) | Action::Accept), other]
+            | [other, reduce @ (Action::Reduce(..) | Action::Accept)] => (reduce, other),
+            _ => return None,
+        };
+        let (first_items, second_items) = (
+            self.items(state, lookahead, first),
+            self.items(state, lookahead, second),
+        );
+        let (steps, states) = first_items
+            .iter()
+            .find_map(|item| self.path(state, item, None))?;
+        let derivation = self.derive(&steps, lookahead);
+        // 移进项目可能有多个，优先取与归约的推导得到同一句型的那个
+        let candidates: Vec<Derivation> = second_items
+            .iter()
+            .filter_map(|item| self.path(state, item, Some(&states)))
+            .map(|(steps, _)| self.derive(&steps, lookahead))
+            .collect();
+        let second = candidates
+            .iter()
+            .find(|v| v.frontier() == derivation.frontier())
+            .or(candidates.first())?
+            .clone();
+
+        let mut derivations = [derivation, second];
+        let mut unifying = derivations[0].frontier() == derivations[1].frontier();
+        if !unifying {
+            if let Some(trees) = self.ambiguous(&derivations) {
+                derivations = trees;
+                unifying = true;
+            }
+        }
+        // 按冲突中动作的顺序排列
+        if first != &actions[0] {
+            derivations.swap(0, 1);
+        }
+        Some((derivations, unifying))
+    }
+
+    /// 状态 `state` 中产生动作 `action` 的项目
+    fn items(&self, state: State, lookahead: &Element, action: &Action) -> Vec<LR1Item> {
+        let mut items: Vec<LR1Item> = self.parser.lr1_sets[state]
+            .iter()
+            .filter(|item| match action {
+                Action::Shift(_) => item.body.get(item.dot) == Some(lookahead),
+                Action::Reduce(head, body) => {
+                    item.head == *head
+                        && item.body == *body
+                        && item.dot == body.len()
+                        && item.lookahead == *lookahead
+                }
+                Action::Accept => {
+                    item.head == self.grammar.start_symbol
+                        && item.dot == item.body.len()
+                        && item.lookahead == *lookahead
+                }
+            })
+            .cloned()
+            .collect();
+        items.sort_by_key(|item| {
+            (
+                item.head.to_string(),
+                item.body.len(),
+                item.lookahead.to_string(),
+            )
+        });
+        items
+    }
+
+    /// 越过 `symbol` 后到达的状态
+    fn target(&self, state: State, symbol: &Element) -> Option<State> {
+        match symbol {
+            Element::Terminal(_) => self
+                .action_sets
+                .get(&(state, symbol.clone()))?
+                .iter()
+                .find_map(|action| match action {
+                    Action::Shift(target) => Some(*target),
+                    _ => None,
+                }),
+            Element::NotTerminal(_) => self
+                .parser
+                .goto_table
+                .get(&(state, symbol.clone()))
+                .copied(),
+        }
+    }
+
+    /// 从初始项目到状态 `state` 中的项目 `target` 的最短路径（越过的符号最少），
+    /// 返回每一步和路径经过的状态。给出 `layers` 时只能沿这些状态转移
+    fn path(&self, state: State, target: &LR1Item, layers: Option<&[State]>) -> Option<Path> {
+        // 键的第一项：不分层时是状态，分层时是已经越过的符号数
+        type Key = (usize, LR1Item);
+        let state_of = |key: &Key| layers.map_or(key.0, |layers| layers[key.0]);
+        let is_target = |key: &Key| {
+            key.1 == *target
+                && match layers {
+                    Some(layers) => key.0 + 1 == layers.len(),
+                    None => key.0 == state,
+                }
+        };
+
+        // 代价为（越过的符号数，展开的次数），展开少的推导更短
+        let start: Key = (0, self.start.clone());
+        let mut dist: HashMap<Key, (usize, usize)> = HashMap::from([(start.clone(), (0, 0))]);
+        let mut prev: HashMap<Key, (Key, Step)> = HashMap::new();
+        let mut keys = vec![start];
+        let mut queue = BinaryHeap::from([Reverse(((0, 0), 0))]);
+        let mut found = None;
+        while let Some(Reverse((d, index))) = queue.pop() {
+            let key = keys[index].clone();
+            if dist[&key] < d {
+                continue;
+            }
+            if is_target(&key) {
+                found = Some(key);
+                break;
+            }
+            let current = state_of(&key);
+            let item = &key.1;
+            let Some(symbol) = item.body.get(item.dot) else {
+                continue;
+            };
+
+            let mut next = Vec::new();
+            if let Some(to) = self.target(current, symbol) {
+                let layer = match layers {
+                    Some(layers) if key.0 + 1 < layers.len() && layers[key.0 + 1] == to => {
+                        Some(key.0 + 1)
+                    }
+                    Some(_) => None,
+                    None => Some(to),
+                };
+                if let Some(layer) = layer {
+                    let mut advanced = item.clone();
+                    advanced.dot += 1;
+                    next.push(((layer, advanced), Step::Transition));
+                }
+            }
+            if let Element::NotTerminal(_) = symbol {
+                let epsilon = Element::Terminal("ε".to_string());
+                let rest: Vec<Element> = item.body[item.dot + 1..]
+                    .iter()
+                    .filter(|v| **v != epsilon)
+                    .cloned()
+                    .collect();
+                let mut lookaheads: Vec<Element> = self
+                    .grammar
+                    .first_symbols(&rest, &item.lookahead)
+                    .into_iter()
+                    .collect();
+                lookaheads.sort_by_key(|v| v.to_string());
+                for body in &self.grammar.pro_list[symbol] {
+                    for lookahead in &lookaheads {
+                        let expanded = LR1Item {
+                            head: symbol.clone(),
+                            body: body.clone(),
+                            dot: 0,
+                            lookahead: lookahead.clone(),
+                        };
+                        if self.parser.lr1_sets[current].contains(&expanded) {
+                            next.push(((key.0, expanded), Step::Production));
+                        }
+                    }
+                }
+            }
+
+            for (to, step) in next {
+                let cost = match step {
+                    Step::Transition => (d.0 + 1, d.1),
+                    Step::Production => (d.0, d.1 + 1),
+                };
+                if dist.get(&to).is_some_and(|&v| v <= cost) {
+                    continue;
+                }
+                dist.insert(to.clone(), cost);
+                prev.insert(to.clone(), (key.clone(), step));
+                queue.push(Reverse((cost, keys.len())));
+                keys.push(to);
+            }
+        }
+
+        let mut key = found?;
+        let mut steps = Vec::new();
+        let mut states = vec![state_of(&key)];
+        while let Some((from, step)) = prev.get(&key) {
+            steps.push((*step, key.1.clone()));
+            if *step == Step::Transition {
+                states.push(state_of(from));
+            }
+            key = from.clone();
+        }
+        steps.reverse();
+        states.reverse();
+        Some((steps, states))
+    }
+
+    /// 由路径构造推导。归约项目之后要接上向前看符号，从外层项目的剩余部分中把它推导出来
+    fn derive(&self, steps: &[(Step, LR1Item)], lookahead: &Element) -> Derivation {
+        let mut frames: Vec<(LR1Item, Vec<Derivation>)> = vec![(self.start.clone(), Vec::new())];
+        for (step, item) in steps {
+            match step {
+                Step::Transition => {
+                    let (current, children) = frames.last_mut().unwrap();
+                    children.push(Derivation::Symbol(current.body[current.dot].clone()));
+                    *current = item.clone();
+                }
+                Step::Production => frames.push((item.clone(), Vec::new())),
+            }
+        }
+
+        let (item, mut children) = frames.pop().unwrap();
+        let mut need = if item.dot == item.body.len() {
+            Some(lookahead.clone())
+        } else {
+            None
+        };
+        children.push(Derivation::Dot);
+        children.extend(
+            item.body[item.dot..]
+                .iter()
+                .cloned()
+                .map(Derivation::Symbol),
+        );
+        let mut derivation = Derivation::Node(item.head, children);
+
+        while let Some((item, mut children)) = frames.pop() {
+            children.push(derivation);
+            let rest = &item.body[item.dot + 1..];
+            match need.as_ref().map(|a| self.lead(rest, a, &mut Vec::new())) {
+                Some(Some(lead)) => {
+                    children.extend(lead);
+                    need = None;
+                }
+                // 剩余部分推出 ε，向前看符号来自更外层
+                Some(None) if rest.iter().all(|v| self.nullable.contains(v)) => {
+                    children.extend(rest.iter().map(|v| Derivation::Node(v.clone(), Vec::new())))
+                }
+                _ => {
+                    children.extend(rest.iter().cloned().map(Derivation::Symbol));
+                    need = None;
+                }
+            }
+            derivation = Derivation::Node(item.head, children);
+        }
+
+        // 不显示增广的开始符号
+        match derivation {
+            Derivation::Node(_, mut children) if children.len() == 1 => children.pop().unwrap(),
+            derivation => derivation,
+        }
+    }
+
+    /// 展开 `symbols`，使推出的第一个终结符是 `a`；前面可空的符号推出 ε。做不到时返回 None
+    fn lead(
+        &self,
+        symbols: &[Element],
+        a: &Element,
+        visiting: &mut Vec<Element>,
+    ) -> Option<Vec<Derivation>> {
+        let epsilon = Element::Terminal("ε".to_string());
+        let mut result = Vec::new();
+        for (i, symbol) in symbols.iter().enumerate() {
+            if *symbol == epsilon {
+                continue;
+            }
+            let rest = symbols[i + 1..].iter().cloned().map(Derivation::Symbol);
+            if symbol == a {
+                result.push(Derivation::Symbol(symbol.clone()));
+                result.extend(rest);
+                return Some(result);
+            }
+            if let Element::NotTerminal(_) = symbol {
+                if !visiting.contains(symbol) {
+                    visiting.push(symbol.clone());
+                    let mut bodies: Vec<&Vec<Element>> =
+                        self.grammar.pro_list[symbol].iter().collect();
+                    bodies.sort_by_key(|body| body.len());
+                    let expanded = bodies
+                        .into_iter()
+                        .find_map(|body| self.lead(body, a, visiting));
+                    visiting.pop();
+                    if let Some(children) = expanded {
+                        result.push(Derivation::Node(symbol.clone(), children));
+                        result.extend(rest);
+                        return Some(result);
+                    }
+                }
+                if self.nullable.contains(symbol) {
+                    result.push(Derivation::Node(symbol.clone(), Vec::new()));
+                    continue;
+                }
+            }
+            return None;
+        }
+        None
+    }
+
+    /// 把两个推导中没展开的非终结符换成最短的终结符串，用 Earley 分析检查得到的句子是否有歧义。
+    /// 有歧义时返回该句子的两棵语法树（两者相同的子树不展开）
+    fn ambiguous(&self, derivations: &[Derivation; 2]) -> Option<[Derivation; 2]> {
+        let earley = EarleyParser::new(self.grammar);
+        for derivation in derivations {
+            let mut sentence = Vec::new();
+            for symbol in derivation.frontier().into_iter().flatten() {
+                match symbol {
+                    Element::Terminal(_) => sentence.push(symbol),
+                    Element::NotTerminal(_) => {
+                        sentence.extend(self.shortest.get(&symbol)?.iter().cloned())
+                    }
+                }
+            }
+            let epsilon = Element::Terminal("ε".to_string());
+            sentence.retain(|v| *v != epsilon);
+            let forest = earley.construct_forest(&sentence).tree;
+            if forest.tree_count().is_some_and(|count| count < 2) {
+                continue;
+            }
+            if let [first, second] = forest.trees(2).as_slice() {
+                return Some([compact(first, second), compact(second, first)]);
+            }
+        }
+        None
+    }
+}
+
+/// 把 `tree` 转成推导，与 `other` 中覆盖相同输入的相同子树不展开
+fn compact(tree: &TreeNode, other: &TreeNode) -> Derivation {
+    fn subtrees<'t>(
+        tree: &'t TreeNode,
+        start: usize,
+        out: &mut Vec<(usize, &'t TreeNode)>,
+    ) -> usize {
+        out.push((start, tree));
+        match &tree.children {
+            Some(children) => children
+                .iter()
+                .fold(start, |pos, child| subtrees(child, pos, out)),
+            None => start + 1,
+        }
+    }
+    fn walk(
+        tree: &TreeNode,
+        start: usize,
+        shared: &[(usize, &TreeNode)],
+        root: bool,
+    ) -> (Derivation, usize) {
+        match &tree.children {
+            None => (Derivation::Symbol(tree.element.clone()), start + 1),
+            Some(_) if !root && shared.contains(&(start, tree)) => {
+                let end = subtrees(tree, start, &mut Vec::new());
+                (Derivation::Symbol(tree.element.clone()), end)
+            }
+            Some(children) => {
+                let mut pos = start;
+                let mut nodes = Vec::with_capacity(children.len());
+                for child in children {
+                    let (node, end) = walk(child, pos, shared, false);
+                    nodes.push(node);
+                    pos = end;
+                }
+                (Derivation::Node(tree.element.clone(), nodes), pos)
+            }
+        }
+    }
+    let mut shared = Vec::new();
+    subtrees(other, 0, &mut shared);
+    walk(tree, 0, &shared, true).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(name: &str) -> Element {
+        Element::Terminal(name.to_string())
+    }
+
+    fn counterexamples(text: &str) -> Vec<Counterexample> {
+        let grammar: Grammar = text.parse().unwrap();
+        LR1Parser::from_grammar(&grammar).counterexamples(&grammar)
+    }
+
+    fn frontiers(counterexample: &Counterexample) -> [Vec<Option<Element>>; 2] {
+        let [first, second] = counterexample.derivations.as_ref().unwrap();
+        [first.frontier(), second.frontier()]
+    }
+
+    /// 句型，`•` 表示冲突的位置，大写字母开头的是非终结符
+    fn form(text: &str) -> Vec<Option<Element>> {
+        text.split_whitespace()
+            .map(|v| match v {
+                "•" => None,
+                _ if v.starts_with(|c: char| c.is_uppercase()) => {
+                    Some(Element::NotTerminal(v.to_string()))
+                }
+                _ => Some(t(v)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dangling_else_is_unifying() {
+        let found =
+            counterexamples("%token c x\nCompUnit': S ;\nS: 'if' c S | 'if' c S 'else' S | x ;");
+        assert_eq!(found.len(), 1);
+        let counterexample = &found[0];
+        assert!(matches!(
+            counterexample.actions,
+            [Action::Shift(_), Action::Reduce(..)]
+        ));
+        assert!(counterexample.unifying);
+        let [shift, reduce] = frontiers(counterexample);
+        assert_eq!(shift, reduce);
+        assert_eq!(shift, form("'if' c 'if' c S • 'else' S"));
+    }
+
+    #[test]
+    fn lr2_grammar_is_not_unifying() {
+        let found =
+            counterexamples("%token x a b c\nCompUnit': S ;\nS: A a b | B a c ;\nA: x ;\nB: x ;");
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].unifying);
+        assert_eq!(frontiers(&found[0]), [form("x • a b"), form("x • a c")]);
+    }
+
+    #[test]
+    fn accept_conflicts_are_reported() {
+        let found = counterexamples("CompUnit': S ;\nS: S | 'a' ;");
+        assert_eq!(found.len(), 1);
+        assert!(matches!(
+            found[0].actions,
+            [Action::Reduce(..), Action::Accept]
+        ));
+        assert!(found[0].unifying);
+        assert!(found[0].to_string().contains("accept derivation"));
+
+        let missing = Counterexample {
+            derivations: None,
+            unifying: false,
+            ..found[0].clone()
+        };
+        assert_eq!(
+            missing.to_string(),
+            "reduce/accept conflict in state 2 on #\n  no counterexample found\n"
+        );
+    }
+
+    #[test]
+    fn path_and_derivation() {
+        let grammar: Grammar = "%token x y c\nCompUnit': S ;\nS: A B c ;\nA: x ;\nB: y | ;"
+            .parse()
+            .unwrap();
+        let parser = LR1Parser::from_grammar(&grammar);
+        let search = Search::new(&parser, &grammar);
+        let target = LR1Item {
+            head: Element::NotTerminal("A".to_string()),
+            body: vec![t("x")],
+            dot: 1,
+            lookahead: t("y"),
+        };
+        let state = parser
+            .lr1_sets
+            .iter()
+            .position(|set| set.contains(&target))
+            .unwrap();
+
+        // 展开 S、A 后越过 x
+        let (steps, states) = search.path(state, &target, None).unwrap();
+        let kinds: Vec<Step> = steps.iter().map(|(step, _)| *step).collect();
+        assert_eq!(
+            kinds,
+            [Step::Production, Step::Production, Step::Transition]
+        );
+        assert_eq!(states.len(), 2);
+        assert_eq!(states.last(), Some(&state));
+
+        // 向前看符号 y 由 B 推出
+        assert_eq!(search.derive(&steps, &t("y")).frontier(), form("x • y c"));
+        // 向前看符号 c 跨过可空的 B
+        assert_eq!(search.derive(&steps, &t("c")).frontier(), form("x • c"));
+    }
+
+    #[test]
+    fn lead_expands_to_the_lookahead() {
+        let grammar: Grammar = "%token x y c\nCompUnit': S ;\nS: A B c ;\nA: x ;\nB: y | ;"
+            .parse()
+            .unwrap();
+        let parser = LR1Parser::from_grammar(&grammar);
+        let search = Search::new(&parser, &grammar);
+        let b = Element::NotTerminal("B".to_string());
+        let symbols = [b.clone(), t("c")];
+        assert_eq!(
+            search.lead(&symbols, &t("y"), &mut Vec::new()),
+            Some(vec![
+                Derivation::Node(b.clone(), vec![Derivation::Symbol(t("y"))]),
+                Derivation::Symbol(t("c"))
+            ])
+        );
+        assert_eq!(
+            search.lead(&symbols, &t("c"), &mut Vec::new()),
+            Some(vec![
+                Derivation::Node(b.clone(), Vec::new()),
+                Derivation::Symbol(t("c"))
+            ])
+        );
+        assert_eq!(search.lead(&symbols, &t("x"), &mut Vec::new()), None);
+    }
+}
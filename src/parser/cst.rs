@@ -0,0 +1,403 @@
+use crate::parser::actions::{Actions, Slot};
+use crate::parser::driver::{LR1Driver, ParseOutcome};
+use crate::parser::lexer::Lexer;
+use crate::parser::lr1_parser::{NodeKind, ParseTables, ProdId, ERROR_TOKEN};
+use crate::parser::repair::Recovery;
+use crate::parser::types::{Element, Span, Token};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// 无损语法树中的记号：文本及其之前的空白和注释
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    pub kind: Element,
+    pub node_kind: NodeKind,
+    pub leading: String,
+    pub text: String,
+}
+impl GreenToken {
+    /// 连同前导空白的字节数
+    pub fn width(&self) -> usize {
+        self.leading.len() + self.text.len()
+    }
+}
+
+/// 无损语法树中的内部节点，不记录位置，相同的子树可以共享
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    pub kind: Element,
+    pub node_kind: NodeKind,
+    width: usize,
+    children: Vec<GreenElement>,
+}
+impl GreenNode {
+    pub fn new(kind: Element, node_kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let width = children.iter().map(GreenElement::width).sum();
+        Self {
+            kind,
+            node_kind,
+            width,
+            children,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Node(node) => node.width(),
+            Self::Token(token) => token.width(),
+        }
+    }
+    fn node(kind: Element, node_kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        Self::Node(Arc::new(GreenNode::new(kind, node_kind, children)))
+    }
+}
+
+/// 按源文本顺序写出所有记号，即原样还原源文本
+impl Display for GreenElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 列表产生式的树可能很深，不用递归
+        let mut stack = vec![self];
+        while let Some(element) = stack.pop() {
+            match element {
+                Self::Node(node) => stack.extend(node.children.iter().rev()),
+                Self::Token(token) => write!(f, "{}{}", token.leading, token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 带位置和父节点的语法树视图，在遍历时由 green 树按需构造
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    green: Arc<GreenNode>,
+    offset: usize,
+    parent: Option<Arc<SyntaxNode>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self {
+            green,
+            offset: 0,
+            parent: None,
+        }
+    }
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.green
+    }
+    pub fn kind(&self) -> &Element {
+        &self.green.kind
+    }
+    pub fn node_kind(&self) -> NodeKind {
+        self.green.node_kind
+    }
+    /// 节点覆盖的源文本区间，包括其中记号的前导空白和注释
+    pub fn text_range(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.width)
+    }
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.parent.as_deref()
+    }
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let parent = Arc::new(self.clone());
+        let mut offset = self.offset;
+        let mut children = Vec::with_capacity(self.green.children.len());
+        for child in &self.green.children {
+            children.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode {
+                    green: green.clone(),
+                    offset,
+                    parent: Some(parent.clone()),
+                }),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    offset,
+                    parent: self.clone(),
+                }),
+            });
+            offset += child.width();
+        }
+        children
+    }
+    /// 按源文本顺序列出所有记号，包括错误恢复插入的空记号
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        let mut stack = vec![SyntaxElement::Node(self.clone())];
+        while let Some(element) = stack.pop() {
+            match element {
+                SyntaxElement::Node(node) => stack.extend(node.children().into_iter().rev()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+}
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GreenElement::Node(self.green.clone()))
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &Element {
+        &self.green.kind
+    }
+    pub fn node_kind(&self) -> NodeKind {
+        self.green.node_kind
+    }
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+    pub fn leading(&self) -> &str {
+        &self.green.leading
+    }
+    /// 记号本身的区间，不含前导空白
+    pub fn span(&self) -> Span {
+        let start = self.offset + self.green.leading.len();
+        Span::new(start, start + self.green.text.len())
+    }
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+}
+
+/// 构造无损语法树的语义动作。被跳过的输入作为 Skipped 节点留在树中，
+/// 因此即使有错误，树也覆盖全部源文本
+#[derive(Debug, Clone)]
+pub struct CstBuilder {
+    tables: Arc<ParseTables>,
+}
+impl CstBuilder {
+    pub fn new(tables: Arc<ParseTables>) -> Self {
+        Self { tables }
+    }
+}
+
+impl Actions for CstBuilder {
+    type Value = GreenElement;
+
+    fn shift(&mut self, token: &Token) -> GreenElement {
+        GreenElement::Token(Arc::new(GreenToken {
+            kind: token.kind.clone(),
+            node_kind: token.recovery.unwrap_or_default(),
+            leading: token.leading.clone(),
+            text: token.text.clone(),
+        }))
+    }
+    fn reduce(&mut self, prod: ProdId, children: Vec<GreenElement>) -> GreenElement {
+        let (head, _) = self.tables.production(prod);
+        let mut elements = Vec::with_capacity(children.len());
+        for child in children {
+            match child {
+                GreenElement::Node(node) if self.tables.is_generated(&node.kind) => {
+                    elements.extend(node.children.iter().cloned())
+                }
+                child => elements.push(child),
+            }
+        }
+        GreenElement::node(head.clone(), NodeKind::Normal, elements)
+    }
+    fn recovered(&mut self, accepted: bool, slots: Vec<Slot<GreenElement>>) -> GreenElement {
+        let mut elements: Vec<GreenElement> = slots.into_iter().map(into_element).collect();
+        let roots: Vec<usize> = (0..elements.len())
+            .filter(|&i| !is_skipped(&elements[i]))
+            .collect();
+        match (accepted, roots.as_slice()) {
+            (true, &[index]) if matches!(elements[index], GreenElement::Node(_)) => {
+                let after = elements.split_off(index + 1);
+                let Some(GreenElement::Node(root)) = elements.pop() else {
+                    unreachable!()
+                };
+                let mut children = elements;
+                children.extend(root.children.iter().cloned());
+                children.extend(after);
+                GreenElement::node(root.kind.clone(), root.node_kind, children)
+            }
+            _ => GreenElement::node(
+                self.tables
+                    .start_symbol
+                    .clone()
+                    .unwrap_or(Element::NotTerminal(ERROR_TOKEN.to_string())),
+                NodeKind::Error,
+                elements,
+            ),
+        }
+    }
+
+    fn reduce_slots(&mut self, prod: ProdId, children: Vec<Slot<GreenElement>>) -> GreenElement {
+        self.reduce(prod, children.into_iter().map(into_element).collect())
+    }
+    fn error(&mut self, token: &Token, discarded: Vec<Slot<GreenElement>>) -> GreenElement {
+        let children = discarded.into_iter().map(into_element).collect();
+        GreenElement::node(token.kind.clone(), NodeKind::Error, children)
+    }
+    fn rewind<'a>(
+        &mut self,
+        slots: impl Iterator<Item = &'a Slot<GreenElement>>,
+        count: usize,
+    ) -> Vec<GreenElement> {
+        let mut tokens = Vec::new();
+        for slot in slots {
+            match slot {
+                Slot::Value(element) => take_input_rev(element, &mut tokens, count),
+                Slot::Skipped(elements) => elements
+                    .iter()
+                    .rev()
+                    .for_each(|element| take_input_rev(element, &mut tokens, count)),
+            }
+        }
+        tokens.reverse();
+        tokens
+    }
+}
+
+fn into_element(slot: Slot<GreenElement>) -> GreenElement {
+    match slot {
+        Slot::Value(element) => element,
+        Slot::Skipped(elements) => GreenElement::node(
+            Element::NotTerminal(ERROR_TOKEN.to_string()),
+            NodeKind::Skipped,
+            elements,
+        ),
+    }
+}
+
+fn is_skipped(element: &GreenElement) -> bool {
+    matches!(element, GreenElement::Node(node) if node.node_kind == NodeKind::Skipped)
+}
+
+/// 从右往左收集 `element` 中来自输入的记号，直到凑够 `count` 个
+fn take_input_rev(element: &GreenElement, tokens: &mut Vec<GreenElement>, count: usize) {
+    if tokens.len() >= count {
+        return;
+    }
+    match element {
+        GreenElement::Node(node) => {
+            for child in node.children.iter().rev() {
+                take_input_rev(child, tokens, count);
+            }
+        }
+        GreenElement::Token(token) => {
+            let generated = [ERROR_TOKEN, "#"].map(|v| Element::Terminal(v.to_string()));
+            if token.node_kind != NodeKind::Missing && !generated.contains(&token.kind) {
+                let mut token = GreenToken::clone(token);
+                token.node_kind = NodeKind::Normal;
+                tokens.push(GreenElement::Token(Arc::new(token)));
+            }
+        }
+    }
+}
+
+/// 用文法中的终结符切分 `source` 并构造无损语法树。
+/// 最后的空白和注释挂在根节点末尾的 `#` 记号上，`to_string()` 与 `source` 完全相同
+pub fn parse_lossless(tables: Arc<ParseTables>, source: &str) -> ParseOutcome<SyntaxNode> {
+    parse_lossless_with(tables, source, Recovery::default())
+}
+
+/// 同 `parse_lossless`，出错时按 `recovery` 恢复
+pub fn parse_lossless_with(
+    tables: Arc<ParseTables>,
+    source: &str,
+    recovery: Recovery,
+) -> ParseOutcome<SyntaxNode> {
+    let (tokens, trailing) = Lexer::new(&tables).tokenize(source);
+    let mut driver = LR1Driver::with_actions(tables.clone(), CstBuilder::new(tables));
+    driver.set_recovery(recovery);
+    driver
+        .construct_tree_from_tokens(tokens, source)
+        .map(|tree| {
+            let eof = GreenElement::Token(Arc::new(GreenToken {
+                kind: Element::Terminal("#".to_string()),
+                node_kind: NodeKind::Normal,
+                leading: trailing,
+                text: String::new(),
+            }));
+            let root = match tree {
+                GreenElement::Node(root) => {
+                    let mut children = root.children.clone();
+                    children.push(eof);
+                    GreenNode::new(root.kind.clone(), root.node_kind, children)
+                }
+                token => GreenNode::new(
+                    Element::NotTerminal(ERROR_TOKEN.to_string()),
+                    NodeKind::Error,
+                    vec![token, eof],
+                ),
+            };
+            SyntaxNode::new_root(Arc::new(root))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Grammar, LR1Parser};
+
+    fn g2_tables() -> Arc<ParseTables> {
+        let mut grammar = Grammar::new();
+        grammar
+            .grammar_load(concat!(env!("CARGO_MANIFEST_DIR"), "/g2.txt"))
+            .unwrap();
+        Arc::new(LR1Parser::from_grammar(&grammar).into_tables())
+    }
+
+    /// 分析 `source`，检查语法树还原出源文本，返回错误数
+    fn round_trip(tables: &Arc<ParseTables>, source: &str, recovery: Recovery) -> usize {
+        let outcome = parse_lossless_with(tables.clone(), source, recovery);
+        assert_eq!(outcome.tree.to_string(), source, "{:?}", recovery);
+        let tokens: String = outcome
+            .tree
+            .tokens()
+            .iter()
+            .map(|token| format!("{}{}", token.leading(), token.text()))
+            .collect();
+        assert_eq!(tokens, source, "{:?}", recovery);
+        outcome.errors.len()
+    }
+
+    #[test]
+    fn sysy_source_round_trips() {
+        let tables = g2_tables();
+        let valid = "// 求和\nvoid main() {\n    int a = 1, b[2] = {1, 2}; /* 数组 */\n    return a + b[1];\n}\n  // 结束\n";
+        // 缺少 `;`、多余的 `)`、无法识别的字符（恐慌模式下跳到 `;` 后回退到分区，由 `take_input_rev` 取回记号）、未闭合的括号
+        let broken = [
+            "void main() {\n    int a = 1 /* 缺少分号 */\n    return a;\n}\n",
+            "void main() {\n    return (1)); // 多余的括号\n}\n",
+            "void main() {\n    int a = 1 @ $ 2;\n    a = a + ;\n    return a;\n}",
+            "void main() {\n  if (a { b = 1; } } ",
+        ];
+        for recovery in [Recovery::Panic, Recovery::Repair] {
+            assert_eq!(round_trip(&tables, valid, recovery), 0);
+            for source in broken {
+                assert!(round_trip(&tables, source, recovery) > 0, "{}", source);
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::parser::types::{Element, Span, Token};
+use std::fmt::Display;
+
+#[derive(Debug, Clone)]
+pub struct ParserError {
+    pub error_type: ErrorType,
+    /// 出错记号在输入中的下标
+    pub error_pos: usize,
+    pub span: Span,
+    pub found: Element,
+    pub expected: Vec<Element>,
+    /// 补充说明，如 "after Ident"、"to close '{' at 3:5"
+    pub context: Option<String>,
+    /// 附加提示，如恢复时采用的修复序列
+    pub help: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ErrorType {
+    // 缺失错误
+    Missing(String),
+    // 多余错误
+    Extra(String),
+    // 意外符号
+    Unexpected(String),
+    // 未知错误
+    Unknown(String),
+}
+
+impl ParserError {
+    pub(crate) fn unexpected(
+        error_pos: usize,
+        span: Span,
+        found: Element,
+        expected: Vec<Element>,
+    ) -> Self {
+        Self {
+            error_type: ErrorType::Unexpected(describe(&found)),
+            error_pos,
+            span,
+            found,
+            expected,
+            context: None,
+            help: None,
+        }
+    }
+
+    /// 恢复时插入了 `inserted`
+    pub(crate) fn missing(
+        error_pos: usize,
+        inserted: &Token,
+        found: Element,
+        context: String,
+    ) -> Self {
+        Self {
+            error_type: ErrorType::Missing(inserted.kind.to_string()),
+            error_pos,
+            span: inserted.span,
+            found,
+            expected: vec![inserted.kind.clone()],
+            context: Some(context),
+            help: None,
+        }
+    }
+
+    /// 恢复时跳过了 `skipped`
+    pub(crate) fn extra(error_pos: usize, skipped: Token, context: String) -> Self {
+        Self {
+            error_type: ErrorType::Extra(describe(&skipped.kind)),
+            error_pos,
+            span: skipped.span,
+            found: skipped.kind,
+            expected: Vec::new(),
+            context: Some(context),
+            help: None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = match &self.error_type {
+            ErrorType::Missing(v) => format!("missing {}", v),
+            ErrorType::Extra(v) => format!("unexpected extra {}", v),
+            ErrorType::Unexpected(v) => format!("unexpected {}", v),
+            ErrorType::Unknown(v) => v.clone(),
+        };
+        if let Some(context) = &self.context {
+            message.push(' ');
+            message.push_str(context);
+        }
+        if matches!(self.error_type, ErrorType::Missing(_) | ErrorType::Extra(_)) {
+            return message;
+        }
+        match self.expected.as_slice() {
+            [] => {}
+            [one] => message.push_str(&format!(", expected {}", describe(one))),
+            many => {
+                let many: Vec<String> = many.iter().map(describe).collect();
+                message.push_str(&format!(", expected one of {}", many.join(", ")));
+            }
+        }
+        message
+    }
+
+    /// 按 rustc 的格式输出出错的源码片段，并在出错位置下画 `^`
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let (line, column) = line_col(source, self.span.start);
+        let line_text = source.lines().nth(line - 1).unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+
+        let rest = line_text.chars().count().saturating_sub(column - 1);
+        let width = source
+            .get(self.span.start..self.span.end)
+            .map_or(1, |v| v.chars().count())
+            .clamp(1, rest.max(1));
+        let padding: String = line_text
+            .chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let mut buffer = String::new();
+        buffer.push_str(&format!("error: {}\n", self.message()));
+        buffer.push_str(&format!(
+            "{}--> {}:{}:{}\n",
+            gutter, file_name, line, column
+        ));
+        buffer.push_str(&format!("{} |\n", gutter));
+        buffer.push_str(&format!("{} | {}\n", line, line_text));
+        let label = match &self.error_type {
+            ErrorType::Missing(v) => format!("expected {}", v),
+            ErrorType::Extra(_) => "help: remove this".to_string(),
+            _ => format!("found {}", describe(&self.found)),
+        };
+        buffer.push_str(&format!(
+            "{} | {}{} {}\n",
+            gutter,
+            padding,
+            "^".repeat(width),
+            label
+        ));
+        buffer.push_str(&format!("{} |\n", gutter));
+        if let Some(help) = &self.help {
+            buffer.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        buffer
+    }
+}
+
+impl Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message(), self.error_pos)
+    }
+}
+
+fn describe(token: &Element) -> String {
+    if token == &Element::Terminal("#".to_string()) {
+        "end of input".to_string()
+    } else {
+        token.to_string()
+    }
+}
+
+/// 字节偏移 -> (行, 列)，均从 1 开始
+pub(crate) fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
@@ -0,0 +1,1222 @@
+use crate::parser::actions::{Actions, Slot, TreeBuilder};
+use crate::parser::diagnostic::{line_col, ParserError};
+use crate::parser::lr1_parser::{Action, NodeKind, ParseTables, State, TreeNode, ERROR_TOKEN};
+use crate::parser::repair::{describe_repairs, find_repair, Recovery, Repair, REPAIR_WINDOW};
+use crate::parser::types::{Element, Span, Token};
+use crate::parser::Grammar;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// 单记号修复时，要求修复后能继续移进的记号数
+const REPAIR_LOOKAHEAD: usize = 3;
+
+/// 一次分析的运行时状态。分析表只读、通过 `Arc` 共享，
+/// 同一份表可以在多个线程中各建一个 driver 同时分析。
+/// 移进和归约时执行 `A` 中的语义动作，默认构造 `TreeNode`
+#[derive(Debug)]
+pub struct LR1Driver<A: Actions = TreeBuilder> {
+    tables: Arc<ParseTables>,
+    actions: A,
+    tokens: Vec<Token>,
+    source: String,
+    error_list: Vec<ParserError>,
+    status: Status<A::Value>,
+    part_table: PartitionTable<A::Value>,
+    brackets: Vec<(Delimiter, Token)>,
+    repair_pos: Option<usize>,
+    recovery: Recovery,
+    /// 移进 `error` 时交给语义动作的、被丢弃的内容
+    discarded: Option<Vec<Slot<A::Value>>>,
+    /// 尚未入栈的被跳过的记号，下次移进前作为一个 Skipped 项入栈
+    skipped: Vec<A::Value>,
+    /// 用 `error` 产生式恢复后，还需成功移进多少个记号才重新报错（同 yacc）
+    error_shifts: usize,
+    /// 恐慌模式：正在跳过输入，直到分隔符或可接受的记号
+    panicking: bool,
+    /// 已经调用过 `finish`，输入中不会再有新的记号
+    eof: bool,
+    accepted: bool,
+    /// 已读入的输入记号数，分区据此回退
+    consumed: usize,
+    /// `tokens[0]` 在整个输入中的下标。已分析过的记号会被丢弃
+    base: usize,
+    pos: usize,
+}
+
+// 分析表在线程间共享，driver 可以移到别的线程中使用
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ParseTables>();
+    assert_send_sync::<LR1Driver>();
+};
+
+impl LR1Driver {
+    pub fn new(tables: Arc<ParseTables>) -> Self {
+        Self::with_actions(tables.clone(), TreeBuilder::new(tables))
+    }
+}
+
+impl<A: Actions> LR1Driver<A> {
+    pub fn with_actions(tables: Arc<ParseTables>, actions: A) -> Self {
+        Self {
+            tables,
+            actions,
+            tokens: Vec::new(),
+            source: String::new(),
+            error_list: Vec::new(),
+            status: Status::default(),
+            part_table: PartitionTable::default(),
+            brackets: Vec::new(),
+            repair_pos: None,
+            recovery: Recovery::default(),
+            discarded: None,
+            skipped: Vec::new(),
+            error_shifts: 0,
+            panicking: false,
+            eof: false,
+            accepted: false,
+            consumed: 0,
+            base: 0,
+            pos: 0,
+        }
+    }
+    pub fn set_recovery(&mut self, recovery: Recovery) {
+        self.recovery = recovery;
+    }
+    fn get_last_token(&self) -> &Element {
+        &self.tokens[if self.pos == 0 { 0 } else { self.pos - 1 }].kind
+    }
+    fn get_current_token(&self) -> &Element {
+        &self.tokens[self.pos.min(self.tokens.len() - 1)].kind
+    }
+    /// 当前记号在整个输入中的下标
+    fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+}
+
+impl<A: Actions> LR1Driver<A> {
+    pub fn construct_tree(self, input: &[Element]) -> ParseOutcome<A::Value> {
+        let (tokens, source) = Token::from_elements(input);
+        self.construct_tree_from_tokens(tokens, &source)
+    }
+
+    /// `tokens` 中的 span 指向 `source`，报错时据此输出源码片段
+    pub fn construct_tree_from_tokens(
+        mut self,
+        tokens: impl IntoIterator<Item = Token>,
+        source: &str,
+    ) -> ParseOutcome<A::Value> {
+        self.push_source(source);
+        self.parse(tokens)
+    }
+
+    /// 从任意记号来源（如惰性运行的词法分析器）拉取记号进行分析。
+    /// 每取一个记号就尽可能向前分析，出错恢复要向后看时先攒下后续的几个记号；
+    /// 记号来源总会被读完。已分析过的记号分批丢弃，缓冲的记号数不随输入变长而增长
+    pub fn parse(mut self, tokens: impl IntoIterator<Item = Token>) -> ParseOutcome<A::Value> {
+        for token in tokens {
+            self.push_token(token);
+        }
+        self.finish()
+    }
+
+    /// 追加一段源文本。流式输入时，记号的 span 指向所有追加过的文本拼接后的位置
+    pub fn push_source(&mut self, text: &str) {
+        self.source.push_str(text);
+    }
+
+    /// 输入一个记号，尽可能向前分析。出错恢复需要向后看几个记号，
+    /// 因此错误可能在之后的 `feed` 或 `finish` 中才报告。
+    /// 返回 `Err` 时分析器已经恢复，可以继续输入
+    pub fn feed(&mut self, token: Token) -> Result<FeedStatus, Vec<ParserError>> {
+        let reported = self.error_list.len();
+        self.push_token(token);
+        if self.error_list.len() > reported {
+            return Err(self.error_list[reported..].to_vec());
+        }
+        if self.pos == self.tokens.len()
+            && self
+                .tables
+                .can_shift(&self.status.states(), &Element::Terminal("#".to_string()))
+        {
+            Ok(FeedStatus::Complete)
+        } else {
+            Ok(FeedStatus::NeedMore)
+        }
+    }
+
+    fn push_token(&mut self, token: Token) {
+        self.tokens.push(token);
+        self.run();
+        self.compact();
+    }
+
+    /// 输入结束：补上 `#`，分析完剩余的记号并收集结果
+    pub fn finish(mut self) -> ParseOutcome<A::Value> {
+        let offset = self
+            .tokens
+            .last()
+            .map_or(0, |token| token.span.end)
+            .max(self.source.len());
+        self.tokens.push(Token::eof(offset));
+        self.eof = true;
+        self.run();
+        self.collect()
+    }
+
+    /// 分析到输入耗尽、接受，或出错恢复需要更多的前瞻记号为止
+    fn run(&mut self) {
+        while !self.accepted && self.pos < self.tokens.len() {
+            if self.tokens[self.pos].recovery == Some(NodeKind::Skipped) {
+                self.skip();
+                continue;
+            }
+            if self.panicking && !self.resync() {
+                self.skip();
+                self.track_bracket();
+                continue;
+            }
+            let state = self.status.state();
+            let symbol = self.tokens[self.pos].kind.clone();
+
+            let action = self.tables.action(state, &symbol).cloned();
+
+            match action {
+                Some(Action::Shift(state)) => {
+                    self.flush_skipped();
+                    let token = &self.tokens[self.pos];
+                    let value = if symbol == Element::Terminal(ERROR_TOKEN.to_string()) {
+                        let discarded = self.discarded.take().unwrap_or_default();
+                        self.actions.error(token, discarded)
+                    } else {
+                        self.error_shifts = self.error_shifts.saturating_sub(1);
+                        self.actions.shift(token)
+                    };
+                    self.status.push(state, Slot::Value(value));
+                    self.step_forward();
+                    self.try_partition();
+                }
+                Some(Action::Reduce(prod_head, prod_body)) => {
+                    let children = self.status.pop_children(prod_body.len());
+                    let prod = self.tables.prod_id(&prod_head, &prod_body);
+
+                    let state = self.status.state();
+                    let state = self.tables.goto(state, &prod_head).unwrap();
+
+                    let value = self.actions.reduce_slots(prod, children);
+                    self.status.push(state, Slot::Value(value));
+                }
+                Some(Action::Accept) => {
+                    self.accepted = true;
+                }
+                None => {
+                    // 只在移进 ε 之后能接受当前记号时才移进 ε；否则在移进之前的状态上报错，
+                    // 期望的记号才是完整的
+                    let epsilon = self
+                        .tables
+                        .action(state, &Element::Terminal("ε".to_string()));
+                    if let Some(&Action::Shift(t)) =
+                        epsilon.filter(|_| self.tables.can_shift(&self.status.states(), &symbol))
+                    {
+                        self.status.push_epsilon(t);
+                    } else if self.needs_lookahead() {
+                        break;
+                    } else {
+                        self.err_handle();
+                    }
+                }
+            }
+        }
+    }
+
+    /// 出错时尝试修复要先看到足够多的后续记号；输入还没结束就等下一次 `feed`
+    fn needs_lookahead(&self) -> bool {
+        let window = match self.recovery {
+            Recovery::Panic => REPAIR_LOOKAHEAD + 1,
+            Recovery::Repair => REPAIR_WINDOW,
+        };
+        !self.eof
+            && self.error_shifts == 0
+            && self.repair_pos != Some(self.offset())
+            && self.tokens.len() - self.pos < window
+    }
+
+    /// 恐慌模式下检查当前记号：遇到分隔符回退到最近的分区，
+    /// 遇到当前状态能接受的记号就继续分析。返回 false 表示跳过它
+    fn resync(&mut self) -> bool {
+        let state = self.status.state();
+        let symbol = self.tables.delimiters.classify(self.get_current_token());
+        if symbol.is_limiter() {
+            self.fallback();
+        } else if self
+            .tables
+            .action(state, self.get_current_token())
+            .is_none()
+        {
+            return false;
+        }
+        self.panicking = false;
+        true
+    }
+
+    /// 收集分析结果和错误。没能干净地结束时，由语义动作用栈中剩下的项构造结果
+    fn collect(mut self) -> ParseOutcome<A::Value> {
+        self.flush_skipped();
+        let mut slots = std::mem::take(&mut self.status).into_slots();
+        let tree = match slots.pop() {
+            Some(Slot::Value(value)) if self.accepted && slots.is_empty() => value,
+            last => {
+                slots.extend(last);
+                self.actions.recovered(self.accepted, slots)
+            }
+        };
+        ParseOutcome {
+            tree,
+            errors: self.error_list,
+            source: self.source,
+        }
+    }
+
+    fn skip(&mut self) {
+        let token = &self.tokens[self.pos];
+        if is_input(token) {
+            let value = self.actions.shift(token);
+            self.skipped.push(value);
+            self.consumed += 1;
+        }
+        self.pos += 1;
+    }
+    fn flush_skipped(&mut self) {
+        if !self.skipped.is_empty() {
+            let state = self.status.state();
+            let skipped = std::mem::take(&mut self.skipped);
+            self.status.push(state, Slot::Skipped(skipped));
+        }
+    }
+
+    fn err_handle(&mut self) {
+        // 刚用 error 产生式恢复过，不再报错，直接丢弃当前记号
+        let end = Element::Terminal("#".to_string());
+        if self.error_shifts > 0 && *self.get_current_token() != end {
+            self.skip();
+            return;
+        }
+
+        // 同一位置只尝试一次单记号修复，避免反复插入
+        if self.repair_pos != Some(self.offset()) {
+            self.repair_pos = Some(self.offset());
+            let repaired = match self.recovery {
+                Recovery::Panic => {
+                    self.try_delete_unmatched() || self.try_insert() || self.try_delete()
+                }
+                Recovery::Repair => self.try_repair(),
+            };
+            if repaired {
+                return;
+            }
+        }
+
+        let token = &self.tokens[self.pos];
+        self.error_list.push(ParserError::unexpected(
+            self.offset(),
+            token.span,
+            token.kind.clone(),
+            self.tables.get_exception_symbols(&self.status.states()),
+        ));
+
+        if self.try_error_production() {
+            return;
+        }
+
+        // 错误处理：跳过输入直到找到一个可以接受的符号
+        self.skip();
+        self.track_bracket();
+        self.panicking = true;
+    }
+    /// 多余的右括号：括号栈里没有与之配对的左括号，直接删除
+    fn try_delete_unmatched(&mut self) -> bool {
+        let current = self.tables.delimiters.classify(self.get_current_token());
+        if !current.is_closing()
+            || self
+                .brackets
+                .iter()
+                .any(|(open, _)| open.is_paired(current))
+        {
+            return false;
+        }
+        let token = self.tokens[self.pos].clone();
+        self.error_list.push(ParserError::extra(
+            self.offset(),
+            token.clone(),
+            format!(
+                "with no matching {}",
+                self.tables.delimiters.opening(current).unwrap()
+            ),
+        ));
+        self.skip();
+        true
+    }
+
+    /// 缺失错误：插入一个记号后，后续输入可以继续分析
+    fn try_insert(&mut self) -> bool {
+        let upcoming = self.upcoming(0);
+        let candidate = self
+            .tables
+            .get_exception_symbols(&self.status.states())
+            .into_iter()
+            .filter(|symbol| symbol != &Element::Terminal("#".to_string()))
+            .find(|symbol| {
+                self.tables.accepts(
+                    &self.status.states(),
+                    std::iter::once(symbol).chain(upcoming.iter()),
+                )
+            });
+        let Some(symbol) = candidate else {
+            return false;
+        };
+
+        let offset = if self.pos == 0 {
+            0
+        } else {
+            self.tokens[self.pos - 1].span.end
+        };
+        let inserted = Token::missing(symbol.clone(), Span::new(offset, offset));
+        let context = match self.brackets.last() {
+            Some((open, token)) if open.is_paired(self.tables.delimiters.classify(&symbol)) => {
+                let (line, column) = line_col(&self.source, token.span.start);
+                format!("to close {} at {}:{}", token.kind, line, column)
+            }
+            _ if self.pos == 0 => "at start of input".to_string(),
+            _ => format!("after {}", self.get_last_token()),
+        };
+        self.error_list.push(ParserError::missing(
+            self.offset(),
+            &inserted,
+            self.tokens[self.pos].kind.clone(),
+            context,
+        ));
+        self.tokens.insert(self.pos, inserted);
+        true
+    }
+
+    /// 多余错误：删掉当前记号后，后续输入可以继续分析
+    fn try_delete(&mut self) -> bool {
+        let upcoming = self.upcoming(1);
+        if upcoming.is_empty() || !self.tables.accepts(&self.status.states(), upcoming.iter()) {
+            return false;
+        }
+        let token = self.tokens[self.pos].clone();
+        let context = if self.pos == 0 {
+            "at start of input".to_string()
+        } else {
+            format!("after {}", self.get_last_token())
+        };
+        self.error_list
+            .push(ParserError::extra(self.offset(), token, context));
+        self.skip();
+        true
+    }
+
+    /// 按搜索到的修复序列改写输入，并为每一步修复报告一个错误
+    fn try_repair(&mut self) -> bool {
+        let window: Vec<Element> = self.tokens[self.pos..]
+            .iter()
+            .take(REPAIR_WINDOW)
+            .map(|token| token.kind.clone())
+            .collect();
+        let Some(repairs) = find_repair(&self.tables, &self.status.states(), &window) else {
+            return false;
+        };
+
+        let mut rewritten: Vec<Token> = Vec::new();
+        let mut errors = Vec::new();
+        let mut index = self.pos;
+        for repair in &repairs {
+            let error_pos = self.offset() + rewritten.len();
+            let last = rewritten
+                .last()
+                .or_else(|| self.pos.checked_sub(1).map(|i| &self.tokens[i]));
+            let context = match last {
+                Some(token) => format!("after {}", token.kind),
+                None => "at start of input".to_string(),
+            };
+            match repair {
+                Repair::Shift => {
+                    rewritten.push(self.tokens[index].clone());
+                    index += 1;
+                }
+                Repair::Insert(symbol) => {
+                    let offset = last.map_or(0, |token| token.span.end);
+                    let inserted = Token::missing(symbol.clone(), Span::new(offset, offset));
+                    errors.push(ParserError::missing(
+                        error_pos,
+                        &inserted,
+                        self.tokens[index].kind.clone(),
+                        context,
+                    ));
+                    rewritten.push(inserted);
+                }
+                Repair::Delete => {
+                    let mut token = self.tokens[index].clone();
+                    errors.push(ParserError::extra(error_pos, token.clone(), context));
+                    token.recovery = Some(NodeKind::Skipped);
+                    rewritten.push(token);
+                    index += 1;
+                }
+                Repair::Substitute(symbol) => {
+                    let token = &self.tokens[index];
+                    errors.push(ParserError::unexpected(
+                        error_pos,
+                        token.span,
+                        token.kind.clone(),
+                        vec![symbol.clone()],
+                    ));
+                    let mut replaced = token.clone();
+                    replaced.recovery = Some(NodeKind::Skipped);
+                    rewritten.push(replaced);
+                    rewritten.push(Token::missing(symbol.clone(), token.span));
+                    index += 1;
+                }
+            }
+        }
+
+        if let Some(first) = errors.first_mut() {
+            first.help = Some(format!(
+                "repaired by: {}",
+                describe_repairs(&repairs, &window)
+            ));
+        }
+        self.error_list.extend(errors);
+        self.tokens.splice(self.pos..index, rewritten);
+        true
+    }
+
+    /// yacc 式恢复：弹栈直到某个状态能移进 `error`，在当前位置插入 `error`。
+    /// 之后无法移进的记号被逐个丢弃，直到成功移进 `REPAIR_LOOKAHEAD` 个记号。
+    /// 弹出的节点挂在 error 节点下
+    fn try_error_production(&mut self) -> bool {
+        let error = Element::Terminal(ERROR_TOKEN.to_string());
+        // 已经在 `#` 处用过 error 产生式仍无法继续，避免反复插入
+        if !self.tables.has_error_productions || self.error_shifts > 0 {
+            return false;
+        }
+
+        let mut status = self.status.clone();
+        let mut discarded = Vec::new();
+        loop {
+            if self.tables.can_shift(&status.states(), &error) {
+                break;
+            }
+            if status.is_bottom() {
+                return false;
+            }
+            discarded.extend(status.pop());
+        }
+        discarded.reverse();
+        discarded.extend(
+            std::mem::take(&mut self.skipped)
+                .into_iter()
+                .map(Slot::Value),
+        );
+
+        let offset = self.tokens[self.pos].span.start;
+        self.status = status;
+        self.discarded = Some(discarded);
+        self.error_shifts = REPAIR_LOOKAHEAD;
+        self.tokens
+            .insert(self.pos, Token::new(error, "", Span::new(offset, offset)));
+        true
+    }
+
+    /// 从 `pos + skip` 开始，用来验证修复是否可行的几个记号
+    fn upcoming(&self, skip: usize) -> Vec<Element> {
+        self.tokens
+            .iter()
+            .skip(self.pos + skip)
+            .take(REPAIR_LOOKAHEAD)
+            .map(|token| token.kind.clone())
+            .collect()
+    }
+
+    fn step_forward(&mut self) {
+        if is_input(&self.tokens[self.pos]) {
+            self.consumed += 1;
+        }
+        self.pos += 1;
+        self.track_bracket();
+    }
+    /// 丢掉已经分析过的记号，只留上一个记号用于报错时定位
+    fn compact(&mut self) {
+        let done = self.pos.saturating_sub(1);
+        if done > 0 && done * 2 >= self.tokens.len() {
+            self.tokens.drain(..done);
+            self.pos -= done;
+            self.base += done;
+        }
+    }
+    fn track_bracket(&mut self) {
+        let Some(token) = self.tokens.get(self.pos - 1).cloned() else {
+            return;
+        };
+        let symbol = self.tables.delimiters.classify(&token.kind);
+        if symbol.is_opening() {
+            self.brackets.push((symbol, token));
+        } else if symbol.is_closing() {
+            if let Some(index) = self
+                .brackets
+                .iter()
+                .rposition(|(open, _)| open.is_paired(symbol))
+            {
+                self.brackets.truncate(index);
+            }
+        }
+    }
+    /// 回退到最近的分区，分区之后读入的记号都作为被跳过的输入。
+    /// 这些记号已经在分析栈中，由语义动作从栈顶往回取出，不需要保留原始输入
+    fn fallback(&mut self) {
+        let count = self.consumed - self.part_table.get_last_consumed();
+        let pending = Slot::Skipped(std::mem::take(&mut self.skipped));
+        let slots = std::iter::once(&pending).chain(self.status.slots());
+        self.skipped = self.actions.rewind(slots, count);
+        self.status = self.part_table.get_last_status();
+    }
+    fn try_partition(&mut self) {
+        let symbol = self.tables.delimiters.classify(self.get_last_token());
+        if symbol.is_delimiter() {
+            self.part_table.update(Partition {
+                partition_head: symbol,
+                status: self.status.clone(),
+                consumed: self.consumed,
+            })
+        }
+    }
+}
+
+/// 由输入产生的记号，不包括恢复时插入的记号和 `#`
+fn is_input(token: &Token) -> bool {
+    token.recovery != Some(NodeKind::Missing)
+        && token.kind != Element::Terminal(ERROR_TOKEN.to_string())
+        && token.kind != Element::Terminal("#".to_string())
+}
+
+#[derive(Debug)]
+struct PartitionTable<V> {
+    table: Vec<Partition<V>>,
+}
+impl<V> Default for PartitionTable<V> {
+    fn default() -> Self {
+        Self { table: Vec::new() }
+    }
+}
+impl<V> PartitionTable<V> {
+    fn get_last_token(&self) -> Delimiter {
+        if let Some(v) = self.table.last() {
+            v.partition_head
+        } else {
+            Delimiter::Default
+        }
+    }
+    fn get_last_consumed(&self) -> usize {
+        self.table.last().map_or(0, |v| v.consumed)
+    }
+    fn get_last_status(&self) -> Status<V> {
+        if let Some(v) = self.table.last() {
+            v.status.clone()
+        } else {
+            Status::default()
+        }
+    }
+    fn update(&mut self, partition: Partition<V>) {
+        let token = partition.partition_head;
+        match token {
+            Delimiter::Sync | Delimiter::Open(_) => {
+                if self.get_last_token() == Delimiter::Sync {
+                    self.table.pop();
+                    self.table.push(partition);
+                } else {
+                    self.table.push(partition);
+                }
+            }
+            Delimiter::Close(_) => {
+                if self.get_last_token() == Delimiter::Sync {
+                    self.table.pop();
+                    self.table.pop();
+                } else {
+                    self.table.pop();
+                }
+            }
+            Delimiter::Default => (),
+        }
+    }
+}
+
+/// 分析栈。用共享的链表实现，分区保存快照时不需要复制整个栈
+#[derive(Debug)]
+pub struct Status<V> {
+    top: Arc<Frame<V>>,
+}
+#[derive(Debug)]
+struct Frame<V> {
+    state: State,
+    /// 为 None 时是 ε 或栈底的占位
+    slot: Option<Slot<V>>,
+    below: Option<Arc<Frame<V>>>,
+}
+impl<V> Drop for Frame<V> {
+    // 栈可能很深，逐层释放以免递归过深
+    fn drop(&mut self) {
+        let mut below = self.below.take();
+        while let Some(frame) = below {
+            match Arc::try_unwrap(frame) {
+                Ok(mut frame) => below = frame.below.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+impl<V> Clone for Status<V> {
+    fn clone(&self) -> Self {
+        Self {
+            top: self.top.clone(),
+        }
+    }
+}
+impl<V> Default for Status<V> {
+    fn default() -> Self {
+        Self {
+            top: Arc::new(Frame {
+                state: 0,
+                slot: None,
+                below: None,
+            }),
+        }
+    }
+}
+impl<V: Clone> Status<V> {
+    fn state(&self) -> State {
+        self.top.state
+    }
+    fn is_bottom(&self) -> bool {
+        self.top.below.is_none()
+    }
+    fn push(&mut self, state: State, slot: Slot<V>) {
+        self.push_frame(state, Some(slot));
+    }
+    fn push_epsilon(&mut self, state: State) {
+        self.push_frame(state, None);
+    }
+    fn push_frame(&mut self, state: State, slot: Option<Slot<V>>) {
+        let below = self.top.clone();
+        self.top = Arc::new(Frame {
+            state,
+            slot,
+            below: Some(below),
+        });
+    }
+    /// 弹出栈顶项，ε 占位返回 None。栈项还被某个快照引用时复制一份
+    fn pop(&mut self) -> Option<Slot<V>> {
+        let below = self.top.below.clone().expect("pop the bottom of the stack");
+        match Arc::try_unwrap(std::mem::replace(&mut self.top, below)) {
+            Ok(mut frame) => frame.slot.take(),
+            Err(frame) => frame.slot.clone(),
+        }
+    }
+    /// 归约时弹出 `n` 个文法符号；夹在其间的 Skipped 项不计数，一并作为子项
+    fn pop_children(&mut self, n: usize) -> Vec<Slot<V>> {
+        let mut children = Vec::new();
+        let mut count = 0;
+        while count < n {
+            match self.pop() {
+                Some(slot @ Slot::Skipped(_)) => children.push(slot),
+                Some(slot) => {
+                    count += 1;
+                    children.push(slot);
+                }
+                None => count += 1,
+            }
+        }
+        children.reverse();
+        children
+    }
+    /// 从栈顶往下的栈项，不含 ε 和栈底
+    fn slots(&self) -> impl Iterator<Item = &Slot<V>> {
+        std::iter::successors(Some(&self.top), |frame| frame.below.as_ref())
+            .filter_map(|frame| frame.slot.as_ref())
+    }
+    /// 从栈底往上的栈项，不含 ε 和栈底
+    fn into_slots(mut self) -> Vec<Slot<V>> {
+        let mut slots = Vec::new();
+        while !self.is_bottom() {
+            slots.extend(self.pop());
+        }
+        slots.reverse();
+        slots
+    }
+    /// 状态栈中去掉 Skipped 项占位的状态，用于模拟分析
+    fn states(&self) -> Vec<State> {
+        let mut states: Vec<State> =
+            std::iter::successors(Some(&self.top), |frame| frame.below.as_ref())
+                .filter(|frame| !matches!(frame.slot, Some(Slot::Skipped(_))))
+                .map(|frame| frame.state)
+                .collect();
+        states.reverse();
+        states
+    }
+}
+#[derive(Debug)]
+pub struct Partition<V> {
+    partition_head: Delimiter,
+    status: Status<V>,
+    /// 分区建立时已读入的记号数
+    consumed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// 语句分隔符，如 ";"
+    Sync,
+    /// 第 n 对括号的左半边，如 "("、"begin"
+    Open(usize),
+    /// 第 n 对括号的右半边，如 ")"、"end"
+    Close(usize),
+    Default,
+}
+impl Delimiter {
+    fn is_delimiter(&self) -> bool {
+        self != &Self::Default
+    }
+    fn is_opening(&self) -> bool {
+        matches!(self, Delimiter::Open(_))
+    }
+    fn is_closing(&self) -> bool {
+        matches!(self, Delimiter::Close(_))
+    }
+    fn is_paired(&self, close: Delimiter) -> bool {
+        matches!((self, close), (Delimiter::Open(a), Delimiter::Close(b)) if *a == b)
+    }
+    fn is_limiter(&self) -> bool {
+        matches!(self, Delimiter::Sync | Delimiter::Close(_))
+    }
+}
+
+/// 分区恢复使用的分隔符，由文法中的 `%sync` 和 `%pair` 声明。
+/// 文法没有声明时，使用类 C 语言的 `;` 与 `()`、`{}`、`[]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimiters {
+    sync: Vec<Element>,
+    pairs: Vec<(Element, Element)>,
+}
+impl Default for Delimiters {
+    fn default() -> Self {
+        let terminal = |v: &str| Element::Terminal(v.to_string());
+        Self {
+            sync: vec![terminal("';'")],
+            pairs: vec![
+                (terminal("'('"), terminal("')'")),
+                (terminal("'{'"), terminal("'}'")),
+                (terminal("'['"), terminal("']'")),
+            ],
+        }
+    }
+}
+impl Delimiters {
+    pub fn new(grammar: &Grammar) -> Self {
+        if grammar.sync_list.is_empty() && grammar.pair_list.is_empty() {
+            return Self::default();
+        }
+        let terminal = |v: &String| Element::Terminal(v.clone());
+        Self {
+            sync: grammar.sync_list.iter().map(terminal).collect(),
+            pairs: grammar
+                .pair_list
+                .iter()
+                .map(|(open, close)| (terminal(open), terminal(close)))
+                .collect(),
+        }
+    }
+    pub(crate) fn classify(&self, symbol: &Element) -> Delimiter {
+        if self.sync.contains(symbol) {
+            return Delimiter::Sync;
+        }
+        for (i, (open, close)) in self.pairs.iter().enumerate() {
+            if open == symbol {
+                return Delimiter::Open(i);
+            }
+            if close == symbol {
+                return Delimiter::Close(i);
+            }
+        }
+        Delimiter::Default
+    }
+    fn opening(&self, close: Delimiter) -> Option<&Element> {
+        match close {
+            Delimiter::Close(i) => Some(&self.pairs[i].0),
+            _ => None,
+        }
+    }
+}
+impl<V> Default for Partition<V> {
+    fn default() -> Self {
+        Self {
+            partition_head: Delimiter::Default,
+            status: Default::default(),
+            consumed: 0,
+        }
+    }
+}
+
+/// `feed` 之后分析器的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedStatus {
+    /// 目前的输入还不完整，需要继续输入
+    NeedMore,
+    /// 目前的输入已经可以作为完整的输入结束，如 REPL 中一条语句已输入完
+    Complete,
+}
+
+/// 一次分析的结果：尽力构造的语法树，以及分析中发现的全部错误
+#[derive(Debug)]
+pub struct ParseOutcome<T = TreeNode> {
+    pub tree: T,
+    pub errors: Vec<ParserError>,
+    source: String,
+}
+impl<T> ParseOutcome<T> {
+    pub(crate) fn new(tree: T, errors: Vec<ParserError>, source: String) -> Self {
+        Self {
+            tree,
+            errors,
+            source,
+        }
+    }
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+    /// 转换分析结果，保留错误和源文本
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ParseOutcome<U> {
+        ParseOutcome {
+            tree: f(self.tree),
+            errors: self.errors,
+            source: self.source,
+        }
+    }
+}
+
+impl<T: Display> Display for ParseOutcome<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tree)?;
+        for i in self.errors.iter() {
+            writeln!(f, "{}", i.render("input", &self.source))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::diagnostic::ErrorType;
+    use crate::parser::{Grammar, LR1Parser};
+
+    fn tables(grammar: &Grammar) -> Arc<ParseTables> {
+        Arc::new(LR1Parser::from_grammar(grammar).into_tables())
+    }
+
+    /// 第一个“意外符号”错误中期望的记号
+    fn expected(outcome: &ParseOutcome) -> Vec<String> {
+        let error = outcome
+            .errors
+            .iter()
+            .find(|error| matches!(error.error_type, ErrorType::Unexpected(_)))
+            .unwrap();
+        let mut expected: Vec<String> = error.expected.iter().map(|v| v.to_string()).collect();
+        expected.sort();
+        expected
+    }
+
+    #[test]
+    fn expected_tokens_are_collected_before_epsilon_shift() {
+        // `L` 可以推出 ε：出错时如果先移进了 ε，期望的记号就只剩下 ε 之后的 `]`
+        let grammar: Grammar = "%token x\nCompUnit': S ;\nS: '[' L ']' ;\nL: x L | ;"
+            .parse()
+            .unwrap();
+        let outcome =
+            LR1Driver::new(tables(&grammar)).construct_tree(&Element::terminals("'[' y y"));
+        assert_eq!(expected(&outcome), ["']'", "x"]);
+    }
+
+    #[test]
+    fn expected_tokens_in_g2_initializer() {
+        let mut grammar = Grammar::new();
+        grammar
+            .grammar_load(concat!(env!("CARGO_MANIFEST_DIR"), "/g2.txt"))
+            .unwrap();
+        let input = Element::terminals("'const' 'int' Ident '=' '{' errIntConst ';'");
+        let outcome = LR1Driver::new(tables(&grammar)).construct_tree(&input);
+        assert_eq!(
+            expected(&outcome),
+            [
+                "'!'",
+                "'('",
+                "'+'",
+                "'{'",
+                "'}'",
+                "'−'",
+                "FloatConst",
+                "Ident",
+                "IntConst"
+            ]
+        );
+    }
+
+    fn brackets() -> Grammar {
+        "%token x\nCompUnit': S ;\nS: '[' L ']' ;\nL: x L | ;"
+            .parse()
+            .unwrap()
+    }
+
+    fn token(kind: &str) -> Token {
+        Token::new(Element::Terminal(kind.to_string()), kind, Span::default())
+    }
+
+    #[test]
+    fn tables_are_shared_across_threads() {
+        let tables = tables(&brackets());
+        let inputs = ["'[' ']'", "'[' x x ']'", "'[' x", "x ']'"];
+        let expected: Vec<String> = inputs
+            .iter()
+            .map(|input| {
+                LR1Driver::new(tables.clone())
+                    .construct_tree(&Element::terminals(input))
+                    .to_string()
+            })
+            .collect();
+        let handles: Vec<_> = inputs
+            .iter()
+            .map(|input| {
+                let tables = tables.clone();
+                let input = Element::terminals(input);
+                std::thread::spawn(move || {
+                    LR1Driver::new(tables).construct_tree(&input).to_string()
+                })
+            })
+            .collect();
+        let found: Vec<String> = handles.into_iter().map(|v| v.join().unwrap()).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn feed_reports_when_the_input_is_complete() {
+        let mut driver = LR1Driver::new(tables(&brackets()));
+        for kind in ["'['", "x", "x"] {
+            assert_eq!(
+                driver.feed(token(kind)).ok(),
+                Some(FeedStatus::NeedMore),
+                "{}",
+                kind
+            );
+        }
+        assert_eq!(driver.feed(token("']'")).ok(), Some(FeedStatus::Complete));
+        let outcome = driver.finish();
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.tree.element, Element::NotTerminal("S".to_string()));
+
+        // 空串也是完整的输入，每输入一个 x 仍然完整
+        let list: Grammar = "%token x\nCompUnit': L ;\nL: x L | ;".parse().unwrap();
+        let mut driver = LR1Driver::new(tables(&list));
+        for _ in 0..3 {
+            assert_eq!(driver.feed(token("x")).ok(), Some(FeedStatus::Complete));
+        }
+        assert!(driver.finish().errors.is_empty());
+    }
+
+    #[test]
+    fn feed_reports_errors_after_lookahead() {
+        let mut driver = LR1Driver::new(tables(&brackets()));
+        let mut reported = Vec::new();
+        for (i, kind) in ["'['", "x", "y", "x", "x", "x", "']'"]
+            .into_iter()
+            .enumerate()
+        {
+            match driver.feed(token(kind)) {
+                Ok(_) => (),
+                Err(errors) => reported.push((i, errors.len())),
+            }
+        }
+        // 多余的 y 要等看过后面的记号，确定删掉它可以继续分析才报告
+        assert_eq!(reported, [(2 + REPAIR_LOOKAHEAD, 1)]);
+        assert_eq!(driver.finish().errors.len(), 1);
+    }
+
+    #[test]
+    fn token_buffer_stays_bounded() {
+        for recovery in [Recovery::Panic, Recovery::Repair] {
+            let mut driver = LR1Driver::new(tables(&brackets()));
+            driver.set_recovery(recovery);
+            let mut longest = 0;
+            let _ = driver.feed(token("'['"));
+            for i in 0..10_000 {
+                // 中间夹着错误，恢复时也不会一直攒着记号
+                let kind = if i % 1000 == 999 { "'['" } else { "x" };
+                let _ = driver.feed(token(kind));
+                longest = longest.max(driver.tokens.len());
+            }
+            assert!(
+                longest <= 2 * (REPAIR_WINDOW + 2),
+                "{:?}: {}",
+                recovery,
+                longest
+            );
+            assert!(!driver.finish().errors.is_empty());
+        }
+    }
+
+    const STATEMENTS: &str = "%token x\nCompUnit': L ;\nL: L S | ;\nS: x '=' x ';' ;";
+
+    fn parse(grammar: &str, input: &str) -> ParseOutcome {
+        let grammar: Grammar = grammar.parse().unwrap();
+        LR1Driver::new(tables(&grammar)).construct_tree(&Element::terminals(input))
+    }
+
+    /// 树中 `kind` 类的节点
+    fn nodes(tree: &TreeNode, kind: NodeKind) -> Vec<&TreeNode> {
+        let mut found = Vec::new();
+        let mut stack = vec![tree];
+        while let Some(node) = stack.pop() {
+            if node.kind == kind {
+                found.push(node);
+            }
+            stack.extend(node.children.iter().flatten().rev());
+        }
+        found
+    }
+
+    #[test]
+    fn missing_and_extra_tokens_are_classified() {
+        let outcome = parse(STATEMENTS, "x '=' x x '=' x ';'");
+        let [error] = outcome.errors.as_slice() else {
+            panic!("{:?}", outcome.errors)
+        };
+        assert!(
+            matches!(&error.error_type, ErrorType::Missing(v) if v == "';'"),
+            "{:?}",
+            error
+        );
+        let missing = nodes(&outcome.tree, NodeKind::Missing);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].element, Element::Terminal("';'".to_string()));
+
+        let outcome = parse(STATEMENTS, "x '=' '=' x ';'");
+        let [error] = outcome.errors.as_slice() else {
+            panic!("{:?}", outcome.errors)
+        };
+        assert!(
+            matches!(&error.error_type, ErrorType::Extra(v) if v == "'='"),
+            "{:?}",
+            error
+        );
+        assert_eq!(nodes(&outcome.tree, NodeKind::Skipped).len(), 1);
+
+        // 没有配对的右括号直接删除
+        let outcome = parse(STATEMENTS, "x '=' x ')' ';'");
+        let [error] = outcome.errors.as_slice() else {
+            panic!("{:?}", outcome.errors)
+        };
+        assert!(
+            matches!(&error.error_type, ErrorType::Extra(v) if v == "')'"),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.context.as_deref(), Some("with no matching '('"));
+    }
+
+    #[test]
+    fn error_production_absorbs_the_bad_statement() {
+        let grammar = "%token x\nCompUnit': L ;\nL: L S | ;\nS: x ';' | error ';' ;";
+        let outcome = parse(grammar, "x ';' y y y ';' x ';'");
+        assert_eq!(outcome.errors.len(), 1);
+        // 出错的语句归约成 `S: error ';'`，被跳过的记号留在其中，之后的语句照常分析
+        let [error] = nodes(&outcome.tree, NodeKind::Error)[..] else {
+            panic!("{}", outcome.tree)
+        };
+        assert_eq!(error.element, Element::Terminal(ERROR_TOKEN.to_string()));
+        let skipped = nodes(&outcome.tree, NodeKind::Skipped);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].children.as_ref().map(Vec::len), Some(3));
+        let statements = nodes(&outcome.tree, NodeKind::Normal)
+            .into_iter()
+            .filter(|node| node.element == Element::NotTerminal("S".to_string()))
+            .count();
+        assert_eq!(statements, 3);
+    }
+
+    #[test]
+    fn declared_delimiters_replace_the_defaults() {
+        let grammar = "%token x NL\n%sync NL\n%pair 'begin' 'end'\nCompUnit': L ;\nL: L S | ;\nS: x '=' x NL | 'begin' L 'end' ;";
+        let terminal = |v: &str| Element::Terminal(v.to_string());
+        let delimiters = Delimiters::new(&grammar.parse().unwrap());
+        assert_eq!(delimiters.classify(&terminal("NL")), Delimiter::Sync);
+        assert_eq!(
+            delimiters.classify(&terminal("'begin'")),
+            Delimiter::Open(0)
+        );
+        assert_eq!(delimiters.classify(&terminal("'end'")), Delimiter::Close(0));
+        assert_eq!(delimiters.classify(&terminal("';'")), Delimiter::Default);
+        assert_eq!(delimiters.classify(&terminal("'('")), Delimiter::Default);
+
+        let defaults = Delimiters::new(&STATEMENTS.parse().unwrap());
+        assert_eq!(defaults, Delimiters::default());
+        assert_eq!(defaults.classify(&terminal("';'")), Delimiter::Sync);
+        assert_eq!(defaults.classify(&terminal("'{'")), Delimiter::Open(1));
+        assert_eq!(defaults.classify(&terminal("']'")), Delimiter::Close(2));
+
+        // 多出的 'end' 按声明的配对报告并删除，后面的语句照常分析
+        let outcome = parse(grammar, "x '=' x NL 'end' x '=' x NL");
+        let [error] = outcome.errors.as_slice() else {
+            panic!("{:?}", outcome.errors)
+        };
+        assert!(
+            matches!(&error.error_type, ErrorType::Extra(v) if v == "'end'"),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.context.as_deref(), Some("with no matching 'begin'"));
+        let skipped = nodes(&outcome.tree, NodeKind::Skipped);
+        assert_eq!(skipped.len(), 1);
+        let statements = nodes(&outcome.tree, NodeKind::Normal)
+            .into_iter()
+            .filter(|node| node.element == Element::NotTerminal("S".to_string()))
+            .count();
+        assert_eq!(statements, 2);
+    }
+
+    #[test]
+    fn best_effort_tree_keeps_every_token() {
+        let input = "x '=' x ';' x x x x ';' x '=' x ';'";
+        let outcome = parse(STATEMENTS, input);
+        assert_eq!(outcome.errors.len(), 2, "{:?}", outcome.errors);
+        // 无法分析的记号收进一个跳过节点，前后两条语句都完整地留在树中
+        let [skipped] = nodes(&outcome.tree, NodeKind::Skipped)[..] else {
+            panic!("{}", outcome.tree)
+        };
+        assert_eq!(skipped.children.as_ref().map(Vec::len), Some(5));
+        let statements = nodes(&outcome.tree, NodeKind::Normal)
+            .into_iter()
+            .filter(|node| node.element == Element::NotTerminal("S".to_string()))
+            .count();
+        assert_eq!(statements, 2);
+
+        // 树的叶子按顺序还原出全部输入
+        let mut leaves = Vec::new();
+        let mut stack = vec![&outcome.tree];
+        while let Some(node) = stack.pop() {
+            match &node.children {
+                Some(children) => stack.extend(children.iter().rev()),
+                None => leaves.push(node.element.clone()),
+            }
+        }
+        leaves.retain(|v| *v != Element::Terminal("ε".to_string()));
+        assert_eq!(leaves, Element::terminals(input));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::parser::diagnostic::ParserError;
+use crate::parser::driver::ParseOutcome;
+use crate::parser::forest::{Forest, ForestId, ForestNode};
+use crate::parser::lexer::Lexer;
+use crate::parser::lr1_parser::{NodeKind, ProdId, TreeNode};
+use crate::parser::types::{Element, Token};
+use crate::parser::Grammar;
+use std::collections::{HashMap, HashSet};
+
+/// Earley 项目：产生式、点的位置和项目开始的记号位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EarleyItem {
+    prod: ProdId,
+    dot: usize,
+    origin: usize,
+}
+
+/// 一个记号位置上的全部项目
+#[derive(Debug, Default)]
+struct EarleySet {
+    items: Vec<EarleyItem>,
+    seen: HashSet<EarleyItem>,
+}
+impl EarleySet {
+    fn add(&mut self, item: EarleyItem) {
+        if self.seen.insert(item) {
+            self.items.push(item);
+        }
+    }
+    fn contains(&self, item: &EarleyItem) -> bool {
+        self.seen.contains(item)
+    }
+}
+
+/// Earley 分析器：不需要分析表，能分析任意上下文无关文法（包括左递归和有歧义的文法），
+/// 结果是与 GLR 相同的共享压缩分析森林
+#[derive(Debug, Clone)]
+pub struct EarleyParser {
+    start_symbol: Element,
+    /// 右部不含 ε
+    productions: Vec<(Element, Vec<Element>)>,
+    by_head: HashMap<Element, Vec<ProdId>>,
+    nullable: HashSet<Element>,
+    generated: HashSet<Element>,
+    /// 文法中的终结符（不含 ε）和 `#`，按名字排列
+    terminals: Vec<Element>,
+}
+
+impl EarleyParser {
+    pub fn new(grammar: &Grammar) -> Self {
+        let epsilon = Element::Terminal("ε".to_string());
+        let productions: Vec<(Element, Vec<Element>)> = grammar
+            .productions()
+            .into_iter()
+            .map(|(head, body)| {
+                let body = body.into_iter().filter(|v| *v != epsilon).collect();
+                (head, body)
+            })
+            .collect();
+
+        let mut by_head: HashMap<Element, Vec<ProdId>> = HashMap::new();
+        for (id, (head, _)) in productions.iter().enumerate() {
+            by_head.entry(head.clone()).or_default().push(id);
+        }
+
+        let mut nullable = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (head, body) in &productions {
+                if !nullable.contains(head) && body.iter().all(|v| nullable.contains(v)) {
+                    nullable.insert(head.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        let mut terminals: Vec<Element> = productions
+            .iter()
+            .flat_map(|(_, body)| body)
+            .filter(|v| matches!(v, Element::Terminal(_)))
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .chain([Element::Terminal("#".to_string())])
+            .collect();
+        terminals.sort_by_key(|symbol| symbol.to_string());
+
+        Self {
+            start_symbol: grammar.start_symbol.clone(),
+            productions,
+            by_head,
+            nullable,
+            generated: grammar.generated(),
+            terminals,
+        }
+    }
+
+    /// 文法中的终结符（不含 ε），可以用来构造 `Lexer`
+    pub fn terminals(&self) -> &[Element] {
+        &self.terminals
+    }
+
+    pub fn construct_forest(&self, input: &[Element]) -> ParseOutcome<Forest> {
+        let (tokens, source) = Token::from_elements(input);
+        self.construct_forest_from_tokens(tokens, &source)
+    }
+
+    /// 用文法中的终结符切分 `source` 后分析
+    pub fn parse_source(&self, source: &str) -> ParseOutcome<Forest> {
+        let (tokens, _) = Lexer::from_terminals(&self.terminals).tokenize(source);
+        self.construct_forest_from_tokens(tokens, source)
+    }
+
+    /// 只要一棵语法树时的便捷写法：每处歧义取产生式编号最小的推导
+    pub fn construct_tree(&self, input: &[Element]) -> ParseOutcome {
+        let start_symbol = self.start_symbol.clone();
+        self.construct_forest(input).map(|forest| {
+            forest.tree().unwrap_or(TreeNode {
+                element: start_symbol,
+                children: Some(Vec::new()),
+                kind: NodeKind::Error,
+            })
+        })
+    }
+
+    /// `tokens` 中的 span 指向 `source`，报错时据此输出源码片段。
+    /// 出错时跳过无法匹配的记号，被跳过的记号不出现在森林中
+    pub fn construct_forest_from_tokens(
+        &self,
+        tokens: impl IntoIterator<Item = Token>,
+        source: &str,
+    ) -> ParseOutcome<Forest> {
+        let mut tokens: Vec<Token> = tokens.into_iter().collect();
+        let offset = tokens
+            .last()
+            .map_or(0, |token| token.span.end)
+            .max(source.len());
+        let eof = Token::eof(offset);
+        let count = tokens.len();
+
+        let mut errors = Vec::new();
+        // 被接受的记号在原输入中的下标
+        let mut indices: Vec<usize> = (0..tokens.len()).collect();
+        let mut chart = vec![EarleySet::default()];
+        for &prod in self.by_head.get(&self.start_symbol).into_iter().flatten() {
+            chart[0].add(EarleyItem {
+                prod,
+                dot: 0,
+                origin: 0,
+            });
+        }
+
+        let mut pos = 0;
+        let mut recovering = false;
+        loop {
+            self.close(&mut chart, pos);
+            if pos == tokens.len() {
+                break;
+            }
+            let next = self.scan(&chart[pos], &tokens[pos].kind);
+            if next.items.is_empty() {
+                // 没有项目能匹配当前记号：报错并跳过它
+                if !recovering {
+                    errors.push(self.unexpected(&chart[pos], indices[pos], &tokens[pos]));
+                }
+                recovering = true;
+                tokens.remove(pos);
+                indices.remove(pos);
+                continue;
+            }
+            chart.push(next);
+            recovering = false;
+            pos += 1;
+        }
+
+        let mut forest = Forest::new(self.generated.clone());
+        let accepted = chart[pos].items.iter().any(|item| {
+            item.origin == 0
+                && self.productions[item.prod].0 == self.start_symbol
+                && item.dot == self.productions[item.prod].1.len()
+        });
+        if accepted {
+            let mut builder = ForestBuilder {
+                parser: self,
+                chart: &chart,
+                tokens: &tokens,
+                indices: &indices,
+                completed: self.completed(&chart),
+                built: HashSet::new(),
+            };
+            let root = builder.symbol(&mut forest, &self.start_symbol, 0, pos);
+            forest.set_root(Some(self.unwrap_start(&forest, root)));
+        } else if !recovering {
+            errors.push(self.unexpected(&chart[pos], count, &eof));
+        }
+        ParseOutcome::new(forest, errors, source.to_string())
+    }
+
+    /// 预测和归约，直到 `pos` 处的项目集不再变化。
+    /// 点后是可空的非终结符时直接越过它，因此 ε 的归约不会遗漏
+    fn close(&self, chart: &mut [EarleySet], pos: usize) {
+        let mut i = 0;
+        while i < chart[pos].items.len() {
+            let item = chart[pos].items[i];
+            let (head, body) = &self.productions[item.prod];
+            match body.get(item.dot) {
+                Some(symbol @ Element::NotTerminal(_)) => {
+                    for &prod in self.by_head.get(symbol).into_iter().flatten() {
+                        chart[pos].add(EarleyItem {
+                            prod,
+                            dot: 0,
+                            origin: pos,
+                        });
+                    }
+                    if self.nullable.contains(symbol) {
+                        chart[pos].add(EarleyItem {
+                            dot: item.dot + 1,
+                            ..item
+                        });
+                    }
+                }
+                Some(Element::Terminal(_)) => {}
+                None => {
+                    let waiting: Vec<EarleyItem> = chart[item.origin]
+                        .items
+                        .iter()
+                        .filter(|v| self.productions[v.prod].1.get(v.dot) == Some(head))
+                        .map(|v| EarleyItem {
+                            dot: v.dot + 1,
+                            ..*v
+                        })
+                        .collect();
+                    for v in waiting {
+                        chart[pos].add(v);
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    fn scan(&self, set: &EarleySet, symbol: &Element) -> EarleySet {
+        let mut next = EarleySet::default();
+        for item in &set.items {
+            if self.productions[item.prod].1.get(item.dot) == Some(symbol) {
+                next.add(EarleyItem {
+                    dot: item.dot + 1,
+                    ..*item
+                });
+            }
+        }
+        next
+    }
+
+    fn unexpected(&self, set: &EarleySet, index: usize, token: &Token) -> ParserError {
+        let mut expected: HashSet<&Element> = set
+            .items
+            .iter()
+            .filter_map(|item| self.productions[item.prod].1.get(item.dot))
+            .filter(|symbol| matches!(symbol, Element::Terminal(_)))
+            .collect();
+        let accepted = set.items.iter().any(|item| {
+            item.origin == 0
+                && self.productions[item.prod].0 == self.start_symbol
+                && item.dot == self.productions[item.prod].1.len()
+        });
+        let end = Element::Terminal("#".to_string());
+        if accepted {
+            expected.insert(&end);
+        }
+        let expected = self
+            .terminals
+            .iter()
+            .filter(|symbol| expected.contains(symbol))
+            .cloned()
+            .collect();
+        ParserError::unexpected(index, token.span, token.kind.clone(), expected)
+    }
+
+    /// 每个位置上已经完成的 (非终结符, 开始位置)
+    fn completed(&self, chart: &[EarleySet]) -> Vec<HashSet<(Element, usize)>> {
+        chart
+            .iter()
+            .map(|set| {
+                set.items
+                    .iter()
+                    .filter(|item| item.dot == self.productions[item.prod].1.len())
+                    .map(|item| (self.productions[item.prod].0.clone(), item.origin))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// 与 LR 分析一致，根节点是增广前的开始符号
+    fn unwrap_start(&self, forest: &Forest, root: ForestId) -> ForestId {
+        match forest.node(root) {
+            ForestNode::Symbol { families, .. }
+                if families.len() == 1 && families[0].children.len() == 1 =>
+            {
+                families[0].children[0]
+            }
+            _ => root,
+        }
+    }
+}
+
+/// 由识别完的项目集构造森林
+struct ForestBuilder<'a> {
+    parser: &'a EarleyParser,
+    chart: &'a [EarleySet],
+    tokens: &'a [Token],
+    indices: &'a [usize],
+    completed: Vec<HashSet<(Element, usize)>>,
+    built: HashSet<ForestId>,
+}
+
+/// 候选式右部中一个符号匹配的输入
+#[derive(Clone)]
+enum Part {
+    Token(usize),
+    Symbol(Element, usize, usize),
+}
+
+impl ForestBuilder<'_> {
+    /// `head` 在 `[start, end)` 上的节点，第一次遇到时添加它的全部推导
+    fn symbol(
+        &mut self,
+        forest: &mut Forest,
+        head: &Element,
+        start: usize,
+        end: usize,
+    ) -> ForestId {
+        let id = forest.symbol(head.clone(), start, end);
+        if !self.built.insert(id) {
+            return id;
+        }
+        let prods: Vec<ProdId> = self.parser.by_head[head]
+            .iter()
+            .copied()
+            .filter(|&prod| {
+                self.chart[end].contains(&EarleyItem {
+                    prod,
+                    dot: self.parser.productions[prod].1.len(),
+                    origin: start,
+                })
+            })
+            .collect();
+        for prod in prods {
+            let body_len = self.parser.productions[prod].1.len();
+            for parts in self.splits(prod, body_len, start, end) {
+                let children = parts
+                    .into_iter()
+                    .map(|part| match part {
+                        Part::Token(pos) => {
+                            forest.token(self.indices[pos], self.tokens[pos].kind.clone())
+                        }
+                        Part::Symbol(symbol, from, to) => self.symbol(forest, &symbol, from, to),
+                    })
+                    .collect();
+                forest.add_family(id, prod, children);
+            }
+        }
+        id
+    }
+
+    /// 右部前 `dot` 个符号匹配 `[start, end)` 的所有切分方式
+    fn splits(&self, prod: ProdId, dot: usize, start: usize, end: usize) -> Vec<Vec<Part>> {
+        if dot == 0 {
+            return if start == end {
+                vec![Vec::new()]
+            } else {
+                Vec::new()
+            };
+        }
+        let symbol = &self.parser.productions[prod].1[dot - 1];
+        let before = |k: usize| {
+            self.chart[k].contains(&EarleyItem {
+                prod,
+                dot: dot - 1,
+                origin: start,
+            })
+        };
+        let mut result = Vec::new();
+        match symbol {
+            Element::Terminal(_) => {
+                if end > start && self.tokens[end - 1].kind == *symbol && before(end - 1) {
+                    for mut parts in self.splits(prod, dot - 1, start, end - 1) {
+                        parts.push(Part::Token(end - 1));
+                        result.push(parts);
+                    }
+                }
+            }
+            Element::NotTerminal(_) => {
+                for k in start..=end {
+                    if !before(k) || !self.completed[end].contains(&(symbol.clone(), k)) {
+                        continue;
+                    }
+                    for mut parts in self.splits(prod, dot - 1, start, k) {
+                        parts.push(Part::Symbol(symbol.clone(), k, end));
+                        result.push(parts);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{GLRDriver, GLRTables, LR1Parser};
+    use std::sync::Arc;
+
+    /// 同一输入上 Earley 和 GLR 得到的森林有相同的树
+    fn assert_same_as_glr(grammar: &str, inputs: &[&str]) {
+        let grammar: Grammar = grammar.parse().unwrap();
+        let earley = EarleyParser::new(&grammar);
+        let glr = GLRDriver::new(Arc::new(GLRTables::new(
+            &LR1Parser::from_grammar(&grammar),
+            &grammar,
+        )));
+        for input in inputs {
+            let input = Element::terminals(input);
+            let expected = glr.construct_forest(&input);
+            let outcome = earley.construct_forest(&input);
+            assert_eq!(outcome.errors.len(), expected.errors.len(), "{:?}", input);
+            let (forest, expected) = (outcome.tree, expected.tree);
+            assert_eq!(forest.tree_count(), expected.tree_count(), "{:?}", input);
+            assert_eq!(forest.tree(), expected.tree(), "{:?}", input);
+            let mut trees = forest.trees(64);
+            let mut expected_trees = expected.trees(64);
+            trees.sort_by_key(|tree| format!("{:?}", tree));
+            expected_trees.sort_by_key(|tree| format!("{:?}", tree));
+            assert_eq!(trees, expected_trees, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn ambiguous_grammar_matches_glr() {
+        assert_same_as_glr(
+            "%token a\nCompUnit': E ;\nE: E '+' E | E '*' E | a ;",
+            &[
+                "a",
+                "a '+' a",
+                "a '+' a '*' a",
+                "a '+' a '+' a '+' a",
+                "a '*' a '+' a '*' a",
+                "a '+'",
+            ],
+        );
+        let sums = EarleyParser::new(
+            &"%token a\nCompUnit': E ;\nE: E '+' E | a ;"
+                .parse()
+                .unwrap(),
+        );
+        let forest = sums
+            .construct_forest(&Element::terminals("a '+' a '+' a '+' a"))
+            .tree;
+        assert_eq!(forest.tree_count(), Some(5));
+    }
+
+    #[test]
+    fn nullable_grammar_matches_glr() {
+        assert_same_as_glr(
+            "%token a\nCompUnit': E ;\nE: E '+' E | a | ;",
+            &["", "a", "'+'", "a '+' '+' a", "'+' a '+'"],
+        );
+        assert_same_as_glr(
+            "%token a b\nCompUnit': S ;\nS: A S B | a ;\nA: | a ;\nB: | b ;",
+            &["a", "a a", "a b", "a a b b", "a a a b"],
+        );
+    }
+
+    #[test]
+    fn cyclic_grammar_has_infinitely_many_trees() {
+        let grammar: Grammar = "%token a\nCompUnit': S ;\nS: S A | a ;\nA: ;"
+            .parse()
+            .unwrap();
+        let forest = EarleyParser::new(&grammar)
+            .construct_forest(&Element::terminals("a"))
+            .tree;
+        assert_eq!(forest.tree_count(), None);
+        assert_eq!(
+            forest.tree().unwrap().element,
+            Element::NotTerminal("S".to_string())
+        );
+    }
+}
@@ -0,0 +1,661 @@
+use crate::parser::grammar::Grammar;
+use crate::parser::lr1_parser::ERROR_TOKEN;
+use crate::parser::types::{Element, Item, PBody, PHead};
+use std::collections::{HashMap, HashSet};
+
+/// 还原了 EBNF 运算符的产生式右部
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Terminal(String),
+    NonTerminal(String),
+    Seq(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    /// 一次或多次，零次或多次写作 `Optional(Repeat(..))`
+    Repeat(Box<Expr>),
+}
+
+/// 把 EBNF 展开时生成的非终结符还原成括号和后缀运算符
+struct Sugar<'a> {
+    grammar: &'a Grammar,
+    groups: HashMap<PHead, (PBody, char)>,
+}
+impl<'a> Sugar<'a> {
+    fn new(grammar: &'a Grammar) -> Self {
+        Self {
+            grammar,
+            groups: grammar.ebnf_groups(),
+        }
+    }
+    /// 文件中写出的规则，不含生成的非终结符
+    fn heads(&self) -> impl Iterator<Item = &'a PHead> + '_ {
+        self.grammar
+            .head_list
+            .iter()
+            .filter(|head| !self.groups.contains_key(*head))
+    }
+    fn bodies(&self, head: &PHead) -> &'a PBody {
+        &self.grammar.pro_list[head]
+    }
+    fn rule(&self, head: &PHead) -> Expr {
+        self.body(self.bodies(head))
+    }
+    fn body(&self, body: &PBody) -> Expr {
+        choice(body.iter().map(|item| self.item(item)).collect())
+    }
+    fn item(&self, item: &Item) -> Expr {
+        seq(item
+            .iter()
+            .filter(|symbol| !is_epsilon(symbol))
+            .map(|symbol| self.symbol(symbol))
+            .collect())
+    }
+    fn symbol(&self, symbol: &Element) -> Expr {
+        match (symbol, self.groups.get(symbol)) {
+            (_, Some((inner, repeat))) => {
+                let inner = self.body(inner);
+                match repeat {
+                    '?' => Expr::Optional(Box::new(inner)),
+                    '*' => Expr::Optional(Box::new(Expr::Repeat(Box::new(inner)))),
+                    '+' => Expr::Repeat(Box::new(inner)),
+                    _ => inner,
+                }
+            }
+            (Element::Terminal(name), None) => Expr::Terminal(name.clone()),
+            (Element::NotTerminal(name), None) => Expr::NonTerminal(name.clone()),
+        }
+    }
+}
+
+fn is_epsilon(symbol: &Element) -> bool {
+    *symbol == Element::Terminal("ε".to_string())
+}
+
+fn seq(mut items: Vec<Expr>) -> Expr {
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        Expr::Seq(items)
+    }
+}
+
+/// 候选式中有空串时写成可选项，`A | ε` 即 `A?`
+fn choice(alternatives: Vec<Expr>) -> Expr {
+    let empty = Expr::Seq(Vec::new());
+    let nullable = alternatives.contains(&empty);
+    let mut alternatives: Vec<Expr> = alternatives
+        .into_iter()
+        .filter(|alt| *alt != empty)
+        .collect();
+    let inner = match alternatives.len() {
+        0 => return empty,
+        1 => alternatives.pop().unwrap(),
+        _ => Expr::Choice(alternatives),
+    };
+    match inner {
+        inner if !nullable => inner,
+        Expr::Optional(inner) => Expr::Optional(inner),
+        inner => Expr::Optional(Box::new(inner)),
+    }
+}
+
+/// 本项目格式中的写法，带引号的字面量要重新转义
+fn literal(symbol: &str) -> String {
+    match symbol.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        Some(content) if symbol.len() > 1 => {
+            let mut quoted = String::from("'");
+            for c in content.chars() {
+                if c == '\\' || c == '\'' {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('\'');
+            quoted
+        }
+        _ => symbol.to_string(),
+    }
+}
+
+fn text(expr: &Expr) -> String {
+    match expr {
+        Expr::Terminal(symbol) => literal(symbol),
+        Expr::NonTerminal(name) => name.clone(),
+        Expr::Seq(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| match item {
+                    Expr::Choice(_) => format!("( {} )", text(item)),
+                    _ => text(item),
+                })
+                .collect();
+            items.join(" ")
+        }
+        Expr::Choice(alternatives) => {
+            let alternatives: Vec<String> = alternatives.iter().map(text).collect();
+            alternatives.join(" | ")
+        }
+        Expr::Optional(inner) => match inner.as_ref() {
+            Expr::Repeat(inner) => postfix(inner, '*'),
+            inner => postfix(inner, '?'),
+        },
+        Expr::Repeat(inner) => postfix(inner, '+'),
+    }
+}
+
+fn postfix(expr: &Expr, op: char) -> String {
+    match expr {
+        Expr::Terminal(_) | Expr::NonTerminal(_) => format!("{}{}", text(expr), op),
+        _ => format!("( {} ){}", text(expr), op),
+    }
+}
+
+/// 本项目格式的文法文本：开始符号、声明、规则和语义动作，EBNF 展开的非终结符还原成括号，
+/// 重新读入后得到相同的开始符号和产生式
+pub fn to_native(grammar: &Grammar) -> String {
+    let sugar = Sugar::new(grammar);
+    let mut buffer = format!("%start {}\n", grammar.start_symbol);
+    let directives = [
+        ("token", &grammar.token_list),
+        ("sync", &grammar.sync_list),
+        ("inline", &grammar.inline_list),
+        ("flatten", &grammar.flatten_list),
+    ];
+    for (name, symbols) in directives {
+        if !symbols.is_empty() {
+            let symbols: Vec<String> = symbols.iter().map(|v| literal(v)).collect();
+            buffer.push_str(&format!("%{} {}\n", name, symbols.join(" ")));
+        }
+    }
+    for (open, close) in &grammar.pair_list {
+        buffer.push_str(&format!("%pair {} {}\n", literal(open), literal(close)));
+    }
+    for head in sugar.heads() {
+        if let Some(ty) = grammar.type_list.get(head) {
+            buffer.push_str(&format!("%type <{}> {}\n", ty, head));
+        }
+    }
+    for head in sugar.heads() {
+        buffer.push_str(&format!(
+            "\n{}:{}\n    ;\n",
+            head,
+            rules(&sugar, head, true)
+        ));
+    }
+    buffer
+}
+
+/// `A ::= B ( ',' B )* | ε`，与 s.txt 的写法相同。声明和语义动作不导出，没有规则的符号都是终结符
+pub fn to_ebnf(grammar: &Grammar) -> String {
+    let sugar = Sugar::new(grammar);
+    let mut buffer = String::new();
+    for head in sugar.heads() {
+        buffer.push_str(&format!("{} ::={}\n", head, rules(&sugar, head, false)));
+    }
+    buffer
+}
+
+/// `head` 的各个候选式，每个一行
+fn rules(sugar: &Sugar, head: &PHead, actions: bool) -> String {
+    let mut buffer = String::new();
+    for (i, item) in sugar.bodies(head).iter().enumerate() {
+        // 整个候选式是一个括号时要保留括号，否则重新读入后变成多个候选式
+        let mut alternative = text(&Expr::Seq(vec![sugar.item(item)]));
+        if alternative.is_empty() {
+            alternative = "ε".to_string();
+        }
+        if let Some(action) = sugar.grammar.action_list[head][i]
+            .as_ref()
+            .filter(|_| actions)
+        {
+            alternative.push_str(&format!(" => {{ {} }}", action));
+        }
+        let separator = if i == 0 { " " } else { "\n    | " };
+        buffer.push_str(&format!("{}{}", separator, alternative));
+    }
+    buffer
+}
+
+/// bison 的符号名：文法符号转成 C 标识符，多字符的字面量用 `%token NAME "text"` 声明
+struct BisonNames {
+    names: HashMap<Element, String>,
+    used: HashSet<String>,
+    /// 需要声明的记号和别名
+    tokens: Vec<(String, Option<String>)>,
+}
+impl BisonNames {
+    fn new(grammar: &Grammar) -> Self {
+        let mut names = Self {
+            names: HashMap::new(),
+            used: HashSet::from([ERROR_TOKEN.to_string()]),
+            tokens: Vec::new(),
+        };
+        for head in &grammar.head_list {
+            let name = names.unique(head.to_string());
+            names.names.insert(head.clone(), name);
+        }
+        for token in &grammar.token_list {
+            let symbol = Element::Terminal(token.clone());
+            if is_epsilon(&symbol) {
+                continue;
+            }
+            let name = match token.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                _ if token == ERROR_TOKEN => ERROR_TOKEN.to_string(),
+                Some(content) if token.len() > 1 => {
+                    let mut chars = content.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c.is_ascii_graphic() || c == ' ' => match c {
+                            '\'' | '\\' => format!("'\\{}'", c),
+                            c => format!("'{}'", c),
+                        },
+                        _ => {
+                            let name = if content
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                            {
+                                content.to_uppercase()
+                            } else {
+                                "T".to_string()
+                            };
+                            let name = names.unique(name);
+                            let alias = format!(
+                                "\"{}\"",
+                                content.replace('\\', "\\\\").replace('"', "\\\"")
+                            );
+                            names.tokens.push((name, Some(alias.clone())));
+                            alias
+                        }
+                    }
+                }
+                _ => {
+                    let name = names.unique(token.clone());
+                    names.tokens.push((name.clone(), None));
+                    name
+                }
+            };
+            names.names.insert(symbol, name);
+        }
+        names
+    }
+    fn unique(&mut self, symbol: String) -> String {
+        let mut name: String = symbol
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            name.insert_str(0, "T_");
+        }
+        let mut candidate = name.clone();
+        let mut n = 1;
+        while !self.used.insert(candidate.clone()) {
+            n += 1;
+            candidate = format!("{}_{}", name, n);
+        }
+        candidate
+    }
+}
+
+/// bison 的 `.y` 文件。bison 没有 EBNF，生成的非终结符作为普通规则导出；
+/// 语义动作是 Rust 代码，不导出
+pub fn to_bison(grammar: &Grammar) -> String {
+    let names = BisonNames::new(grammar);
+    let mut buffer = String::new();
+    for (name, alias) in &names.tokens {
+        match alias {
+            Some(alias) => buffer.push_str(&format!("%token {} {}\n", name, alias)),
+            None => buffer.push_str(&format!("%token {}\n", name)),
+        }
+    }
+    if let Some(start) = names.names.get(&grammar.start_symbol) {
+        buffer.push_str(&format!("%start {}\n", start));
+    }
+    buffer.push_str("%%\n");
+    for head in &grammar.head_list {
+        buffer.push_str(&format!("\n{}:", names.names[head]));
+        for (i, item) in grammar.pro_list[head].iter().enumerate() {
+            let symbols: Vec<&str> = item
+                .iter()
+                .filter(|symbol| !is_epsilon(symbol))
+                .map(|symbol| names.names[symbol].as_str())
+                .collect();
+            let alternative = if symbols.is_empty() {
+                "%empty".to_string()
+            } else {
+                symbols.join(" ")
+            };
+            let separator = if i == 0 { " " } else { "\n    | " };
+            buffer.push_str(&format!("{}{}", separator, alternative));
+        }
+        buffer.push_str("\n    ;\n");
+    }
+    buffer.push_str("%%\n");
+    buffer
+}
+
+const CHAR_WIDTH: f64 = 8.0;
+const BOX_HEIGHT: f64 = 22.0;
+const BOX_PADDING: f64 = 10.0;
+const GAP: f64 = 16.0;
+const ARC: f64 = 10.0;
+const ROW_GAP: f64 = 8.0;
+const MARGIN: f64 = 10.0;
+
+const STYLE: &str = "path{fill:none;stroke:#333;stroke-width:1.5}\
+rect{fill:#fff;stroke:#333;stroke-width:1.5}rect.t{fill:#eef}\
+text{font:13px monospace;text-anchor:middle}";
+
+/// 铁路图中一个部分的宽度和基线上下的高度
+#[derive(Debug, Clone, Copy)]
+struct Size {
+    width: f64,
+    up: f64,
+    down: f64,
+}
+
+fn label(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Terminal(symbol) => Some(
+            match symbol.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                Some(content) if symbol.len() > 1 => content.to_string(),
+                _ => symbol.clone(),
+            },
+        ),
+        Expr::NonTerminal(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn measure(expr: &Expr) -> Size {
+    if let Some(label) = label(expr) {
+        return Size {
+            width: label.chars().count() as f64 * CHAR_WIDTH + 2.0 * BOX_PADDING,
+            up: BOX_HEIGHT / 2.0,
+            down: BOX_HEIGHT / 2.0,
+        };
+    }
+    match expr {
+        Expr::Seq(items) => {
+            let sizes: Vec<Size> = items.iter().map(measure).collect();
+            Size {
+                width: sizes.iter().map(|v| v.width).sum::<f64>()
+                    + GAP * sizes.len().saturating_sub(1) as f64,
+                up: sizes.iter().map(|v| v.up).fold(0.0, f64::max),
+                down: sizes.iter().map(|v| v.down).fold(0.0, f64::max),
+            }
+        }
+        Expr::Choice(alternatives) => {
+            let rows = rows(alternatives);
+            let last = rows.last().unwrap();
+            Size {
+                width: rows.iter().map(|(size, _)| size.width).fold(0.0, f64::max) + 4.0 * ARC,
+                up: rows[0].0.up,
+                down: last.1 + last.0.down,
+            }
+        }
+        Expr::Optional(inner) => measure(&Expr::Choice(vec![
+            Expr::Seq(Vec::new()),
+            Expr::clone(inner),
+        ])),
+        Expr::Repeat(inner) => {
+            let size = measure(inner);
+            Size {
+                width: size.width + 4.0 * ARC,
+                up: size.up,
+                down: loop_depth(size),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// 各候选式的大小和基线相对第一个候选式的偏移
+fn rows(alternatives: &[Expr]) -> Vec<(Size, f64)> {
+    let mut rows: Vec<(Size, f64)> = Vec::new();
+    for alternative in alternatives {
+        let size = measure(alternative);
+        let y = match rows.last() {
+            Some((last, y)) => y + (last.down + ROW_GAP + size.up).max(2.0 * ARC),
+            None => 0.0,
+        };
+        rows.push((size, y));
+    }
+    rows
+}
+
+/// 重复的回路在基线下方的深度
+fn loop_depth(size: Size) -> f64 {
+    (size.down + ROW_GAP).max(2.0 * ARC)
+}
+
+/// 以 `(x, y)` 为入口画出 `expr`，出口在 `(x + width, y)`
+fn draw(expr: &Expr, x: f64, y: f64, out: &mut String) {
+    let size = measure(expr);
+    if let Some(label) = label(expr) {
+        let text = format!(
+            "<rect{} x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/><text x=\"{}\" y=\"{}\">{}</text>",
+            if matches!(expr, Expr::Terminal(_)) { " class=\"t\"" } else { "" },
+            x,
+            y - BOX_HEIGHT / 2.0,
+            size.width,
+            BOX_HEIGHT,
+            if matches!(expr, Expr::Terminal(_)) { BOX_HEIGHT / 2.0 } else { 0.0 },
+            x + size.width / 2.0,
+            y + 4.0,
+            escape(&label)
+        );
+        match expr {
+            Expr::NonTerminal(name) => {
+                out.push_str(&format!("<a href=\"#{}\">{}</a>\n", escape(name), text))
+            }
+            _ => out.push_str(&format!("{}\n", text)),
+        }
+        return;
+    }
+    match expr {
+        Expr::Seq(items) => {
+            let mut x = x;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    line(out, x, y, x + GAP);
+                    x += GAP;
+                }
+                draw(item, x, y, out);
+                x += measure(item).width;
+            }
+        }
+        Expr::Choice(alternatives) => {
+            let (left, right) = (x + 2.0 * ARC, x + size.width - 2.0 * ARC);
+            for (alternative, (row, dy)) in alternatives.iter().zip(rows(alternatives)) {
+                let row_y = y + dy;
+                if dy == 0.0 {
+                    line(out, x, y, left);
+                    line(out, right, y, x + size.width);
+                } else {
+                    out.push_str(&format!(
+                        "<path d=\"M{} {} q{a} 0 {a} {a} V{} q0 {a} {a} {a} M{} {} q{a} 0 {a} -{a} V{} q0 -{a} {a} -{a}\"/>\n",
+                        x,
+                        y,
+                        row_y - ARC,
+                        right,
+                        row_y,
+                        y + ARC,
+                        a = ARC
+                    ));
+                }
+                draw(alternative, left, row_y, out);
+                line(out, left + row.width, row_y, right);
+            }
+        }
+        Expr::Optional(inner) => draw(
+            &Expr::Choice(vec![Expr::Seq(Vec::new()), Expr::clone(inner)]),
+            x,
+            y,
+            out,
+        ),
+        Expr::Repeat(inner) => {
+            let inner_size = measure(inner);
+            let (left, right) = (x + 2.0 * ARC, x + 2.0 * ARC + inner_size.width);
+            line(out, x, y, left);
+            draw(inner, left, y, out);
+            line(out, right, y, x + size.width);
+            out.push_str(&format!(
+                "<path d=\"M{} {} q{a} 0 {a} {a} V{} q0 {a} -{a} {a} H{} q-{a} 0 -{a} -{a} V{} q0 -{a} {a} -{a}\"/>\n",
+                right,
+                y,
+                y + size.down - ARC,
+                left,
+                y + ARC,
+                a = ARC
+            ));
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn line(out: &mut String, from: f64, y: f64, to: f64) {
+    if to > from {
+        out.push_str(&format!("<path d=\"M{} {} H{}\"/>\n", from, y, to));
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `head` 的规则的铁路图（SVG）。非终结符链接到 HTML 中同名的锚点
+pub fn railroad_svg(grammar: &Grammar, head: &PHead) -> Option<String> {
+    if !grammar.pro_list.contains_key(head) {
+        return None;
+    }
+    let expr = Sugar::new(grammar).rule(head);
+    let size = measure(&expr);
+    let (width, height) = (
+        size.width + 2.0 * (MARGIN + GAP),
+        size.up + size.down + 2.0 * MARGIN,
+    );
+    let y = MARGIN + size.up;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n<style>{}</style>\n",
+        width, height, width, height, STYLE
+    );
+    // 入口和出口的竖线
+    let end = MARGIN + GAP + size.width;
+    svg.push_str(&format!(
+        "<path d=\"M{m} {} v16 M{m} {y} H{} M{} {y} H{} M{} {} v16\"/>\n",
+        y - 8.0,
+        MARGIN + GAP,
+        end,
+        end + GAP,
+        end + GAP,
+        y - 8.0,
+        m = MARGIN,
+        y = y
+    ));
+    draw(&expr, MARGIN + GAP, y, &mut svg);
+    svg.push_str("</svg>\n");
+    Some(svg)
+}
+
+/// 所有规则的铁路图，每条规则一节，可以点击非终结符跳转
+pub fn railroad_html(grammar: &Grammar) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Grammar</title>\n</head>\n<body>\n",
+    );
+    for head in Sugar::new(grammar).heads() {
+        let name = escape(&head.to_string());
+        html.push_str(&format!("<h2 id=\"{}\">{}</h2>\n", name, name));
+        html.push_str(&railroad_svg(grammar, head).unwrap());
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{GrammarFormat, LR1Driver, LR1Parser};
+    use std::sync::Arc;
+
+    /// 导出后重新读入，开始符号和产生式都不变
+    fn assert_round_trip(grammar: &Grammar) -> Grammar {
+        let text = to_native(grammar);
+        let again: Grammar = text.parse().unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        assert_eq!(again.start_symbol, grammar.start_symbol);
+        assert_eq!(again.productions(), grammar.productions());
+        assert_eq!(again.action_list, grammar.action_list);
+        again
+    }
+
+    #[test]
+    fn g2_round_trip() {
+        let mut grammar = Grammar::new();
+        grammar
+            .grammar_load(concat!(env!("CARGO_MANIFEST_DIR"), "/g2.txt"))
+            .unwrap();
+        assert_round_trip(&grammar);
+    }
+
+    #[test]
+    fn ebnf_round_trip() {
+        let grammar: Grammar = "%token x\nCompUnit': L ;\nL: '[' ( x ( ',' x )* )? ']' | x+ ;"
+            .parse()
+            .unwrap();
+        assert!(!grammar.generated().is_empty());
+        assert_round_trip(&grammar);
+    }
+
+    #[test]
+    fn actions_round_trip() {
+        let grammar: Grammar = "%token x\nCompUnit': S ;\nS: x { x } => { $1 } | => { () } ;"
+            .parse()
+            .unwrap();
+        assert!(to_native(&grammar).contains("=> { $1 }"));
+        assert_round_trip(&grammar);
+    }
+
+    #[test]
+    fn imported_round_trip() {
+        let mut grammar = Grammar::new();
+        grammar
+            .grammar_parse_as(
+                "%token NUM\n%%\nexp: exp '+' NUM | NUM ;",
+                GrammarFormat::Bison,
+            )
+            .unwrap();
+        assert_eq!(
+            grammar.start_symbol,
+            Element::NotTerminal("exp'".to_string())
+        );
+        let again = assert_round_trip(&grammar);
+
+        let tables = Arc::new(LR1Parser::from_grammar(&again).into_tables());
+        let outcome = LR1Driver::new(tables).construct_tree(&Element::terminals("NUM '+' NUM"));
+        assert!(outcome.errors.is_empty());
+        assert_eq!(
+            outcome.tree.element,
+            Element::NotTerminal("exp".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_start_rule_is_an_error() {
+        let error = "%token NUM\nexp: exp '+' NUM | NUM ;"
+            .parse::<Grammar>()
+            .err()
+            .unwrap();
+        assert!(error.message.contains("CompUnit'"), "{}", error);
+        // 开始符号出现在右部时 LR 分析在归约它时就会接受，要求先增广
+        let error = "%start exp\n%token NUM\nexp: exp '+' NUM | NUM ;"
+            .parse::<Grammar>()
+            .err()
+            .unwrap();
+        assert!(error.message.contains("right side"), "{}", error);
+        let grammar: Grammar = "%start S\n%token NUM\nS: exp ;\nexp: exp '+' NUM | NUM ;"
+            .parse()
+            .unwrap();
+        assert_eq!(grammar.start_symbol, Element::NotTerminal("S".to_string()));
+    }
+}
@@ -0,0 +1,463 @@
+use crate::parser::lr1_parser::{NodeKind, ProdId, TreeNode};
+use crate::parser::types::Element;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// 森林中节点的下标
+pub type ForestId = usize;
+
+/// 非终结符节点的一种推导：所用的产生式和各个子节点（ε 产生式没有子节点）
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Family {
+    pub prod: ProdId,
+    pub children: Vec<ForestId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForestNode {
+    /// 第 `index` 个输入记号
+    Token { element: Element, index: usize },
+    /// 非终结符在记号区间 `[start, end)` 上的全部推导，多于一族时有歧义
+    Symbol {
+        element: Element,
+        start: usize,
+        end: usize,
+        families: Vec<Family>,
+    },
+}
+
+/// 共享压缩分析森林（SPPF）：同一符号在同一区间上的推导共用一个节点，
+/// 不同的推导作为该节点的不同族保存
+#[derive(Debug, Clone, Default)]
+pub struct Forest {
+    nodes: Vec<ForestNode>,
+    root: Option<ForestId>,
+    /// EBNF 展开时生成的非终结符，取出语法树时它们的子节点并入父节点
+    generated: HashSet<Element>,
+    symbols: HashMap<(Element, usize, usize), ForestId>,
+    tokens: HashMap<usize, ForestId>,
+}
+
+impl Forest {
+    pub(crate) fn new(generated: HashSet<Element>) -> Self {
+        Self {
+            generated,
+            ..Self::default()
+        }
+    }
+    pub(crate) fn token(&mut self, index: usize, element: Element) -> ForestId {
+        if let Some(&id) = self.tokens.get(&index) {
+            return id;
+        }
+        self.nodes.push(ForestNode::Token { element, index });
+        self.tokens.insert(index, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+    pub(crate) fn symbol(&mut self, element: Element, start: usize, end: usize) -> ForestId {
+        let key = (element, start, end);
+        if let Some(&id) = self.symbols.get(&key) {
+            return id;
+        }
+        let (element, start, end) = key.clone();
+        self.nodes.push(ForestNode::Symbol {
+            element,
+            start,
+            end,
+            families: Vec::new(),
+        });
+        self.symbols.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+    /// 给非终结符节点添加一族推导，已有相同的族时返回 false
+    pub(crate) fn add_family(
+        &mut self,
+        id: ForestId,
+        prod: ProdId,
+        children: Vec<ForestId>,
+    ) -> bool {
+        let ForestNode::Symbol { families, .. } = &mut self.nodes[id] else {
+            return false;
+        };
+        let family = Family { prod, children };
+        if families.contains(&family) {
+            return false;
+        }
+        families.push(family);
+        true
+    }
+    pub(crate) fn set_root(&mut self, root: Option<ForestId>) {
+        self.root = root;
+    }
+
+    /// 整个输入的推导，分析失败时为 None
+    pub fn root(&self) -> Option<ForestId> {
+        self.root
+    }
+    pub fn node(&self, id: ForestId) -> &ForestNode {
+        &self.nodes[id]
+    }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    fn families(&self, id: ForestId) -> &[Family] {
+        match &self.nodes[id] {
+            ForestNode::Symbol { families, .. } => families,
+            ForestNode::Token { .. } => &[],
+        }
+    }
+
+    /// 从根可达、有多种推导的节点
+    pub fn ambiguities(&self) -> Vec<ForestId> {
+        let mut result = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<ForestId> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let families = self.families(id);
+            if families.len() > 1 {
+                result.push(id);
+            }
+            stack.extend(families.iter().flat_map(|family| family.children.iter()));
+        }
+        result.sort_unstable();
+        result
+    }
+    pub fn is_ambiguous(&self) -> bool {
+        !self.ambiguities().is_empty()
+    }
+
+    /// 森林中语法树的数目。循环文法的森林中有环，树有无穷多棵，返回 None；分析失败时也返回 None
+    pub fn tree_count(&self) -> Option<u128> {
+        fn count(
+            forest: &Forest,
+            id: ForestId,
+            memo: &mut HashMap<ForestId, Option<u128>>,
+            visiting: &mut HashSet<ForestId>,
+        ) -> Option<u128> {
+            if let Some(&result) = memo.get(&id) {
+                return result;
+            }
+            if !visiting.insert(id) {
+                return None;
+            }
+            let result = match &forest.nodes[id] {
+                ForestNode::Token { .. } => Some(1),
+                ForestNode::Symbol { families, .. } => {
+                    families.iter().try_fold(0u128, |total, family| {
+                        let product =
+                            family.children.iter().try_fold(1u128, |product, &child| {
+                                Some(product.saturating_mul(count(forest, child, memo, visiting)?))
+                            })?;
+                        Some(total.saturating_add(product))
+                    })
+                }
+            };
+            visiting.remove(&id);
+            memo.insert(id, result);
+            result
+        }
+        let root = self.root?;
+        count(self, root, &mut HashMap::new(), &mut HashSet::new())
+    }
+
+    /// 每处歧义取产生式编号最小的一族；编号相同时取靠左的子节点更长的一族（即左结合），
+    /// 因此结果与森林的构造顺序无关
+    pub fn tree(&self) -> Option<TreeNode> {
+        self.disambiguate(|forest, _, families| {
+            (0..families.len())
+                .min_by_key(|&i| {
+                    let ends: Vec<usize> = families[i]
+                        .children
+                        .iter()
+                        .map(|&child| forest.end(child))
+                        .collect();
+                    (families[i].prod, std::cmp::Reverse(ends))
+                })
+                .unwrap()
+        })
+    }
+    /// 节点覆盖的输入的结束位置
+    fn end(&self, id: ForestId) -> usize {
+        match &self.nodes[id] {
+            ForestNode::Token { index, .. } => index + 1,
+            ForestNode::Symbol { end, .. } => *end,
+        }
+    }
+
+    /// 由 `choose` 在每个有多种推导的节点上选出一族，得到一棵语法树。
+    /// 传给 `choose` 的候选族不含会形成环的族，返回值是其中的下标
+    pub fn disambiguate(
+        &self,
+        mut choose: impl FnMut(&Forest, ForestId, &[&Family]) -> usize,
+    ) -> Option<TreeNode> {
+        let mut visiting = HashSet::new();
+        Some(self.build(self.root?, &mut choose, &mut visiting))
+    }
+
+    fn build(
+        &self,
+        id: ForestId,
+        choose: &mut impl FnMut(&Forest, ForestId, &[&Family]) -> usize,
+        visiting: &mut HashSet<ForestId>,
+    ) -> TreeNode {
+        let (element, families) = match &self.nodes[id] {
+            ForestNode::Token { element, .. } => return TreeNode::leaf(element.clone()),
+            ForestNode::Symbol {
+                element, families, ..
+            } => (element, families),
+        };
+        visiting.insert(id);
+        let candidates: Vec<&Family> = families
+            .iter()
+            .filter(|family| {
+                family
+                    .children
+                    .iter()
+                    .all(|child| !visiting.contains(child))
+            })
+            .collect();
+        let node = match candidates.len() {
+            0 => {
+                let mut node = TreeNode::leaf(element.clone());
+                node.kind = NodeKind::Error;
+                node
+            }
+            1 => self.build_family(element, candidates[0], choose, visiting),
+            _ => {
+                let index = choose(self, id, &candidates);
+                self.build_family(element, candidates[index], choose, visiting)
+            }
+        };
+        visiting.remove(&id);
+        node
+    }
+
+    fn build_family(
+        &self,
+        element: &Element,
+        family: &Family,
+        choose: &mut impl FnMut(&Forest, ForestId, &[&Family]) -> usize,
+        visiting: &mut HashSet<ForestId>,
+    ) -> TreeNode {
+        let mut nodes = Vec::with_capacity(family.children.len());
+        for &child in &family.children {
+            let child = self.build(child, choose, visiting);
+            match child.children {
+                Some(grandchildren) if self.generated.contains(&child.element) => {
+                    nodes.extend(grandchildren)
+                }
+                _ => nodes.push(child),
+            }
+        }
+        TreeNode::new(element.clone(), nodes)
+    }
+
+    /// 列出森林中的语法树，最多 `limit` 棵
+    pub fn trees(&self, limit: usize) -> Vec<TreeNode> {
+        let Some(root) = self.root else {
+            return Vec::new();
+        };
+        self.expand(root, limit, &mut HashSet::new())
+    }
+
+    fn expand(
+        &self,
+        id: ForestId,
+        limit: usize,
+        visiting: &mut HashSet<ForestId>,
+    ) -> Vec<TreeNode> {
+        let (element, families) = match &self.nodes[id] {
+            ForestNode::Token { element, .. } => return vec![TreeNode::leaf(element.clone())],
+            ForestNode::Symbol {
+                element, families, ..
+            } => (element, families),
+        };
+        if !visiting.insert(id) {
+            return Vec::new();
+        }
+        let mut trees = Vec::new();
+        for family in families {
+            // 各子节点可选的子树做笛卡尔积
+            let mut partial: Vec<Vec<TreeNode>> = vec![Vec::new()];
+            for &child in &family.children {
+                let options = self.expand(child, limit, visiting);
+                let mut next = Vec::new();
+                'outer: for prefix in &partial {
+                    for option in &options {
+                        if next.len() >= limit {
+                            break 'outer;
+                        }
+                        let mut nodes = prefix.clone();
+                        match &option.children {
+                            Some(grandchildren) if self.generated.contains(&option.element) => {
+                                nodes.extend(grandchildren.iter().cloned())
+                            }
+                            _ => nodes.push(option.clone()),
+                        }
+                        next.push(nodes);
+                    }
+                }
+                partial = next;
+            }
+            for nodes in partial {
+                if trees.len() >= limit {
+                    break;
+                }
+                trees.push(TreeNode::new(element.clone(), nodes));
+            }
+        }
+        visiting.remove(&id);
+        trees
+    }
+}
+
+impl Display for Forest {
+    /// 有歧义的节点下依次列出每一族推导
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn print_node(
+            forest: &Forest,
+            id: ForestId,
+            depth: usize,
+            visiting: &mut HashSet<ForestId>,
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> std::fmt::Result {
+            let indent = "  ".repeat(depth);
+            let (element, families) = match &forest.nodes[id] {
+                ForestNode::Token { element, .. } => return writeln!(f, "{}{:?}", indent, element),
+                ForestNode::Symbol {
+                    element, families, ..
+                } => (element, families),
+            };
+            if !visiting.insert(id) {
+                return writeln!(f, "{}{:?} <cycle>", indent, element);
+            }
+            match families.as_slice() {
+                [family] => {
+                    writeln!(f, "{}{:?}", indent, element)?;
+                    for &child in &family.children {
+                        print_node(forest, child, depth + 1, visiting, f)?;
+                    }
+                }
+                _ => {
+                    writeln!(f, "{}{:?} <ambiguous>", indent, element)?;
+                    for family in families {
+                        writeln!(f, "{}  <production {}>", indent, family.prod)?;
+                        for &child in &family.children {
+                            print_node(forest, child, depth + 2, visiting, f)?;
+                        }
+                    }
+                }
+            }
+            visiting.remove(&id);
+            Ok(())
+        }
+        match self.root {
+            Some(root) => print_node(self, root, 0, &mut HashSet::new(), f)?,
+            None => writeln!(f, "<no parse>")?,
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(name: &str) -> TreeNode {
+        TreeNode::leaf(Element::Terminal(name.to_string()))
+    }
+
+    fn node(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode::new(Element::NotTerminal(name.to_string()), children)
+    }
+
+    /// `a + a + a` 在 `E: E '+' E | a` 下的森林，根节点的两族按 `left_first` 决定的顺序加入
+    fn sum(left_first: bool) -> Forest {
+        let mut forest = Forest::new(HashSet::new());
+        let e = Element::NotTerminal("E".to_string());
+        let tokens: Vec<ForestId> = ["a", "'+'", "a", "'+'", "a"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| forest.token(i, Element::Terminal(name.to_string())))
+            .collect();
+        let atoms: Vec<ForestId> = [0, 2, 4]
+            .map(|i| {
+                let id = forest.symbol(e.clone(), i, i + 1);
+                forest.add_family(id, 2, vec![tokens[i]]);
+                id
+            })
+            .to_vec();
+        let left = forest.symbol(e.clone(), 0, 3);
+        forest.add_family(left, 1, vec![atoms[0], tokens[1], atoms[1]]);
+        let right = forest.symbol(e.clone(), 2, 5);
+        forest.add_family(right, 1, vec![atoms[1], tokens[3], atoms[2]]);
+        let root = forest.symbol(e, 0, 5);
+        let mut families = [
+            vec![left, tokens[3], atoms[2]],
+            vec![atoms[0], tokens[1], right],
+        ];
+        if !left_first {
+            families.reverse();
+        }
+        for children in families {
+            forest.add_family(root, 1, children);
+        }
+        forest.set_root(Some(root));
+        forest
+    }
+
+    #[test]
+    fn tree_prefers_left_association() {
+        let a = || node("E", vec![leaf("a")]);
+        let expected = node(
+            "E",
+            vec![node("E", vec![a(), leaf("'+'"), a()]), leaf("'+'"), a()],
+        );
+        for left_first in [true, false] {
+            let forest = sum(left_first);
+            assert_eq!(forest.tree_count(), Some(2));
+            assert_eq!(forest.ambiguities(), [forest.root().unwrap()]);
+            assert_eq!(forest.tree(), Some(expected.clone()));
+            assert_eq!(forest.trees(10).len(), 2);
+        }
+    }
+
+    #[test]
+    fn tree_prefers_smaller_production() {
+        let mut forest = Forest::new(HashSet::new());
+        let token = forest.token(0, Element::Terminal("x".to_string()));
+        let a = forest.symbol(Element::NotTerminal("A".to_string()), 0, 1);
+        forest.add_family(a, 4, vec![token]);
+        let b = forest.symbol(Element::NotTerminal("B".to_string()), 0, 1);
+        forest.add_family(b, 5, vec![token]);
+        let root = forest.symbol(Element::NotTerminal("S".to_string()), 0, 1);
+        forest.add_family(root, 3, vec![a]);
+        forest.add_family(root, 2, vec![b]);
+        forest.set_root(Some(root));
+        assert_eq!(
+            forest.tree(),
+            Some(node("S", vec![node("B", vec![leaf("x")])]))
+        );
+    }
+
+    #[test]
+    fn cycle_has_infinitely_many_trees() {
+        // S: S | x
+        let mut forest = Forest::new(HashSet::new());
+        let token = forest.token(0, Element::Terminal("x".to_string()));
+        let root = forest.symbol(Element::NotTerminal("S".to_string()), 0, 1);
+        forest.add_family(root, 1, vec![root]);
+        forest.add_family(root, 2, vec![token]);
+        forest.set_root(Some(root));
+        assert_eq!(forest.tree_count(), None);
+        assert!(forest.is_ambiguous());
+        // 会形成环的族不参与选择
+        assert_eq!(forest.tree(), Some(node("S", vec![leaf("x")])));
+        assert_eq!(forest.trees(10), [node("S", vec![leaf("x")])]);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::parser::diagnostic::ParserError;
+use crate::parser::driver::ParseOutcome;
+use crate::parser::forest::{Forest, ForestId};
+use crate::parser::lr1_parser::{Action, ActionSets, LR1Parser, ParseTables, State};
+use crate::parser::types::{Element, Token};
+use crate::parser::Grammar;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// GLR 分析用的分析表：每一格可以有多个动作
+#[derive(Debug, Clone)]
+pub struct GLRTables {
+    action_sets: ActionSets,
+    tables: ParseTables,
+    generated: HashSet<Element>,
+}
+
+impl GLRTables {
+    /// `parser` 需要已经构造好项集族和分析表
+    pub fn new(parser: &LR1Parser, grammar: &Grammar) -> Self {
+        Self {
+            action_sets: parser.action_sets(grammar),
+            tables: parser.tables(),
+            generated: grammar.generated(),
+        }
+    }
+    pub fn actions(&self, state: State, symbol: &Element) -> &[Action] {
+        self.action_sets
+            .get(&(state, symbol.clone()))
+            .map_or(&[], Vec::as_slice)
+    }
+    /// 有多个动作的格子，按状态排列
+    pub fn conflicts(&self) -> Vec<(State, &Element, &[Action])> {
+        let mut conflicts: Vec<_> = self
+            .action_sets
+            .iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|((state, symbol), actions)| (*state, symbol, actions.as_slice()))
+            .collect();
+        conflicts.sort_by_key(|(state, symbol, _)| (*state, symbol.to_string()));
+        conflicts
+    }
+}
+
+/// 图结构栈中的节点。边指向下面的节点，边上是移进或归约得到的符号在森林中的节点
+#[derive(Debug)]
+struct GssNode {
+    state: State,
+    /// 节点所在的记号位置，归约时作为区间的起点
+    level: usize,
+    /// ε 的移进没有对应的森林节点
+    edges: Vec<(usize, Option<ForestId>)>,
+}
+
+/// Tomita 式的 GLR 分析：冲突时分裂栈，栈顶状态相同时合并，
+/// 所有推导保存在共享压缩分析森林中
+#[derive(Debug, Clone)]
+pub struct GLRDriver {
+    tables: Arc<GLRTables>,
+}
+
+impl GLRDriver {
+    pub fn new(tables: Arc<GLRTables>) -> Self {
+        Self { tables }
+    }
+
+    pub fn construct_forest(&self, input: &[Element]) -> ParseOutcome<Forest> {
+        let (tokens, source) = Token::from_elements(input);
+        self.construct_forest_from_tokens(tokens, &source)
+    }
+
+    /// `tokens` 中的 span 指向 `source`，报错时据此输出源码片段。
+    /// 出错时跳过无法移进的记号，被跳过的记号不出现在森林中
+    pub fn construct_forest_from_tokens(
+        &self,
+        tokens: impl IntoIterator<Item = Token>,
+        source: &str,
+    ) -> ParseOutcome<Forest> {
+        let mut tokens: Vec<Token> = tokens.into_iter().collect();
+        let offset = tokens
+            .last()
+            .map_or(0, |token| token.span.end)
+            .max(source.len());
+        tokens.push(Token::eof(offset));
+
+        let end = Element::Terminal("#".to_string());
+        let mut forest = Forest::new(self.tables.generated.clone());
+        let mut gss = vec![GssNode {
+            state: 0,
+            level: 0,
+            edges: Vec::new(),
+        }];
+        let mut frontier = vec![0];
+        let mut errors = Vec::new();
+        let mut recovering = false;
+
+        for (pos, token) in tokens.iter().enumerate() {
+            self.reduce_all(&mut gss, &mut frontier, &mut forest, &token.kind, pos);
+
+            if token.kind == end {
+                let root = frontier
+                    .iter()
+                    .filter(|&&v| {
+                        self.tables
+                            .actions(gss[v].state, &end)
+                            .contains(&Action::Accept)
+                    })
+                    .flat_map(|&v| gss[v].edges.iter().filter_map(|(_, label)| *label))
+                    .next();
+                if root.is_none() && !recovering {
+                    errors.push(self.unexpected(&gss, &frontier, pos, token));
+                }
+                forest.set_root(root);
+                break;
+            }
+
+            let leaf = forest.token(pos, token.kind.clone());
+            let mut next = Vec::new();
+            for &v in &frontier {
+                for action in self.tables.actions(gss[v].state, &token.kind) {
+                    if let Action::Shift(target) = action {
+                        link(&mut gss, &mut next, *target, pos + 1, v, Some(leaf));
+                    }
+                }
+            }
+            if next.is_empty() {
+                // 没有栈能移进当前记号：报错并跳过它，保留原来的栈顶
+                if !recovering {
+                    errors.push(self.unexpected(&gss, &frontier, pos, token));
+                }
+                recovering = true;
+            } else {
+                frontier = next;
+                recovering = false;
+            }
+        }
+        ParseOutcome::new(forest, errors, source.to_string())
+    }
+
+    /// 对所有栈顶反复做 ε 移进和归约，直到不再产生新的栈顶或新的边
+    fn reduce_all(
+        &self,
+        gss: &mut Vec<GssNode>,
+        frontier: &mut Vec<usize>,
+        forest: &mut Forest,
+        lookahead: &Element,
+        pos: usize,
+    ) {
+        let epsilon = Element::Terminal("ε".to_string());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let mut i = 0;
+            while i < frontier.len() {
+                let v = frontier[i];
+                let state = gss[v].state;
+                for action in self.tables.actions(state, &epsilon) {
+                    if let Action::Shift(target) = action {
+                        changed |= link(gss, frontier, *target, pos, v, None);
+                    }
+                }
+                for action in self.tables.actions(state, lookahead) {
+                    let Action::Reduce(head, body) = action else {
+                        continue;
+                    };
+                    let prod = self.tables.tables.prod_id(head, body);
+                    for (u, labels) in paths(gss, v, body.len()) {
+                        let node = forest.symbol(head.clone(), gss[u].level, pos);
+                        forest.add_family(node, prod, labels.into_iter().flatten().collect());
+                        if let Some(target) = self.tables.tables.goto(gss[u].state, head) {
+                            changed |= link(gss, frontier, target, pos, u, Some(node));
+                        }
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
+
+    fn unexpected(
+        &self,
+        gss: &[GssNode],
+        frontier: &[usize],
+        pos: usize,
+        token: &Token,
+    ) -> ParserError {
+        let states: HashSet<State> = frontier.iter().map(|&v| gss[v].state).collect();
+        let expected = self
+            .tables
+            .tables
+            .terminals()
+            .iter()
+            .filter(|symbol| {
+                states
+                    .iter()
+                    .any(|&state| !self.tables.actions(state, symbol).is_empty())
+            })
+            .cloned()
+            .collect();
+        ParserError::unexpected(pos, token.span, token.kind.clone(), expected)
+    }
+}
+
+/// 在栈顶 `frontier` 中找到（或新建）状态为 `state` 的节点，并添加指向 `below` 的边。
+/// 添加了新边时返回 true
+fn link(
+    gss: &mut Vec<GssNode>,
+    frontier: &mut Vec<usize>,
+    state: State,
+    level: usize,
+    below: usize,
+    label: Option<ForestId>,
+) -> bool {
+    let top = match frontier.iter().find(|&&v| gss[v].state == state) {
+        Some(&v) => v,
+        None => {
+            gss.push(GssNode {
+                state,
+                level,
+                edges: Vec::new(),
+            });
+            frontier.push(gss.len() - 1);
+            gss.len() - 1
+        }
+    };
+    let edge = (below, label);
+    if gss[top].edges.contains(&edge) {
+        return false;
+    }
+    gss[top].edges.push(edge);
+    true
+}
+
+/// 从 `top` 往下长度为 `len` 的所有路径：路径底部的节点，以及从左到右的边上的符号
+fn paths(gss: &[GssNode], top: usize, len: usize) -> Vec<(usize, Vec<Option<ForestId>>)> {
+    if len == 0 {
+        return vec![(top, Vec::new())];
+    }
+    let mut result = Vec::new();
+    for &(below, label) in &gss[top].edges {
+        for (bottom, mut labels) in paths(gss, below, len - 1) {
+            labels.push(label);
+            result.push((bottom, labels));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forest(grammar: &str, input: &str) -> ParseOutcome<Forest> {
+        let grammar: Grammar = grammar.parse().unwrap();
+        let parser = LR1Parser::from_grammar(&grammar);
+        let tables = Arc::new(GLRTables::new(&parser, &grammar));
+        GLRDriver::new(tables).construct_forest(&Element::terminals(input))
+    }
+
+    #[test]
+    fn ambiguous_sums() {
+        let grammar = "%token a\nCompUnit': E ;\nE: E '+' E | a ;";
+        // 树的数目是卡特兰数
+        for (input, count) in [
+            ("a", 1),
+            ("a '+' a", 1),
+            ("a '+' a '+' a", 2),
+            ("a '+' a '+' a '+' a", 5),
+        ] {
+            let outcome = forest(grammar, input);
+            assert!(outcome.errors.is_empty());
+            assert_eq!(outcome.tree.tree_count(), Some(count), "`{}`", input);
+            assert_eq!(outcome.tree.is_ambiguous(), count > 1);
+            assert_eq!(outcome.tree.trees(10).len(), count as usize);
+        }
+    }
+
+    #[test]
+    fn nullable_operands() {
+        let grammar = "%token a\nCompUnit': E ;\nE: E '+' E | a | ;";
+        assert_eq!(forest(grammar, "").tree.tree_count(), Some(1));
+        assert_eq!(forest(grammar, "'+'").tree.tree_count(), Some(1));
+        // (a + ε) + a 和 a + (ε + a)
+        assert_eq!(forest(grammar, "a '+' '+' a").tree.tree_count(), Some(2));
+    }
+
+    #[test]
+    fn cyclic_grammars() {
+        for grammar in [
+            "%token a\nCompUnit': S ;\nS: S | a ;",
+            // 经过 ε 的环：S ⇒ S A ⇒ S
+            "%token a\nCompUnit': S ;\nS: S A | a ;\nA: ;",
+        ] {
+            let outcome = forest(grammar, "a");
+            assert!(outcome.errors.is_empty());
+            assert_eq!(outcome.tree.tree_count(), None, "{}", grammar);
+            let tree = outcome.tree.tree().unwrap();
+            assert_eq!(tree.element, Element::NotTerminal("S".to_string()));
+        }
+    }
+
+    #[test]
+    fn syntax_error_has_no_root() {
+        let outcome = forest("%token a\nCompUnit': E ;\nE: E '+' E | a ;", "a '+'");
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.tree.root(), None);
+        assert_eq!(outcome.tree.tree_count(), None);
+    }
+}
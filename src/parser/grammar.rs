@@ -1,125 +1,483 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::fs::File;
 use std::io::Read;
+use std::iter::Peekable;
+use crate::parser::grammar_reader::{self, Entry, GrammarError, Spanned, Tok};
+use crate::parser::import::{self, GrammarFormat};
+use crate::parser::lr1_parser::ERROR_TOKEN;
 use crate::parser::types::{Element, Item, PBody, PHead};
 
 pub struct Grammar {
   file_buff: String,
   pub(crate) token_list: Vec<String>,
+  pub(crate) sync_list: Vec<String>,
+  pub(crate) pair_list: Vec<(String, String)>,
+  /// 化简语法树时并入父节点的辅助非终结符
+  pub(crate) inline_list: Vec<String>,
+  /// 化简语法树时展平为子节点序列的递归列表
+  pub(crate) flatten_list: Vec<String>,
   pub(crate) pro_list: HashMap<PHead, PBody>,
+  /// 产生式左部在文件中出现的顺序
+  pub(crate) head_list: Vec<PHead>,
+  /// 每个候选式末尾的 `=> { ... }` 语义动作，与 pro_list 中的候选式一一对应
+  pub(crate) action_list: HashMap<PHead, Vec<Option<String>>>,
+  /// `%type` 声明的非终结符的值类型
+  pub(crate) type_list: HashMap<PHead, String>,
+  /// EBNF 的括号和后缀运算符生成的非终结符
+  ebnf_list: HashMap<(PBody, char), PHead>,
   pub(crate) first_sets: HashMap<Element, HashSet<Element>>,
   pub(crate) start_symbol: Element,
 }
 
+impl Default for Grammar {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl std::str::FromStr for Grammar {
+  type Err = GrammarError;
+  /// 按本项目的格式读入文法文本，见 `grammar_parse`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let mut grammar = Self::new();
+    grammar.grammar_parse(text)?;
+    Ok(grammar)
+  }
+}
+
 impl Grammar {
   pub fn new() -> Self {
     Self {
       token_list: Vec::<String>::new(),
+      sync_list: Vec::<String>::new(),
+      pair_list: Vec::<(String, String)>::new(),
+      inline_list: Vec::<String>::new(),
+      flatten_list: Vec::<String>::new(),
       pro_list: HashMap::<PHead, PBody>::new(),
+      head_list: Vec::<PHead>::new(),
+      action_list: HashMap::<PHead, Vec<Option<String>>>::new(),
+      type_list: HashMap::<PHead, String>::new(),
+      ebnf_list: HashMap::<(PBody, char), PHead>::new(),
       first_sets: HashMap::<Element, HashSet<Element>>::new(),
       file_buff: String::new(),
       start_symbol: Element::NotTerminal("CompUnit'".to_string()),
     }
   }
 
-  fn file_load(&mut self, file_path: &str) {
-    let mut file = File::open(file_path).expect("File open Err!!{}");
-    file.read_to_string(&mut self.file_buff).expect("File read Err!!");
+  fn file_load(&mut self, file_path: &str) -> std::io::Result<()> {
+    let mut file = File::open(file_path)?;
+    file.read_to_string(&mut self.file_buff)?;
+    Ok(())
   }
 
-  pub fn grammar_load(&mut self, grammar_path: &str) {
-    self.file_load(grammar_path);
-    let lines = self.file_buff.lines();
-    for line in lines {
+  pub fn grammar_load(&mut self, grammar_path: &str) -> Result<(), GrammarError> {
+    self.file_load(grammar_path)
+      .map_err(|e| GrammarError::new(format!("cannot read the grammar file: {}", e)))?;
+    let file_buff = std::mem::take(&mut self.file_buff);
+    let format = GrammarFormat::detect(grammar_path, &file_buff);
+    let result = self.grammar_parse_as(&file_buff, format);
+    self.file_buff = file_buff;
+    result
+  }
+
+  /// 读入 `format` 格式的文法。其他格式先转换成本项目的格式，转换时增广的开始符号用 %start 声明
+  pub fn grammar_parse_as(&mut self, text: &str, format: GrammarFormat) -> Result<(), GrammarError> {
+    match import::convert(text, format)? {
+      None => self.grammar_parse(text),
+      Some(imported) => self.grammar_parse(&imported.text),
+    }
+  }
 
-      if line.starts_with('{') { continue; }
-      if line.starts_with('}') { continue; }
+  /// 读入文法文本。规则 `Head: A B | C ;` 可以跨行，`;` 可以省略；
+  /// 候选式末尾的 `=> { ... }` 是语义动作，其他位置的 `{ ... }` 是 EBNF 的重复。
+  /// 支持 `//`、`/* */` 注释，旧格式的 `#|#` 等同于 `|`，用单独一行的 `{`、`}` 包住规则时要先声明 `%legacy`。
+  /// 开始符号默认是 `CompUnit'`，可以用 `%start Name` 指定
+  pub fn grammar_parse(&mut self, text: &str) -> Result<(), GrammarError> {
+    let entries = grammar_reader::parse(text)?;
 
-      if line.starts_with("%token") {
-        let tokens = line.split_whitespace().skip(1);
-        for token in tokens {
-          self.token_list.push(token.to_string());
-        }
-        continue;
+    // 先处理全部声明，规则中的符号是否是终结符与声明的位置无关
+    for entry in &entries {
+      if let Entry::Directive { name, args, offset } = entry {
+        self.directive(text, name, *args, *offset)?;
       }
-      {
-
-        let mut tmp = line.split(':'); // 只有可能是两部分
-
-        let p_head = PHead::NotTerminal(tmp.next().unwrap().to_string());
-        let mut p_body = PBody::new();
-        let items = tmp.next().unwrap().split("#|#"); // 拆分右部
-        for item in items {
-          let elements = item.split_whitespace();
-          let mut item = Item::new();
-          for element in elements {
-            item.push(
-              if self.token_list.contains(&element.to_string()) {
-                Element::Terminal(element.to_string())
-              } else {
-                Element::NotTerminal(element.to_string())
-              }
-            );
-          }
-          p_body.push(item);
+    }
+
+    // 规则中没有声明为终结符的符号要是某条规则的左部
+    let heads: HashSet<&str> = entries
+      .iter()
+      .filter_map(|entry| match entry {
+        Entry::Rule { head, .. } => Some(head.as_str()),
+        _ => None,
+      })
+      .collect();
+
+    for entry in &entries {
+      let Entry::Rule { head, offset, alternatives } = entry else { continue };
+      if self.token_list.contains(head) {
+        return Err(GrammarError::at(text, *offset, format!("`{}` is declared as a token and cannot be the head of a rule", head)));
+      }
+      let p_head = PHead::NotTerminal(head.to_string());
+      let mut p_body = PBody::new();
+      let mut actions = Vec::new();
+      let position = self.head_list.len();
+      for alternative in alternatives {
+        let mut alternative = alternative.as_slice();
+        let mut action = None;
+        if let Some((Spanned { tok: Tok::Action(start, end), .. }, rest)) = alternative.split_last() {
+          action = Some(text[*start..*end].trim().to_string());
+          alternative = rest;
+        }
+        let mut words = alternative.iter().peekable();
+        let items = self.ebnf_alternatives(text, &p_head, &mut words, &heads)?;
+        if let Some(word) = words.next() {
+          return Err(GrammarError::at(text, word.offset, "unmatched closing bracket"));
         }
-        self.pro_list.insert(p_head, p_body);
+        actions.push(action);
+        p_body.extend(items);
       }
+      // 同一个左部的多条规则合并；EBNF 生成的非终结符排在它们所在的规则之后
+      if !self.pro_list.contains_key(&p_head) {
+        self.head_list.insert(position, p_head.clone());
+      }
+      self.action_list.entry(p_head.clone()).or_default().extend(actions);
+      self.pro_list.entry(p_head).or_default().extend(p_body);
     }
 
+    if !self.pro_list.contains_key(&self.start_symbol) {
+      let message = format!("the start symbol `{}` has no rule; add one or declare another with %start", self.start_symbol);
+      return Err(GrammarError::new(message));
+    }
+    if self.pro_list.values().flatten().any(|item| item.contains(&self.start_symbol)) {
+      let message = format!(
+        "the start symbol `{}` appears on the right side of a rule; add a rule like `{}' : {} ;` and declare it with %start",
+        self.start_symbol, self.start_symbol, self.start_symbol
+      );
+      return Err(GrammarError::new(message));
+    }
     self.calculate_first_sets();
+    Ok(())
   }
 
-  fn first(&mut self, symbol: &Element) -> HashSet<Element> {
-    if let Some(first_set) = self.first_sets.get(symbol) {
-      return first_set.clone();
+  fn directive(&mut self, text: &str, name: &str, args: (usize, usize), offset: usize) -> Result<(), GrammarError> {
+    let (start, end) = args;
+    let symbols = |start: usize| -> Result<Vec<String>, GrammarError> {
+      grammar_reader::tokenize(text, start, end)?
+        .into_iter()
+        .map(|word| match word.tok {
+          Tok::Symbol(symbol) => Ok(symbol),
+          _ => Err(GrammarError::at(text, word.offset, format!("expected a symbol in %{}", name))),
+        })
+        .collect()
+    };
+    match name {
+      "token" => self.token_list.extend(symbols(start)?),
+      // 错误恢复用的同步记号：%sync ';' NEWLINE
+      "sync" => self.sync_list.extend(symbols(start)?),
+      // 成对的括号：%pair 'begin' 'end'
+      "pair" => match symbols(start)?.as_slice() {
+        [open, close] => self.pair_list.push((open.clone(), close.clone())),
+        _ => return Err(GrammarError::at(text, offset, "%pair takes an opening and a closing token")),
+      },
+      // 化简语法树：%inline ExpAux 并入父节点，%flatten BlockItemRepeat 展平递归列表
+      "inline" => self.inline_list.extend(symbols(start)?),
+      "flatten" => self.flatten_list.extend(symbols(start)?),
+      // 旧格式：单独一行的 `{`、`}` 包住规则，读入时跳过这些行
+      "legacy" if text[start..end].trim().is_empty() => (),
+      "legacy" => return Err(GrammarError::at(text, offset, "%legacy takes no arguments")),
+      // 开始符号：%start Program
+      "start" => match symbols(start)?.as_slice() {
+        [symbol] if !symbol.starts_with('\'') => self.start_symbol = PHead::NotTerminal(symbol.clone()),
+        _ => return Err(GrammarError::at(text, offset, "%start takes one nonterminal")),
+      },
+      // 非终结符的值类型：%type <Vec<Stmt>> Block BlockItemList
+      "type" => {
+        let Some((ty, names)) = split_type(text[start..end].trim()) else {
+          return Err(GrammarError::at(text, offset, "expected `<Type>` after %type"));
+        };
+        for name in symbols(end - names.len())? {
+          self.type_list.insert(PHead::NotTerminal(name), ty.to_string());
+        }
+      }
+      _ => return Err(GrammarError::at(text, offset, format!("unknown directive `%{}`", name))),
     }
+    Ok(())
+  }
 
-    let mut result = HashSet::new();
-    match symbol {
-      Element::Terminal(_) => {
-        result.insert(symbol.clone());
+
+  /// 产生式中的符号。带引号的字面量、保留的 `error` 和 ε 不需要声明就是终结符，
+  /// 其余的符号要在 %token 中声明或有自己的规则，否则返回 None
+  fn element(&mut self, symbol: &str, heads: &HashSet<&str>) -> Option<Element> {
+    if symbol.starts_with('\'') || symbol == ERROR_TOKEN || symbol == "ε" {
+      Some(self.terminal(symbol))
+    } else if self.token_list.iter().any(|token| token == symbol) {
+      Some(Element::Terminal(symbol.to_string()))
+    } else if heads.contains(symbol) {
+      Some(Element::NotTerminal(symbol.to_string()))
+    } else {
+      None
+    }
+  }
+
+  /// 终结符，没有声明的补到 token_list 中
+  fn terminal(&mut self, symbol: &str) -> Element {
+    if !self.token_list.iter().any(|token| token == symbol) {
+      self.token_list.push(symbol.to_string());
+    }
+    Element::Terminal(symbol.to_string())
+  }
+
+  /// 解析 `A B | C ...`，返回各个候选式。遇到右括号或结尾时停止
+  fn ebnf_alternatives<'a>(
+    &mut self,
+    text: &str,
+    head: &PHead,
+    words: &mut Peekable<impl Iterator<Item = &'a Spanned>>,
+    heads: &HashSet<&str>,
+  ) -> Result<PBody, GrammarError> {
+    let mut alternatives = vec![Item::new()];
+    while let Some(word) = words.peek() {
+      let offset = word.offset;
+      let group = match &word.tok {
+        Tok::Close(_) => break,
+        Tok::Bar => {
+          alternatives.push(Item::new());
+          None
+        }
+        Tok::Open(open) => {
+          let open = *open;
+          words.next();
+          let inner = self.ebnf_alternatives(text, head, words, heads)?;
+          let close = if open == '(' { ')' } else { ']' };
+          if words.peek().map(|word| &word.tok) != Some(&Tok::Close(close)) {
+            return Err(GrammarError::at(text, offset, format!("unclosed `{}`", open)));
+          }
+          words.next();
+          // 括号后的后缀运算符作用于整个括号
+          let repeat = match words.peek().map(|word| &word.tok) {
+            Some(Tok::Postfix(repeat)) => *repeat,
+            _ => {
+              let group = self.ebnf_group(head, inner, if open == '[' { '?' } else { ' ' });
+              alternatives.last_mut().unwrap().extend(group);
+              continue;
+            }
+          };
+          let inner = if open == '[' { vec![self.ebnf_group(head, inner, '?')] } else { inner };
+          Some(self.ebnf_group(head, inner, repeat))
+        }
+        Tok::Action(..) => {
+          return Err(GrammarError::at(text, offset, "a semantic action must be at the end of an alternative"));
+        }
+        Tok::Brace(start, end) => {
+          let inner_words = grammar_reader::tokenize(text, *start, *end)?;
+          let mut inner_words = inner_words.iter().peekable();
+          let inner = self.ebnf_alternatives(text, head, &mut inner_words, heads)?;
+          if let Some(word) = inner_words.next() {
+            return Err(GrammarError::at(text, word.offset, "unmatched closing bracket"));
+          }
+          Some(self.ebnf_group(head, inner, '*'))
+        }
+        Tok::Postfix(repeat) => {
+          // 后缀运算符作用于前一个符号
+          let repeat = *repeat;
+          let Some(last) = alternatives.last_mut().unwrap().pop() else {
+            return Err(GrammarError::at(text, offset, format!("`{}` without an operand", repeat)));
+          };
+          Some(self.ebnf_group(head, vec![vec![last]], repeat))
+        }
+        Tok::Symbol(symbol) => match self.element(symbol, heads) {
+          Some(element) => Some(vec![element]),
+          None => {
+            return Err(GrammarError::at(
+              text,
+              offset,
+              format!("undeclared symbol `{}`: declare it with %token or add a rule for it", symbol),
+            ))
+          }
+        },
+        Tok::Colon | Tok::Semi | Tok::Directive(..) => {
+          return Err(GrammarError::at(text, offset, "unexpected token in a production"));
+        }
+      };
+      alternatives.last_mut().unwrap().extend(group.into_iter().flatten());
+      words.next();
+    }
+    // 空的候选式即 ε
+    for item in alternatives.iter_mut().filter(|item| item.is_empty()) {
+      item.push(self.terminal("ε"));
+    }
+    Ok(alternatives)
+  }
+
+  /// 把括号或后缀运算符作用的候选式换成生成的非终结符：
+  /// `X?` 为 `X | ε`，`X*` 为 `X R | ε`，`X+` 为 `X R | X`，`( A | B )` 为 `A | B`
+  fn ebnf_group(&mut self, head: &PHead, inner: PBody, repeat: char) -> Item {
+    let single = inner.len() == 1 && !inner[0].contains(&Element::Terminal("ε".to_string()));
+    if repeat == ' ' && single {
+      return inner.into_iter().next().unwrap();
+    }
+    let key = (inner.clone(), repeat);
+    if let Some(symbol) = self.ebnf_list.get(&key) {
+      return vec![symbol.clone()];
+    }
+    let symbol = Element::NotTerminal(format!("{}@{}", head, self.ebnf_list.len() + 1));
+    let mut body = PBody::new();
+    for item in &inner {
+      let item: Item = item.iter().filter(|v| **v != Element::Terminal("ε".to_string())).cloned().collect();
+      match repeat {
+        '*' | '+' => body.push([item.clone(), vec![symbol.clone()]].concat()),
+        _ => body.push(item),
       }
-      Element::NotTerminal(_) => {
-        if let Some(productions) = self.pro_list.get(symbol) {
+    }
+    match repeat {
+      '*' | '?' => body.push(Vec::new()),
+      '+' => body.extend(inner.iter().cloned()),
+      _ => (),
+    }
+    let epsilon = self.terminal("ε");
+    for item in body.iter_mut().filter(|item| item.is_empty()) {
+      item.push(epsilon.clone());
+    }
+    self.head_list.push(symbol.clone());
+    self.action_list.insert(symbol.clone(), vec![None; body.len()]);
+    self.pro_list.insert(symbol.clone(), body);
+    self.ebnf_list.insert(key, symbol.clone());
+    vec![symbol]
+  }
+
+  /// EBNF 展开时生成的非终结符，分析树中它们的子节点会并入父节点
+  pub(crate) fn generated(&self) -> HashSet<PHead> {
+    self.ebnf_list.values().cloned().collect()
+  }
+
+  /// 生成的非终结符对应的括号内容和运算符（`' '`、`?`、`*`、`+`），导出时还原成 EBNF
+  pub(crate) fn ebnf_groups(&self) -> HashMap<PHead, (PBody, char)> {
+    self.ebnf_list
+      .iter()
+      .map(|((inner, repeat), symbol)| (symbol.clone(), (inner.clone(), *repeat)))
+      .collect()
+  }
+
+  /// 由变换得到的产生式构造文法，声明和开始符号沿用原文法；语义动作和 EBNF 的记录不保留
+  pub(crate) fn with_productions(&self, head_list: Vec<PHead>, pro_list: HashMap<PHead, PBody>) -> Grammar {
+    let mut grammar = Grammar::new();
+    grammar.token_list = self.token_list.clone();
+    if !grammar.token_list.iter().any(|token| token == "ε") {
+      grammar.token_list.push("ε".to_string());
+    }
+    grammar.sync_list = self.sync_list.clone();
+    grammar.pair_list = self.pair_list.clone();
+    grammar.inline_list = self.inline_list.clone();
+    grammar.flatten_list = self.flatten_list.clone();
+    grammar.type_list = self.type_list.clone();
+    grammar.start_symbol = self.start_symbol.clone();
+    grammar.action_list = head_list.iter().map(|head| (head.clone(), vec![None; pro_list[head].len()])).collect();
+    grammar.head_list = head_list;
+    grammar.pro_list = pro_list;
+    grammar.calculate_first_sets();
+    grammar
+  }
+
+  /// 编号为 `id` 的产生式的语义动作
+  pub(crate) fn action(&self, id: usize) -> Option<&str> {
+    self.head_list
+      .iter()
+      .flat_map(|head| self.action_list[head].iter())
+      .nth(id)?
+      .as_deref()
+  }
+
+  /// 按文件中的顺序列出全部产生式，下标即产生式编号 `ProdId`
+  pub(crate) fn productions(&self) -> Vec<(PHead, Item)> {
+    self.head_list
+      .iter()
+      .flat_map(|head| self.pro_list[head].iter().map(move |body| (head.clone(), body.clone())))
+      .collect()
+  }
 
-          let productions: Vec<Vec<_>> = productions.to_vec();
+  /// 文法内容的指纹：开始符号、终结符和全部产生式相同的文法指纹相同，用作分析表缓存的键
+  pub(crate) fn fingerprint(&self) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    self.start_symbol.hash(&mut hasher);
+    self.token_list.hash(&mut hasher);
+    self.productions().hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// 不动点迭代计算所有非终结符的 FIRST 集，左递归的文法也能处理
+  fn calculate_first_sets(&mut self) {
+    let epsilon = Element::Terminal("ε".to_string());
+    self.first_sets = self.pro_list.keys().map(|head| (head.clone(), HashSet::new())).collect();
 
-          for production in productions {
-            let first_symbol = &production[0];
-            match first_symbol {
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for (head, bodies) in &self.pro_list {
+        for body in bodies {
+          let mut first = HashSet::new();
+          let mut nullable = true;
+          for symbol in body.iter().filter(|symbol| **symbol != epsilon) {
+            match symbol {
               Element::Terminal(_) => {
-                result.insert(first_symbol.clone());
+                first.insert(symbol.clone());
+                nullable = false;
               }
               Element::NotTerminal(_) => {
-                let mut first_set = self.first(first_symbol);
-                let mut i = 1;
-                while i < production.len() && first_set.contains(&Element::Terminal("ε".to_string())) {
-                  first_set.remove(&Element::Terminal("ε".to_string()));
-                  result.extend(first_set);
-
-                  let next_symbol = &production[i];
-                  first_set = self.first(next_symbol);
-                  i += 1;
-                }
-                result.extend(first_set);
+                let set = self.first_sets.get(symbol);
+                first.extend(set.into_iter().flatten().filter(|v| **v != epsilon).cloned());
+                nullable = set.is_some_and(|set| set.contains(&epsilon));
               }
             }
+            if !nullable {
+              break;
+            }
+          }
+          if nullable {
+            first.insert(epsilon.clone());
           }
+
+          let set = self.first_sets.get_mut(head).unwrap();
+          let len = set.len();
+          set.extend(first);
+          changed |= set.len() > len;
         }
       }
     }
-
-    self.first_sets.insert(symbol.clone(), result.clone());
-    result
   }
 
-  fn calculate_first_sets(&mut self) {
-    let non_terminals: Vec<_> = self.pro_list.keys().cloned().collect();
+  /// 不动点迭代计算所有非终结符的 FOLLOW 集，开始符号的 FOLLOW 集含 `#`
+  pub(crate) fn follow_sets(&self) -> HashMap<PHead, HashSet<Element>> {
+    let epsilon = Element::Terminal("ε".to_string());
+    let mut follow_sets: HashMap<PHead, HashSet<Element>> =
+      self.pro_list.keys().map(|head| (head.clone(), HashSet::new())).collect();
+    if let Some(set) = follow_sets.get_mut(&self.start_symbol) {
+      set.insert(Element::Terminal("#".to_string()));
+    }
 
-    for non_terminal in non_terminals {
-      self.first(&non_terminal);
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for (head, bodies) in &self.pro_list {
+        for body in bodies {
+          let body: Vec<Element> = body.iter().filter(|symbol| **symbol != epsilon).cloned().collect();
+          for (i, symbol) in body.iter().enumerate() {
+            if !matches!(symbol, Element::NotTerminal(_)) {
+              continue;
+            }
+            let mut follow = self.first_symbols(&body[i + 1..], &epsilon);
+            if follow.remove(&epsilon) {
+              follow.extend(follow_sets[head].iter().cloned());
+            }
+            let set = follow_sets.get_mut(symbol).unwrap();
+            let len = set.len();
+            set.extend(follow);
+            changed |= set.len() > len;
+          }
+        }
+      }
     }
+    follow_sets
   }
 
   pub(crate) fn first_symbols(&self, symbols: &[Element], fallback: &Element) -> HashSet<Element> {
@@ -141,7 +499,7 @@ impl Grammar {
             epsilon = true;
             result.extend(first_set.clone().into_iter().filter(|x| *x != Element::Terminal("ε".to_string())));
           } else {
-            result.extend(first_set.clone().into_iter());
+            result.extend(first_set.clone());
             break;
           }
         }
@@ -154,4 +512,62 @@ impl Grammar {
 
     result
   }
-}
\ No newline at end of file
+}
+
+/// 拆出 `<...>` 中的类型（类型中可以有嵌套的尖括号）和其后的部分
+fn split_type(text: &str) -> Option<(&str, &str)> {
+  let text = text.strip_prefix('<')?;
+  let mut depth = 1;
+  for (i, c) in text.char_indices() {
+    match c {
+      '<' => depth += 1,
+      '>' => depth -= 1,
+      _ => (),
+    }
+    if depth == 0 {
+      return Some((text[..i].trim(), &text[i + 1..]));
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn nt(name: &str) -> Element {
+    Element::NotTerminal(name.to_string())
+  }
+
+  #[test]
+  fn braces_are_repetitions() {
+    // 内容全是文法符号的 `{ Unit }` 也是重复，不是语义动作
+    let grammar: Grammar = "%token a\nCompUnit': S ;\nS: a { Unit } ;\nUnit: a ;".parse().unwrap();
+    assert_eq!(grammar.pro_list[&nt("S")], [vec![Element::Terminal("a".to_string()), nt("S@1")]]);
+    assert_eq!(grammar.pro_list[&nt("S@1")], [vec![nt("Unit"), nt("S@1")], vec![Element::Terminal("ε".to_string())]]);
+    assert_eq!(grammar.action(1), None);
+  }
+
+  #[test]
+  fn actions_follow_an_arrow() {
+    let grammar: Grammar = "%token x\nCompUnit': S ;\nS: x\n=> {\n  1\n}\n| => { \"{}\" }\n;".parse().unwrap();
+    assert_eq!(grammar.action(1), Some("1"));
+    assert_eq!(grammar.action(2), Some("\"{}\""));
+
+    let err = "%token x\nCompUnit': S ;\nS: => { 1 } x ;".parse::<Grammar>().err().unwrap();
+    assert_eq!(err.to_string(), "3:4: a semantic action must be at the end of an alternative");
+    let err = "%token x\nCompUnit': S ;\nS: x => 1 ;".parse::<Grammar>().err().unwrap();
+    assert_eq!(err.to_string(), "3:6: expected `{ ... }` after `=>`");
+  }
+
+  #[test]
+  fn legacy_delimiters_need_the_directive() {
+    let text = "%token x\n{\nCompUnit':S\nS:x#|#S x\n}\n";
+    let err = text.parse::<Grammar>().err().unwrap();
+    assert_eq!((err.line, err.column), (2, 1));
+    assert!(err.message.contains("%legacy"), "{}", err);
+
+    let grammar: Grammar = format!("%legacy\n{}", text).parse().unwrap();
+    assert_eq!(grammar.pro_list[&nt("S")].len(), 2);
+  }
+}
@@ -0,0 +1,477 @@
+use crate::parser::diagnostic::line_col;
+use std::fmt::Display;
+
+/// 文法文件中的错误，行列号从 1 开始；读文件失败时行号为 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+impl GrammarError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            line: 0,
+            column: 0,
+            message: message.into(),
+        }
+    }
+    /// `text` 中字节偏移 `offset` 处的错误
+    pub(crate) fn at(text: &str, offset: usize, message: impl Into<String>) -> Self {
+        let (line, column) = line_col(text, offset);
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        }
+    }
+}
+impl std::error::Error for GrammarError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Tok {
+    /// 符号名，或带引号的字面量（去掉转义后仍用引号括起来，如 `'''`）
+    Symbol(String),
+    Colon,
+    Semi,
+    /// `|`，以及旧格式中的 `#|#`
+    Bar,
+    Open(char),
+    Close(char),
+    /// 紧跟在符号或右括号后的 `*`、`+`、`?`
+    Postfix(char),
+    /// `{ ... }` 内容的字节区间，EBNF 的重复
+    Brace(usize, usize),
+    /// 语义动作 `=> { ... }` 中代码的字节区间
+    Action(usize, usize),
+    /// `%name` 和到行尾为止的参数的字节区间
+    Directive(String, usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Spanned {
+    pub(crate) tok: Tok,
+    pub(crate) offset: usize,
+}
+
+/// 文法文件中的一项：声明，或一条规则及其候选式
+#[derive(Debug, Clone)]
+pub(crate) enum Entry {
+    Directive {
+        name: String,
+        args: (usize, usize),
+        offset: usize,
+    },
+    Rule {
+        head: String,
+        offset: usize,
+        alternatives: Vec<Vec<Spanned>>,
+    },
+}
+
+/// 切分 `text[start..end]`。声明了 `%legacy` 之后，单独一行的 `{`、`}` 是旧格式中包住规则的括号，直接跳过
+pub(crate) fn tokenize(text: &str, start: usize, end: usize) -> Result<Vec<Spanned>, GrammarError> {
+    let mut tokens = Vec::new();
+    let mut pos = start;
+    // 上一个记号紧挨着当前位置时，`*`、`+`、`?` 是后缀运算符
+    let mut attached = false;
+    let mut legacy = false;
+    while pos < end {
+        let rest = &text[pos..end];
+        let c = rest.chars().next().unwrap();
+        let line_start = pos == 0 || text.as_bytes()[pos - 1] == b'\n';
+        let line_len = rest.find('\n').unwrap_or(rest.len());
+        if legacy && line_start && matches!(rest[..line_len].trim_end(), "{" | "}") {
+            pos += line_len;
+            attached = false;
+            continue;
+        }
+        if c.is_whitespace() || rest.starts_with("//") || rest.starts_with("/*") {
+            pos += match c {
+                _ if c.is_whitespace() => c.len_utf8(),
+                _ if rest.starts_with("//") => line_len,
+                _ => match rest[2..].find("*/") {
+                    Some(len) => len + 4,
+                    None => return Err(GrammarError::at(text, pos, "unterminated comment")),
+                },
+            };
+            attached = false;
+            continue;
+        }
+
+        let (tok, len) = match c {
+            _ if rest.starts_with("#|#") => (Tok::Bar, 3),
+            _ if rest.starts_with("=>") => {
+                let open = pos + 2 + trivia_len(&text[pos + 2..end]);
+                let close = text[open..end]
+                    .starts_with('{')
+                    .then(|| matching_brace(&text[..end], open));
+                match close.flatten() {
+                    Some(close) => (Tok::Action(open + 1, close), close + 1 - pos),
+                    None => {
+                        return Err(GrammarError::at(text, pos, "expected `{ ... }` after `=>`"))
+                    }
+                }
+            }
+            '|' => (Tok::Bar, 1),
+            ':' => (Tok::Colon, 1),
+            ';' => (Tok::Semi, 1),
+            '(' | '[' => (Tok::Open(c), 1),
+            ')' | ']' => (Tok::Close(c), 1),
+            '}' => return Err(GrammarError::at(text, pos, "unmatched `}`")),
+            '{' => match matching_brace(&text[..end], pos) {
+                Some(close) => (Tok::Brace(pos + 1, close), close + 1 - pos),
+                None => return Err(GrammarError::at(text, pos, "unclosed `{`")),
+            },
+            '*' | '+' | '?' if attached => (Tok::Postfix(c), 1),
+            '%' => {
+                let name_len = name_len(&rest[1..]);
+                if name_len == 0 {
+                    return Err(GrammarError::at(
+                        text,
+                        pos,
+                        "expected a directive name after `%`",
+                    ));
+                }
+                let name = rest[1..1 + name_len].to_string();
+                (
+                    Tok::Directive(name, pos + 1 + name_len, pos + line_len),
+                    line_len,
+                )
+            }
+            '\'' => match quoted(&rest[..line_len]) {
+                Some((_, 2)) => return Err(GrammarError::at(text, pos, "empty quoted literal")),
+                Some((symbol, len)) => (Tok::Symbol(symbol), len),
+                None => return Err(GrammarError::at(text, pos, "unterminated quoted literal")),
+            },
+            _ => {
+                let len = name_len(rest).max(c.len_utf8());
+                (Tok::Symbol(rest[..len].to_string()), len)
+            }
+        };
+        legacy |= matches!(&tok, Tok::Directive(name, ..) if name == "legacy");
+        attached = !matches!(
+            tok,
+            Tok::Open(_) | Tok::Bar | Tok::Colon | Tok::Semi | Tok::Directive(..) | Tok::Action(..)
+        );
+        tokens.push(Spanned { tok, offset: pos });
+        pos += len;
+    }
+    Ok(tokens)
+}
+
+/// 把文法文件分成声明和规则。规则写作 `Head: A B | C ;`，可以跨行；
+/// 省略 `;` 时规则在下一个 `Name:` 或声明之前结束
+pub(crate) fn parse(text: &str) -> Result<Vec<Entry>, GrammarError> {
+    let tokens = tokenize(text, 0, text.len())?;
+    let starts_rule = |i: usize| {
+        matches!(
+            (
+                tokens.get(i).map(|t| &t.tok),
+                tokens.get(i + 1).map(|t| &t.tok)
+            ),
+            (Some(Tok::Symbol(_)), Some(Tok::Colon))
+        )
+    };
+
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while let Some(token) = tokens.get(i) {
+        match &token.tok {
+            Tok::Directive(name, start, end) => {
+                entries.push(Entry::Directive {
+                    name: name.clone(),
+                    args: (*start, *end),
+                    offset: token.offset,
+                });
+                i += 1;
+            }
+            Tok::Symbol(head) if starts_rule(i) => {
+                if head.starts_with('\'') {
+                    return Err(GrammarError::at(
+                        text,
+                        token.offset,
+                        "the head of a rule must be a nonterminal name",
+                    ));
+                }
+                i += 2;
+                // 只在括号外的 `|` 处分开候选式
+                let mut alternatives = vec![Vec::new()];
+                let mut depth = 0usize;
+                while let Some(next) = tokens.get(i) {
+                    match &next.tok {
+                        Tok::Semi if depth == 0 => {
+                            i += 1;
+                            break;
+                        }
+                        Tok::Directive(..) => break,
+                        Tok::Symbol(_) if starts_rule(i) => break,
+                        Tok::Colon => {
+                            return Err(GrammarError::at(text, next.offset, "unexpected `:`"));
+                        }
+                        Tok::Bar if depth == 0 => alternatives.push(Vec::new()),
+                        tok => {
+                            match tok {
+                                Tok::Open(_) => depth += 1,
+                                Tok::Close(_) => depth = depth.saturating_sub(1),
+                                _ => (),
+                            }
+                            alternatives.last_mut().unwrap().push(next.clone());
+                        }
+                    }
+                    i += 1;
+                }
+                entries.push(Entry::Rule {
+                    head: head.clone(),
+                    offset: token.offset,
+                    alternatives,
+                });
+            }
+            Tok::Brace(..) => {
+                return Err(GrammarError::at(
+                    text,
+                    token.offset,
+                    "expected a rule like `Name: ...`; declare %legacy to wrap the rules in `{` and `}` lines",
+                ));
+            }
+            _ => {
+                return Err(GrammarError::at(
+                    text,
+                    token.offset,
+                    "expected a rule like `Name: ...` or a `%` directive",
+                ));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// 符号名的长度：到空白、括号或 `|:;*+?` 为止，遇到 `=>` 或旧格式的 `#|#` 也停止
+fn name_len(text: &str) -> usize {
+    text.char_indices()
+        .find(|&(i, c)| {
+            c.is_whitespace()
+                || "()[]{}|:;*+?".contains(c)
+                || text[i..].starts_with("#|#")
+                || text[i..].starts_with("=>")
+        })
+        .map_or(text.len(), |(i, _)| i)
+}
+
+/// 开头的空白和注释的长度
+fn trivia_len(text: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &text[len..];
+        if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+            len += c.len_utf8();
+        } else if rest.starts_with("//") {
+            len += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(body) = rest.strip_prefix("/*").and_then(|body| body.find("*/")) {
+            len += body + 4;
+        } else {
+            return len;
+        }
+    }
+}
+
+/// 以 `'` 开头的字面量，`\'` 和 `\\` 是转义。返回去掉转义后的符号名和在源文本中的长度
+fn quoted(text: &str) -> Option<(String, usize)> {
+    let mut symbol = String::from("'");
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => symbol.push(chars.next()?.1),
+            '\'' => {
+                symbol.push('\'');
+                return Some((symbol, i + 1));
+            }
+            _ => symbol.push(c),
+        }
+    }
+    None
+}
+
+/// 与 `text[open]` 处的 `{` 配对的 `}` 的位置。跳过引号、字符串和注释中的括号，
+/// 这样语义动作中可以写 `"{}"` 之类的代码
+pub(crate) fn matching_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = open;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let c = rest.chars().next().unwrap();
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(pos);
+                }
+            }
+            '\'' => {
+                // 没有在同一行闭合的单引号（如 Rust 的生命周期）按普通字符处理
+                let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+                if let Some((_, len)) = quoted(line) {
+                    pos += len;
+                    continue;
+                }
+            }
+            '"' => {
+                let mut chars = rest.char_indices().skip(1);
+                let close = loop {
+                    match chars.next()? {
+                        (_, '\\') => {
+                            chars.next();
+                        }
+                        (i, '"') => break i,
+                        _ => (),
+                    }
+                };
+                pos += close + 1;
+                continue;
+            }
+            '/' if rest.starts_with("//") => {
+                pos += rest.find('\n').unwrap_or(rest.len());
+                continue;
+            }
+            '/' if rest.starts_with("/*") => {
+                pos += rest[2..].find("*/")? + 4;
+                continue;
+            }
+            _ => (),
+        }
+        pos += c.len_utf8();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::Element;
+    use crate::parser::Grammar;
+
+    fn toks(text: &str) -> Vec<Tok> {
+        tokenize(text, 0, text.len())
+            .unwrap()
+            .into_iter()
+            .map(|v| v.tok)
+            .collect()
+    }
+
+    fn symbol(name: &str) -> Tok {
+        Tok::Symbol(name.to_string())
+    }
+
+    fn error(text: &str) -> GrammarError {
+        text.parse::<Grammar>().err().unwrap()
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let text = "S: a // 注释 | b\n  /* 跨行\n  的注释 : ; */ | c ;";
+        assert_eq!(
+            toks(text),
+            [
+                symbol("S"),
+                Tok::Colon,
+                symbol("a"),
+                Tok::Bar,
+                symbol("c"),
+                Tok::Semi
+            ]
+        );
+        assert_eq!(
+            error("S: a /* 没有结束").to_string(),
+            "1:6: unterminated comment"
+        );
+    }
+
+    #[test]
+    fn quoted_punctuation_is_a_symbol() {
+        let text = "S: ':' '|' ';' '\\'' S* ;";
+        assert_eq!(
+            toks(text),
+            [
+                symbol("S"),
+                Tok::Colon,
+                symbol("':'"),
+                symbol("'|'"),
+                symbol("';'"),
+                symbol("'''"),
+                symbol("S"),
+                Tok::Postfix('*'),
+                Tok::Semi,
+            ]
+        );
+        // 引号中的 `:`、`|` 不开始新的规则或候选式
+        let grammar: Grammar = "CompUnit': S ;\nS: ':' '|' S | ;".parse().unwrap();
+        assert_eq!(
+            grammar.pro_list[&Element::NotTerminal("S".to_string())].len(),
+            2
+        );
+    }
+
+    #[test]
+    fn rules_span_several_lines() {
+        let text = "S:\n    a\n  | b c\n  ;\nT: x\nU: y";
+        let entries = parse(text).unwrap();
+        let rules: Vec<(&str, usize)> = entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::Rule {
+                    head, alternatives, ..
+                } => (head.as_str(), alternatives.len()),
+                Entry::Directive { .. } => unreachable!(),
+            })
+            .collect();
+        // 省略 `;` 时规则在下一个 `Name:` 之前结束
+        assert_eq!(rules, [("S", 2), ("T", 1), ("U", 1)]);
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        assert_eq!(
+            error("%token x\nCompUnit': S ;\nS: x\n   | x Y ;").to_string(),
+            "4:8: undeclared symbol `Y`: declare it with %token or add a rule for it"
+        );
+        assert_eq!(
+            error("CompUnit': S ;\nS: 'a\n;").to_string(),
+            "2:4: unterminated quoted literal"
+        );
+        assert_eq!(
+            error("%token a b\nCompUnit': S ;\n  S: a b ) ;").to_string(),
+            "3:10: unmatched closing bracket"
+        );
+        assert_eq!(
+            error("%token x\n%bogus x").to_string(),
+            "2:1: unknown directive `%bogus`"
+        );
+        // 读文件失败时没有行列号
+        let mut grammar = Grammar::new();
+        let error = grammar
+            .grammar_load("/nonexistent/grammar.txt")
+            .unwrap_err();
+        assert_eq!((error.line, error.column), (0, 0));
+    }
+
+    #[test]
+    fn quoted_literals_need_no_declaration() {
+        let grammar: Grammar = "%token Ident\nCompUnit': S ;\nS: 'int' Ident ';' ;"
+            .parse()
+            .unwrap();
+        for token in ["'int'", "Ident", "';'"] {
+            assert!(grammar.token_list.iter().any(|v| v == token), "{}", token);
+        }
+        assert_eq!(error("CompUnit': S ;\nS: 'int' Ident ;").line, 2);
+    }
+}
@@ -0,0 +1,687 @@
+use crate::parser::grammar_reader::{matching_brace, GrammarError};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// 文法文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrammarFormat {
+    /// 本项目的格式，见 `Grammar::grammar_parse`
+    Native,
+    /// bison/yacc 的 `.y` 文件，语义动作被忽略。本项目不支持运算符优先级，
+    /// 有 `%left`、`%prec` 等优先级声明的文法要先改写成按优先级分层的规则
+    Bison,
+    /// `A → B { ',' B } [ C ]`、`A ::= B*` 一类的 EBNF，如 s.txt 的第 1–31、34–71、74–129 行。
+    /// s.txt 是几份草稿放在一起，之后还有本项目格式的草稿和注释掉的代码，
+    /// 整个文件不能作为一个文法导入，需要先取出其中一段
+    Ebnf,
+    /// ANTLR 的 `.g4` 文件，只取语法规则，词法规则的名字作为终结符
+    Antlr,
+}
+impl GrammarFormat {
+    /// 按扩展名判断格式；扩展名不能确定时，引号外有 `→` 或 `::=` 的是 EBNF
+    pub fn detect(path: &str, text: &str) -> Self {
+        let extension = Path::new(path).extension().and_then(|v| v.to_str());
+        match extension {
+            Some("y" | "yy" | "ypp") => Self::Bison,
+            Some("g4") => Self::Antlr,
+            Some("ebnf" | "bnf") => Self::Ebnf,
+            _ if unquoted_contains(text, "→") || unquoted_contains(text, "::=") => Self::Ebnf,
+            _ => Self::Native,
+        }
+    }
+}
+
+/// 引号外的文本中是否有 `pattern`。没有在同一行闭合的引号（如 `CompUnit'`）按普通字符处理
+fn unquoted_contains(text: &str, pattern: &str) -> bool {
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with(pattern) {
+            return true;
+        }
+        let len = match c {
+            '\'' | '"' => quoted(rest, c).map_or(1, |(_, len)| len),
+            _ => c.len_utf8(),
+        };
+        rest = &rest[len..];
+    }
+    false
+}
+
+/// 转换成本项目格式的文法。规则保持在原来的行上，报错的行号与原文件一致；
+/// 末尾用 %start 声明增广的开始符号，如 `program'`
+#[derive(Debug)]
+pub(crate) struct Imported {
+    pub(crate) text: String,
+}
+
+pub(crate) fn convert(text: &str, format: GrammarFormat) -> Result<Option<Imported>, GrammarError> {
+    match format {
+        GrammarFormat::Native => Ok(None),
+        GrammarFormat::Bison => from_bison(text).map(Some),
+        GrammarFormat::Ebnf => from_ebnf(text).map(Some),
+        GrammarFormat::Antlr => from_antlr(text).map(Some),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lex {
+    Name(String),
+    /// 单引号的字面量，已转成本项目的写法
+    Quoted(String),
+    /// 双引号的字面量，bison 中可能是某个记号的别名
+    Double(String),
+    /// `{ ... }` 等整体跳过的块，记录内容的字节区间
+    Block(usize, usize),
+    /// `<...>` 中的内容
+    Tag(String),
+    Punct(String),
+}
+
+#[derive(Debug, Clone)]
+struct Piece {
+    lex: Lex,
+    offset: usize,
+}
+
+#[derive(Clone, Copy)]
+struct ScanOptions {
+    /// `{ ... }` 是语义动作；否则是 EBNF 的重复
+    braces: bool,
+    /// `[ ... ]` 是 ANTLR 的参数或字符集；否则是 EBNF 的可选项
+    brackets: bool,
+    /// 符号名中可以有 `.` 和 `-`（bison、W3C EBNF）
+    dotted: bool,
+}
+
+const PUNCTS: [&str; 8] = ["%%", "::=", ":=", "→", "->", "+=", "::", "%{"];
+
+fn scan(text: &str, options: ScanOptions) -> Result<Vec<Piece>, GrammarError> {
+    let mut pieces = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let c = rest.chars().next().unwrap();
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+        if rest.starts_with("//") {
+            pos += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("/*") || rest.starts_with("(*") {
+            let close = if c == '/' { "*/" } else { "*)" };
+            match rest[2..].find(close) {
+                Some(len) => pos += len + 4,
+                None => return Err(GrammarError::at(text, pos, "unterminated comment")),
+            }
+            continue;
+        }
+
+        let (lex, len) = match c {
+            '\'' | '"' => {
+                let (content, len) = quoted(rest, c)
+                    .ok_or_else(|| GrammarError::at(text, pos, "unterminated quoted literal"))?;
+                let native = native_quote(&content);
+                (
+                    if c == '"' {
+                        Lex::Double(native)
+                    } else {
+                        Lex::Quoted(native)
+                    },
+                    len,
+                )
+            }
+            '{' if options.braces => match matching_brace(text, pos) {
+                Some(close) => (Lex::Block(pos + 1, close), close + 1 - pos),
+                None => return Err(GrammarError::at(text, pos, "unclosed `{`")),
+            },
+            '[' if options.brackets => match matching_bracket(rest) {
+                Some(len) => (Lex::Block(pos + 1, pos + len - 1), len),
+                None => return Err(GrammarError::at(text, pos, "unclosed `[`")),
+            },
+            '<' => match rest[1..].find(['>', '\n']) {
+                Some(len) if rest[1 + len..].starts_with('>') => {
+                    (Lex::Tag(rest[1..1 + len].trim().to_string()), len + 2)
+                }
+                _ => (Lex::Punct("<".to_string()), 1),
+            },
+            _ if rest.starts_with("%{") => match rest.find("%}") {
+                Some(len) => (Lex::Block(pos + 2, pos + len), len + 2),
+                None => return Err(GrammarError::at(text, pos, "unclosed `%{`")),
+            },
+            '%' if rest[1..].starts_with(|c: char| c.is_alphabetic()) => {
+                let len = 1 + name_len(&rest[1..], true);
+                (Lex::Punct(rest[..len].to_string()), len)
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let len = name_len(rest, options.dotted);
+                (Lex::Name(rest[..len].to_string()), len)
+            }
+            _ => {
+                let len = PUNCTS
+                    .iter()
+                    .find(|punct| rest.starts_with(*punct))
+                    .map_or(c.len_utf8(), |punct| punct.len());
+                (Lex::Punct(rest[..len].to_string()), len)
+            }
+        };
+        pieces.push(Piece { lex, offset: pos });
+        pos += len;
+    }
+    Ok(pieces)
+}
+
+/// 符号名的长度。名字后紧跟的 `'` 在其后是空白或分隔符时是撇号，如 `CompUnit'`
+fn name_len(text: &str, dotted: bool) -> usize {
+    let len = text
+        .char_indices()
+        .find(|&(_, c)| !(c.is_alphanumeric() || c == '_' || (dotted && (c == '.' || c == '-'))))
+        .map_or(text.len(), |(i, _)| i);
+    let primes = text[len..].len() - text[len..].trim_start_matches('\'').len();
+    match text[len + primes..].chars().next() {
+        Some(c) if !(c.is_whitespace() || ")]}|;,".contains(c)) => len,
+        _ => len + primes,
+    }
+}
+
+/// `quote` 括起来的字面量。只处理 `\\` 和引号的转义，其余的反斜杠原样保留
+fn quoted(text: &str, quote: char) -> Option<(String, usize)> {
+    let mut content = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => return None,
+            '\\' => match chars.next()? {
+                (_, c) if c == '\\' || c == '\'' || c == '"' => content.push(c),
+                (_, c) => {
+                    content.push('\\');
+                    content.push(c);
+                }
+            },
+            _ if c == quote => return Some((content, i + 1)),
+            _ => content.push(c),
+        }
+    }
+    None
+}
+
+/// 本项目写法的字面量：单引号括起，`\` 和 `'` 要转义
+fn native_quote(content: &str) -> String {
+    let mut native = String::from("'");
+    for c in content.chars() {
+        if c == '\\' || c == '\'' {
+            native.push('\\');
+        }
+        native.push(c);
+    }
+    native.push('\'');
+    native
+}
+
+/// ANTLR 中 `[ ... ]` 的长度，`\]` 是转义
+fn matching_bracket(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            ']' => return Some(i + 1),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// 按原来的行输出转换后的文法
+struct Emitter<'a> {
+    source: &'a str,
+    out: String,
+    /// 已经输出到 source 中的哪个位置（只用来数换行）
+    pos: usize,
+}
+impl<'a> Emitter<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            out: String::new(),
+            pos: 0,
+        }
+    }
+    /// 在 `offset` 所在的行上输出一个词
+    fn emit(&mut self, offset: usize, word: &str) {
+        if offset > self.pos {
+            let lines = self.source[self.pos..offset].matches('\n').count();
+            self.out.extend(std::iter::repeat_n('\n', lines));
+            self.pos = offset;
+        }
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push(' ');
+        }
+        self.out.push_str(word);
+    }
+    /// 紧跟在上一个词后输出，如后缀运算符
+    fn attach(&mut self, word: &str) {
+        self.out.push_str(word);
+    }
+
+    /// 在末尾补上终结符声明、增广的开始规则和 %start
+    fn finish(
+        mut self,
+        heads: &[String],
+        tokens: Vec<String>,
+        start: Option<String>,
+    ) -> Result<Imported, GrammarError> {
+        let start = start
+            .or_else(|| heads.first().cloned())
+            .ok_or_else(|| GrammarError::new("the grammar has no rules"))?;
+        let mut augmented = format!("{}'", start);
+        while heads.contains(&augmented) {
+            augmented.push('\'');
+        }
+        let heads: HashSet<&String> = heads.iter().collect();
+        let mut declared = HashSet::new();
+        let tokens: Vec<String> = tokens
+            .into_iter()
+            .filter(|token| !heads.contains(token) && declared.insert(token.clone()))
+            .collect();
+        if !tokens.is_empty() {
+            self.out.push_str(&format!("\n%token {}", tokens.join(" ")));
+        }
+        self.out.push_str(&format!(
+            "\n%start {}\n{} : {} ;\n",
+            augmented, augmented, start
+        ));
+        Ok(Imported { text: self.out })
+    }
+}
+
+fn unsupported(text: &str, piece: &Piece, format: &str) -> GrammarError {
+    let word = match &piece.lex {
+        Lex::Name(v) | Lex::Quoted(v) | Lex::Double(v) | Lex::Punct(v) => v.clone(),
+        Lex::Tag(v) => format!("<{}>", v),
+        Lex::Block(..) => "{ ... }".to_string(),
+    };
+    GrammarError::at(
+        text,
+        piece.offset,
+        format!("unsupported `{}` in {}", word, format),
+    )
+}
+
+/// bison 的优先级声明
+const PRECEDENCE: [&str; 4] = ["%left", "%right", "%nonassoc", "%precedence"];
+
+fn precedence(text: &str, piece: &Piece, directive: &str) -> GrammarError {
+    GrammarError::at(
+        text,
+        piece.offset,
+        format!(
+            "`{}` is not supported: operator precedence cannot be imported; rewrite the operators as one rule per precedence level",
+            directive
+        ),
+    )
+}
+
+fn punct(piece: Option<&Piece>) -> Option<&str> {
+    match piece.map(|piece| &piece.lex) {
+        Some(Lex::Punct(punct)) => Some(punct),
+        _ => None,
+    }
+}
+
+/// `A → B { ',' B } [ C ]`、`A ::= B (C | D)*`。没有规则的符号都是终结符
+fn from_ebnf(text: &str) -> Result<Imported, GrammarError> {
+    let options = ScanOptions {
+        braces: false,
+        brackets: false,
+        dotted: true,
+    };
+    let pieces = scan(text, options)?;
+    let mut emitter = Emitter::new(text);
+    let mut heads = Vec::new();
+    let mut used = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        // BNF 中的 `<expr>` 是符号名
+        let name = match &piece.lex {
+            Lex::Name(name) => Some(name.clone()),
+            Lex::Tag(name) => Some(name.replace(char::is_whitespace, "_")),
+            _ => None,
+        };
+        match (&piece.lex, name) {
+            (_, Some(name)) => {
+                if matches!(
+                    punct(pieces.get(i + 1)),
+                    Some("→" | "::=" | ":=" | "=" | ":")
+                ) {
+                    heads.push(name.clone());
+                } else if name != "ε" {
+                    used.push(name.clone());
+                }
+                emitter.emit(piece.offset, &name);
+            }
+            (Lex::Quoted(literal) | Lex::Double(literal), _) => emitter.emit(piece.offset, literal),
+            (Lex::Punct(punct), _) => match punct.as_str() {
+                "→" | "::=" | ":=" | "=" | ":" => emitter.attach(" :"),
+                "|" | "(" | ")" | "[" | "]" | "{" | "}" | ";" => emitter.emit(piece.offset, punct),
+                "*" | "+" | "?" => emitter.attach(punct),
+                _ => return Err(unsupported(text, piece, "EBNF")),
+            },
+            _ => return Err(unsupported(text, piece, "EBNF")),
+        }
+    }
+    emitter.finish(&heads, used, None)
+}
+
+/// bison 的 `.y` 文件：取第一个 `%%` 之后的规则，语义动作、`%dprec` 等被丢弃，
+/// `"while"` 这样的别名换成对应的记号名。优先级声明会改变冲突的解决方式，不能丢弃，报错
+fn from_bison(text: &str) -> Result<Imported, GrammarError> {
+    let options = ScanOptions {
+        braces: true,
+        brackets: false,
+        dotted: true,
+    };
+    let pieces = scan(text, options)?;
+    let rules_start = pieces
+        .iter()
+        .position(|piece| piece.lex == Lex::Punct("%%".to_string()))
+        .ok_or_else(|| GrammarError::new("missing `%%` before the rules"))?;
+
+    // 声明部分：记号、别名和开始符号
+    let mut tokens = Vec::new();
+    let mut aliases = HashMap::new();
+    let mut start = None;
+    let mut declaring = None;
+    for (i, piece) in pieces[..rules_start].iter().enumerate() {
+        match &piece.lex {
+            Lex::Punct(directive) if PRECEDENCE.contains(&directive.as_str()) => {
+                return Err(precedence(text, piece, directive));
+            }
+            Lex::Punct(directive) if directive.starts_with('%') => {
+                declaring = Some(directive.as_str())
+            }
+            Lex::Name(name) => match declaring {
+                // 名字后的数字是记号的编号
+                Some("%token") if !name.starts_with(|c: char| c.is_ascii_digit()) => {
+                    tokens.push(name.clone())
+                }
+                Some("%start") => start = Some(name.clone()),
+                _ => (),
+            },
+            Lex::Double(alias) if declaring == Some("%token") => {
+                if let Some(Lex::Name(token)) =
+                    pieces.get(i.wrapping_sub(1)).map(|piece| &piece.lex)
+                {
+                    aliases.insert(alias.clone(), token.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut emitter = Emitter::new(text);
+    let mut heads = Vec::new();
+    let mut skip_next = false;
+    for (i, piece) in pieces.iter().enumerate().skip(rules_start + 1) {
+        if std::mem::take(&mut skip_next) {
+            continue;
+        }
+        match &piece.lex {
+            Lex::Punct(punct) if punct == "%%" => break,
+            Lex::Name(name) => {
+                if punct(pieces.get(i + 1)) == Some(":") {
+                    heads.push(name.clone());
+                }
+                emitter.emit(piece.offset, name);
+            }
+            Lex::Quoted(literal) => emitter.emit(piece.offset, literal),
+            Lex::Double(alias) => emitter.emit(piece.offset, aliases.get(alias).unwrap_or(alias)),
+            Lex::Punct(punct) => match punct.as_str() {
+                ":" | "|" | ";" => emitter.emit(piece.offset, punct),
+                "%empty" => (),
+                "%prec" => return Err(precedence(text, piece, punct)),
+                "%dprec" | "%merge" => skip_next = true,
+                _ => return Err(unsupported(text, piece, "a bison rule")),
+            },
+            Lex::Block(..) | Lex::Tag(_) => (),
+        }
+    }
+    emitter.finish(&heads, tokens, start)
+}
+
+/// ANTLR 的 `.g4` 文件：小写开头的语法规则被转换，大写开头的词法规则只取名字作为终结符；
+/// 标签、动作、谓词和规则参数被丢弃
+fn from_antlr(text: &str) -> Result<Imported, GrammarError> {
+    let options = ScanOptions {
+        braces: true,
+        brackets: true,
+        dotted: false,
+    };
+    let pieces = scan(text, options)?;
+    let is_token = |name: &str| name.starts_with(|c: char| c.is_uppercase());
+    let skip_to_semi = |i: usize| {
+        (i..pieces.len())
+            .find(|&j| punct(pieces.get(j)) == Some(";"))
+            .map_or(pieces.len(), |j| j + 1)
+    };
+
+    let mut emitter = Emitter::new(text);
+    let mut heads = Vec::new();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(piece) = pieces.get(i) {
+        let name = match &piece.lex {
+            Lex::Name(name) => name.as_str(),
+            Lex::Punct(punct) if punct == "@" => {
+                // @header { ... } 之类
+                i = (i..pieces.len())
+                    .find(|&j| matches!(pieces[j].lex, Lex::Block(..)))
+                    .map_or(pieces.len(), |j| j + 1);
+                continue;
+            }
+            _ => return Err(unsupported(text, piece, "an ANTLR grammar")),
+        };
+        match name {
+            "grammar" | "parser" | "lexer" | "import" | "mode" | "fragment" => {
+                i = skip_to_semi(i);
+                continue;
+            }
+            "options" | "channels" | "tokens" => {
+                if let Some(Piece {
+                    lex: Lex::Block(start, end),
+                    ..
+                }) = pieces.get(i + 1)
+                {
+                    if name == "tokens" {
+                        let names =
+                            text[*start..*end].split(|c: char| c == ',' || c.is_whitespace());
+                        tokens.extend(names.filter(|name| !name.is_empty()).map(str::to_string));
+                    }
+                    i += 2;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        if is_token(name) {
+            tokens.push(name.to_string());
+            i = skip_to_semi(i);
+            continue;
+        }
+
+        // 语法规则：跳过 returns [...]、locals [...]、@init { ... } 等，直到 `:`
+        let head = name.to_string();
+        let colon = (i..pieces.len())
+            .find(|&j| punct(pieces.get(j)) == Some(":"))
+            .ok_or_else(|| {
+                GrammarError::at(
+                    text,
+                    piece.offset,
+                    format!("missing `:` after rule `{}`", head),
+                )
+            })?;
+        emitter.emit(piece.offset, &head);
+        emitter.attach(" :");
+        heads.push(head);
+        i = colon + 1;
+        while let Some(piece) = pieces.get(i) {
+            i += 1;
+            let next = punct(pieces.get(i));
+            match &piece.lex {
+                // 标签 `x=expr`、`xs+=expr`
+                Lex::Name(_) if matches!(next, Some("=" | "+=")) => i += 1,
+                Lex::Name(name) if name == "EOF" => (),
+                Lex::Name(name) => {
+                    if is_token(name) {
+                        tokens.push(name.clone());
+                    }
+                    emitter.emit(piece.offset, name);
+                }
+                Lex::Quoted(literal) => emitter.emit(piece.offset, literal),
+                // 动作和谓词 `{ ... }?`
+                Lex::Block(..) => {
+                    if next == Some("?") {
+                        i += 1;
+                    }
+                }
+                Lex::Tag(_) => (),
+                Lex::Punct(punct) => match punct.as_str() {
+                    ";" => {
+                        emitter.emit(piece.offset, ";");
+                        break;
+                    }
+                    "|" | "(" | ")" => emitter.emit(piece.offset, punct),
+                    "*" | "+" | "?" => {
+                        emitter.attach(punct);
+                        // 非贪婪的 `*?` 对分析没有影响
+                        if next == Some("?") {
+                            i += 1;
+                        }
+                    }
+                    // 候选式的标签 `# Name`
+                    "#" => i += 1,
+                    _ => return Err(unsupported(text, piece, "an ANTLR rule")),
+                },
+                Lex::Double(_) => return Err(unsupported(text, piece, "an ANTLR rule")),
+            }
+        }
+    }
+    emitter.finish(&heads, tokens, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Grammar;
+
+    /// 按 `head: body` 列出导入后的产生式
+    fn import(text: &str, format: GrammarFormat) -> Vec<String> {
+        let mut grammar = Grammar::new();
+        grammar
+            .grammar_parse_as(text, format)
+            .unwrap_or_else(|e| panic!("{}", e));
+        grammar
+            .productions()
+            .iter()
+            .map(|(head, body)| {
+                let body: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+                format!("{}: {}", head, body.join(" "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bison_rules() {
+        let text = "%{\n#include <stdio.h>\n%}\n%token NUM \"number\"\n%token WHILE \"while\"\n%start stmt\n%%\n\
+            stmt: \"while\" '(' exp ')' stmt { loop(); }\n    | exp ';'\n    | %empty\n    ;\n\
+            exp: exp '+' term | term ;\nterm: NUM | \"number\" '*' NUM ;\n%%\nint main() {}\n";
+        assert_eq!(
+            import(text, GrammarFormat::Bison),
+            [
+                "stmt: WHILE '(' exp ')' stmt",
+                "stmt: exp ';'",
+                "stmt: ε",
+                "exp: exp '+' term",
+                "exp: term",
+                "term: NUM",
+                "term: NUM '*' NUM",
+                "stmt': stmt",
+            ]
+        );
+    }
+
+    #[test]
+    fn bison_precedence_is_rejected() {
+        let text = "%token NUM\n%left '+'\n%%\nexp: exp '+' exp | NUM ;\n";
+        let error = convert(text, GrammarFormat::Bison).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.message.contains("%left"), "{}", error);
+
+        let text = "%token NUM\n%%\nexp: '-' exp %prec NEG | NUM ;\n";
+        let error = convert(text, GrammarFormat::Bison).unwrap_err();
+        assert_eq!((error.line, error.column), (3, 14));
+    }
+
+    #[test]
+    fn ebnf_rules() {
+        let text = "Program → Stmt { ';' Stmt }\nStmt ::= Ident '=' Exp | [ 'return' ] Exp\nExp → Ident | \"→\" Exp\n";
+        assert_eq!(
+            import(text, GrammarFormat::Ebnf),
+            [
+                "Program: Stmt Program@1",
+                "Program@1: ';' Stmt Program@1",
+                "Program@1: ε",
+                "Stmt: Ident '=' Exp",
+                "Stmt: Stmt@2 Exp",
+                "Stmt@2: 'return'",
+                "Stmt@2: ε",
+                "Exp: Ident",
+                "Exp: '→' Exp",
+                "Program': Program",
+            ]
+        );
+    }
+
+    #[test]
+    fn antlr_rules() {
+        let text = "grammar Calc;\noptions { language = Java; }\nprog: stat+ EOF ;\n\
+            stat: e=expr NEWLINE # print\n    | ID '=' expr NEWLINE # assign\n    ;\n\
+            expr: expr ('*'|'/') expr | INT | '(' expr ')' {action();} ;\n\
+            ID : [a-z]+ ;\nINT : [0-9]+ ;\nNEWLINE : '\\r'? '\\n' ;\nWS : [ \\t]+ -> skip ;\n";
+        assert_eq!(
+            import(text, GrammarFormat::Antlr),
+            [
+                "prog: prog@1",
+                "prog@1: stat prog@1",
+                "prog@1: stat",
+                "stat: expr NEWLINE",
+                "stat: ID '=' expr NEWLINE",
+                "expr: expr expr@2 expr",
+                "expr: INT",
+                "expr: '(' expr ')'",
+                "expr@2: '*'",
+                "expr@2: '/'",
+                "prog': prog",
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_ignores_quoted_arrows() {
+        let native = "%token x\nCompUnit':S\nS: x '→' x | x \"::=\" ;";
+        assert_eq!(
+            GrammarFormat::detect("g.txt", native),
+            GrammarFormat::Native
+        );
+        assert_eq!(
+            GrammarFormat::detect("g.txt", "S ::= 'x'"),
+            GrammarFormat::Ebnf
+        );
+        assert_eq!(
+            GrammarFormat::detect("g.txt", "S → x '→'"),
+            GrammarFormat::Ebnf
+        );
+        assert_eq!(GrammarFormat::detect("g.y", "S → x"), GrammarFormat::Bison);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::parser::lr1_parser::ParseTables;
+use crate::parser::types::{Element, Span, Token};
+
+/// 按文法中的终结符切分源文本。
+///
+/// 带引号的终结符按字面匹配（`'int'` 匹配 `int`，关键字只匹配整个单词），
+/// 文法中有 `Ident`、`IntConst`、`FloatConst` 时按 SysY 的规则识别。
+/// 空白和 `//`、`/* */` 注释作为下一个记号的 `leading`，不丢弃任何字符
+#[derive(Debug, Clone)]
+pub struct Lexer {
+    /// 字面量及其终结符，按长度从长到短排列
+    literals: Vec<(String, Element)>,
+    ident: Option<Element>,
+    int_const: Option<Element>,
+    float_const: Option<Element>,
+}
+
+impl Lexer {
+    pub fn new(tables: &ParseTables) -> Self {
+        Self::from_terminals(tables.terminals())
+    }
+
+    /// 由终结符列表构造，供不使用 LR 分析表的分析器（如 `EarleyParser`）使用
+    pub fn from_terminals(terminals: &[Element]) -> Self {
+        let class = |name: &str| {
+            let element = Element::Terminal(name.to_string());
+            terminals.contains(&element).then_some(element)
+        };
+        let mut literals: Vec<(String, Element)> = terminals
+            .iter()
+            .filter(|symbol| symbol.lexeme() != symbol.to_string())
+            .map(|symbol| (symbol.lexeme().to_string(), symbol.clone()))
+            .collect();
+        literals.sort_by_key(|(lexeme, _)| std::cmp::Reverse(lexeme.len()));
+        Self {
+            literals,
+            ident: class("Ident"),
+            int_const: class("IntConst"),
+            float_const: class("FloatConst"),
+        }
+    }
+
+    /// 切分 `source`，返回记号和最后一个记号之后的空白与注释。
+    /// 无法识别的字符单独作为一个记号，由分析器报错
+    pub fn tokenize(&self, source: &str) -> (Vec<Token>, String) {
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        loop {
+            let start = pos + trivia_len(&source[pos..]);
+            let leading = source[pos..start].to_string();
+            let rest = &source[start..];
+            let Some(first) = rest.chars().next() else {
+                return (tokens, leading);
+            };
+
+            let (kind, len) = if first.is_alphabetic() || first == '_' {
+                let len = word_len(rest);
+                match (self.literal(&rest[..len]), &self.ident) {
+                    (Some(kind), _) => (kind.clone(), len),
+                    (None, Some(ident)) => (ident.clone(), len),
+                    (None, None) => self.punct(rest),
+                }
+            } else if first.is_ascii_digit()
+                || (first == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+            {
+                let (float, len) = number_len(rest);
+                match (float, &self.int_const, &self.float_const) {
+                    (false, Some(kind), _) | (true, _, Some(kind)) => (kind.clone(), len),
+                    _ => self.punct(rest),
+                }
+            } else {
+                self.punct(rest)
+            };
+
+            let mut token = Token::new(kind, &rest[..len], Span::new(start, start + len));
+            token.leading = leading;
+            tokens.push(token);
+            pos = start + len;
+        }
+    }
+
+    fn literal(&self, word: &str) -> Option<&Element> {
+        self.literals
+            .iter()
+            .find(|(lexeme, _)| lexeme == word)
+            .map(|(_, kind)| kind)
+    }
+    /// 最长的符号字面量；都不匹配时，单个字符作为一个未知记号
+    fn punct(&self, rest: &str) -> (Element, usize) {
+        let found = self.literals.iter().find(|(lexeme, _)| {
+            rest.starts_with(lexeme.as_str()) && !lexeme.starts_with(|c: char| c.is_alphanumeric())
+        });
+        match found {
+            Some((lexeme, kind)) => (kind.clone(), lexeme.len()),
+            None => {
+                let len = rest.chars().next().unwrap().len_utf8();
+                (Element::Terminal(format!("'{}'", &rest[..len])), len)
+            }
+        }
+    }
+}
+
+/// 开头的空白和注释的长度。未闭合的块注释一直到文件末尾
+fn trivia_len(text: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &text[len..];
+        if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+            len += c.len_utf8();
+        } else if rest.starts_with("//") {
+            len += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            len += body.find("*/").map_or(rest.len(), |end| end + 4);
+        } else {
+            return len;
+        }
+    }
+}
+
+fn word_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+/// 数字常量的长度，以及它是否是浮点数。支持十六进制和指数
+fn number_len(text: &str) -> (bool, usize) {
+    let bytes = text.as_bytes();
+    if text.starts_with("0x") || text.starts_with("0X") {
+        let len = 2 + text[2..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(text.len() - 2);
+        return (false, len);
+    }
+    let mut len = 0;
+    let mut float = false;
+    while len < bytes.len() {
+        match bytes[len] {
+            b'0'..=b'9' => len += 1,
+            b'.' if !float => {
+                float = true;
+                len += 1;
+            }
+            b'e' | b'E' => {
+                let sign = matches!(bytes.get(len + 1), Some(b'+' | b'-')) as usize;
+                if !bytes.get(len + 1 + sign).is_some_and(u8::is_ascii_digit) {
+                    break;
+                }
+                float = true;
+                len += 1 + sign;
+            }
+            _ => break,
+        }
+    }
+    (float, len)
+}
@@ -0,0 +1,574 @@
+use crate::parser::diagnostic::ParserError;
+use crate::parser::driver::ParseOutcome;
+use crate::parser::lr1_parser::{NodeKind, ProdId, TreeNode, ERROR_TOKEN};
+use crate::parser::types::{Element, Span, Token};
+use crate::parser::Grammar;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LL1ConflictKind {
+    /// 两个候选式的 FIRST 集相交（或都能推出 ε）
+    FirstFirst,
+    /// 一个候选式能推出 ε，另一个候选式的 FIRST 集与左部的 FOLLOW 集相交
+    FirstFollow,
+}
+
+/// 预测分析表中同一格出现了同一非终结符的两个候选式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LL1Conflict {
+    pub head: Element,
+    pub kind: LL1ConflictKind,
+    /// 两个候选式都会被预测的向前看符号
+    pub lookaheads: Vec<Element>,
+    /// 冲突的两个候选式，按文法中的顺序
+    pub productions: [(ProdId, Vec<Element>); 2],
+}
+
+impl Display for LL1Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            LL1ConflictKind::FirstFirst => "FIRST/FIRST",
+            LL1ConflictKind::FirstFollow => "FIRST/FOLLOW",
+        };
+        let lookaheads: Vec<String> = self.lookaheads.iter().map(|v| v.to_string()).collect();
+        write!(
+            f,
+            "{} conflict in {} on {}:",
+            kind,
+            self.head,
+            lookaheads.join(", ")
+        )?;
+        for (id, body) in &self.productions {
+            let body: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+            let body = if body.is_empty() {
+                "ε".to_string()
+            } else {
+                body.join(" ")
+            };
+            write!(f, "\n  ({}) {} -> {}", id, self.head, body)?;
+        }
+        Ok(())
+    }
+}
+
+/// LL(1) 预测分析表和表驱动的分析器，构造与 `LR1Parser` 相同的 `TreeNode` 语法树。
+/// 文法不是 LL(1) 时仍然可以分析，冲突的格子里取文法中靠前的候选式
+#[derive(Debug, Clone)]
+pub struct LL1Parser {
+    start_symbol: Element,
+    productions: Vec<(Element, Vec<Element>)>,
+    /// (非终结符, 向前看符号) -> 预测的候选式，按文法中的顺序
+    table: HashMap<(Element, Element), Vec<ProdId>>,
+    follow_sets: HashMap<Element, HashSet<Element>>,
+    /// EBNF 展开时生成的非终结符
+    generated: HashSet<Element>,
+    /// 文法中出现的终结符（不含 ε）和 `#`，按出现的顺序
+    terminals: Vec<Element>,
+    conflicts: Vec<LL1Conflict>,
+}
+
+impl LL1Parser {
+    pub fn new(grammar: &Grammar) -> Self {
+        let epsilon = Element::Terminal("ε".to_string());
+        let follow_sets = grammar.follow_sets();
+        // ε 在右部中只是占位，不作为符号参与分析
+        let productions: Vec<(Element, Vec<Element>)> = grammar
+            .productions()
+            .into_iter()
+            .map(|(head, body)| {
+                let body = body.into_iter().filter(|v| *v != epsilon).collect();
+                (head, body)
+            })
+            .collect();
+
+        let mut terminals = Vec::new();
+        for symbol in productions.iter().flat_map(|(_, body)| body) {
+            if matches!(symbol, Element::Terminal(_)) && !terminals.contains(symbol) {
+                terminals.push(symbol.clone());
+            }
+        }
+        terminals.push(Element::Terminal("#".to_string()));
+
+        // FIRST(α)，以及 α 能推出 ε 时并上 FOLLOW(A)
+        let mut firsts = Vec::with_capacity(productions.len());
+        let mut predicts = Vec::with_capacity(productions.len());
+        let mut table: HashMap<(Element, Element), Vec<ProdId>> = HashMap::new();
+        for (id, (head, body)) in productions.iter().enumerate() {
+            let mut first = grammar.first_symbols(body, &epsilon);
+            let nullable = first.remove(&epsilon);
+            let mut predict = first.clone();
+            if nullable {
+                predict.extend(follow_sets[head].iter().cloned());
+            }
+            for lookahead in &predict {
+                table
+                    .entry((head.clone(), lookahead.clone()))
+                    .or_default()
+                    .push(id);
+            }
+            firsts.push((first, nullable));
+            predicts.push(predict);
+        }
+
+        let mut conflicts = Vec::new();
+        for i in 0..productions.len() {
+            for j in i + 1..productions.len() {
+                if productions[i].0 != productions[j].0 {
+                    continue;
+                }
+                let mut lookaheads: HashMap<LL1ConflictKind, Vec<Element>> = HashMap::new();
+                for lookahead in terminals
+                    .iter()
+                    .filter(|v| predicts[i].contains(*v) && predicts[j].contains(*v))
+                {
+                    let ((first_i, nullable_i), (first_j, nullable_j)) = (&firsts[i], &firsts[j]);
+                    let kind = if (first_i.contains(lookahead) && first_j.contains(lookahead))
+                        || (*nullable_i && *nullable_j)
+                    {
+                        LL1ConflictKind::FirstFirst
+                    } else {
+                        LL1ConflictKind::FirstFollow
+                    };
+                    lookaheads.entry(kind).or_default().push(lookahead.clone());
+                }
+                for kind in [LL1ConflictKind::FirstFirst, LL1ConflictKind::FirstFollow] {
+                    if let Some(lookaheads) = lookaheads.remove(&kind) {
+                        conflicts.push(LL1Conflict {
+                            head: productions[i].0.clone(),
+                            kind,
+                            lookaheads,
+                            productions: [
+                                (i, productions[i].1.clone()),
+                                (j, productions[j].1.clone()),
+                            ],
+                        });
+                    }
+                }
+            }
+        }
+
+        Self {
+            start_symbol: grammar.start_symbol.clone(),
+            productions,
+            table,
+            follow_sets,
+            generated: grammar.generated(),
+            terminals,
+            conflicts,
+        }
+    }
+
+    pub fn is_ll1(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+    /// 文法中的全部 LL(1) 冲突，按候选式在文法中的顺序
+    pub fn conflicts(&self) -> &[LL1Conflict] {
+        &self.conflicts
+    }
+    /// 预测分析表中 `head` 遇到 `lookahead` 时选择的候选式
+    pub fn predict(&self, head: &Element, lookahead: &Element) -> Option<ProdId> {
+        self.table
+            .get(&(head.clone(), lookahead.clone()))
+            .map(|ids| ids[0])
+    }
+    /// 编号为 `id` 的产生式的左部和右部（右部不含 ε）
+    pub fn production(&self, id: ProdId) -> (&Element, &[Element]) {
+        let (head, body) = &self.productions[id];
+        (head, body)
+    }
+    pub fn follow_set(&self, head: &Element) -> Option<&HashSet<Element>> {
+        self.follow_sets.get(head)
+    }
+
+    pub fn construct_tree(&self, input: &[Element]) -> ParseOutcome {
+        let (tokens, source) = Token::from_elements(input);
+        self.construct_tree_from_tokens(tokens, &source)
+    }
+
+    /// `tokens` 中的 span 指向 `source`，报错时据此输出源码片段
+    pub fn construct_tree_from_tokens(
+        &self,
+        tokens: impl IntoIterator<Item = Token>,
+        source: &str,
+    ) -> ParseOutcome {
+        let mut tokens: Vec<Token> = tokens.into_iter().collect();
+        let offset = tokens
+            .last()
+            .map_or(0, |token| token.span.end)
+            .max(source.len());
+        tokens.push(Token::eof(offset));
+
+        let mut run = Run {
+            parser: self,
+            tokens,
+            pos: 0,
+            errors: Vec::new(),
+            reported: None,
+        };
+        let tree = run.run();
+        ParseOutcome::new(tree, run.errors, source.to_string())
+    }
+
+    /// 能让 `head` 展开的向前看符号，报告期望记号时使用
+    fn expected(&self, head: &Element) -> Vec<Element> {
+        self.terminals
+            .iter()
+            .filter(|v| self.table.contains_key(&(head.clone(), (*v).clone())))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 正在展开的候选式
+struct Frame {
+    head: Element,
+    /// 最外层包住开始符号的一项没有对应的产生式
+    prod: Option<ProdId>,
+    body: Vec<Element>,
+    next: usize,
+    /// 展开时所在的记号位置，用来发现左递归
+    start: usize,
+    children: Vec<TreeNode>,
+}
+
+struct Run<'a> {
+    parser: &'a LL1Parser,
+    tokens: Vec<Token>,
+    pos: usize,
+    errors: Vec<ParserError>,
+    /// 上一次报错的位置，同一位置只报告一次，避免级联错误
+    reported: Option<usize>,
+}
+
+impl Run<'_> {
+    fn current(&self) -> &Element {
+        &self.tokens[self.pos].kind
+    }
+    fn at_end(&self) -> bool {
+        *self.current() == Element::Terminal("#".to_string())
+    }
+
+    fn run(&mut self) -> TreeNode {
+        let start_symbol = self.parser.start_symbol.clone();
+        let mut stack = vec![Frame {
+            head: start_symbol.clone(),
+            prod: None,
+            body: vec![start_symbol],
+            next: 0,
+            start: 0,
+            children: Vec::new(),
+        }];
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let Some(symbol) = frame.body.get(frame.next).cloned() else {
+                let mut frame = stack.pop().unwrap();
+                let Some(parent) = stack.last_mut() else {
+                    self.trailing(&mut frame.children);
+                    return self.root(frame.children);
+                };
+                if self.parser.generated.contains(&frame.head) {
+                    parent.children.extend(frame.children);
+                } else {
+                    parent
+                        .children
+                        .push(TreeNode::new(frame.head, frame.children));
+                }
+                continue;
+            };
+            frame.next += 1;
+
+            match symbol {
+                Element::Terminal(_) => {
+                    let nodes = self.expect(symbol);
+                    stack.last_mut().unwrap().children.extend(nodes);
+                }
+                Element::NotTerminal(_) => {
+                    let prod = match self.choose(&symbol, &stack) {
+                        Some(prod) => Some(prod),
+                        None => {
+                            let skipped = self.recover(&symbol);
+                            let children = &mut stack.last_mut().unwrap().children;
+                            children.extend(skipped);
+                            let prod = self.choose(&symbol, &stack);
+                            if prod.is_none() {
+                                let mut node = TreeNode::leaf(symbol.clone());
+                                node.kind = NodeKind::Missing;
+                                stack.last_mut().unwrap().children.push(node);
+                            }
+                            prod
+                        }
+                    };
+                    if let Some(prod) = prod {
+                        stack.push(Frame {
+                            head: symbol,
+                            prod: Some(prod),
+                            body: self.parser.productions[prod].1.clone(),
+                            next: 0,
+                            start: self.pos,
+                            children: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// 按预测分析表选择候选式。跳过会在同一位置再次展开自身的候选式，
+    /// 因此左递归的文法不会死循环，但只能分析出递归的一层
+    fn choose(&self, head: &Element, stack: &[Frame]) -> Option<ProdId> {
+        let ids = self
+            .parser
+            .table
+            .get(&(head.clone(), self.current().clone()))?;
+        ids.iter().copied().find(|&id| {
+            !stack
+                .iter()
+                .any(|frame| frame.prod == Some(id) && frame.start == self.pos)
+        })
+    }
+
+    fn report(&mut self, error: ParserError) {
+        if self.reported != Some(self.pos) {
+            self.reported = Some(self.pos);
+            self.errors.push(error);
+        }
+    }
+
+    /// 匹配终结符。当前记号多余时跳过它，否则插入缺失的记号
+    fn expect(&mut self, symbol: Element) -> Vec<TreeNode> {
+        if *self.current() == symbol {
+            self.pos += 1;
+            return vec![TreeNode::leaf(symbol)];
+        }
+
+        if !self.at_end() && self.tokens[self.pos + 1].kind == symbol {
+            let token = self.tokens[self.pos].clone();
+            let context = format!("before {}", symbol);
+            self.report(ParserError::extra(self.pos, token.clone(), context));
+            self.pos += 2;
+            return vec![
+                skipped(vec![TreeNode::leaf(token.kind)]),
+                TreeNode::leaf(symbol),
+            ];
+        }
+
+        let offset = if self.pos == 0 {
+            0
+        } else {
+            self.tokens[self.pos - 1].span.end
+        };
+        let inserted = Token::missing(symbol.clone(), Span::new(offset, offset));
+        let context = if self.pos == 0 {
+            "at start of input".to_string()
+        } else {
+            format!("after {}", self.tokens[self.pos - 1].kind)
+        };
+        let found = self.current().clone();
+        self.report(ParserError::missing(self.pos, &inserted, found, context));
+        let mut node = TreeNode::leaf(symbol);
+        node.kind = NodeKind::Missing;
+        vec![node]
+    }
+
+    /// `head` 无法展开：报错，并跳过记号直到能展开 `head` 或遇到它的 FOLLOW 集中的记号
+    fn recover(&mut self, head: &Element) -> Vec<TreeNode> {
+        let token = &self.tokens[self.pos];
+        let error = ParserError::unexpected(
+            self.pos,
+            token.span,
+            token.kind.clone(),
+            self.parser.expected(head),
+        );
+        self.report(error);
+
+        let follow = self.parser.follow_sets.get(head);
+        let mut leaves = Vec::new();
+        while !self.at_end()
+            && !self
+                .parser
+                .table
+                .contains_key(&(head.clone(), self.current().clone()))
+            && !follow.is_some_and(|set| set.contains(self.current()))
+        {
+            leaves.push(TreeNode::leaf(self.current().clone()));
+            self.pos += 1;
+        }
+        if leaves.is_empty() {
+            Vec::new()
+        } else {
+            vec![skipped(leaves)]
+        }
+    }
+
+    /// 开始符号分析完后还有输入：报错并跳过剩下的记号
+    fn trailing(&mut self, children: &mut Vec<TreeNode>) {
+        if self.at_end() {
+            return;
+        }
+        let token = &self.tokens[self.pos];
+        let end = Element::Terminal("#".to_string());
+        let error = ParserError::unexpected(self.pos, token.span, token.kind.clone(), vec![end]);
+        self.report(error);
+        let leaves = self.tokens[self.pos..self.tokens.len() - 1]
+            .iter()
+            .map(|token| TreeNode::leaf(token.kind.clone()))
+            .collect();
+        self.pos = self.tokens.len() - 1;
+        children.push(skipped(leaves));
+    }
+
+    /// 与 LR 分析一致，根节点是增广前的开始符号，首尾被跳过的输入并入根节点
+    fn root(&self, children: Vec<TreeNode>) -> TreeNode {
+        let start_symbol = &self.parser.start_symbol;
+        let mut nodes = Vec::new();
+        for node in children {
+            match node.children {
+                Some(children)
+                    if node.element == *start_symbol && node.kind == NodeKind::Normal =>
+                {
+                    nodes.extend(children)
+                }
+                _ => nodes.push(node),
+            }
+        }
+        let roots: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].kind != NodeKind::Skipped)
+            .collect();
+        match roots.as_slice() {
+            &[index] if nodes[index].children.is_some() => {
+                let after = nodes.split_off(index + 1);
+                let mut root = nodes.pop().unwrap();
+                let children = root.children.as_mut().unwrap();
+                children.splice(0..0, nodes);
+                children.extend(after);
+                root
+            }
+            _ => TreeNode {
+                element: start_symbol.clone(),
+                children: Some(nodes),
+                kind: NodeKind::Error,
+            },
+        }
+    }
+}
+
+fn skipped(leaves: Vec<TreeNode>) -> TreeNode {
+    TreeNode {
+        element: Element::NotTerminal(ERROR_TOKEN.to_string()),
+        children: Some(leaves),
+        kind: NodeKind::Skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{LR1Driver, LR1Parser};
+    use std::sync::Arc;
+
+    const ARITHMETIC: &str = "%token x\nCompUnit': E ;\nE: T E' ;\nE': '+' T E' | ;\nT: F T' ;\nT': '*' F T' | ;\nF: '(' E ')' | x ;";
+
+    fn symbols(text: &str) -> HashSet<Element> {
+        Element::terminals(text).into_iter().collect()
+    }
+
+    fn head(name: &str) -> Element {
+        Element::NotTerminal(name.to_string())
+    }
+
+    #[test]
+    fn first_and_follow_sets() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        assert_eq!(grammar.first_sets[&head("E")], symbols("'(' x"));
+        assert_eq!(grammar.first_sets[&head("E'")], symbols("'+' ε"));
+        assert_eq!(grammar.first_sets[&head("T'")], symbols("'*' ε"));
+
+        let parser = LL1Parser::new(&grammar);
+        assert!(parser.is_ll1());
+        assert_eq!(parser.follow_set(&head("E")), Some(&symbols("')' #")));
+        assert_eq!(parser.follow_set(&head("E'")), Some(&symbols("')' #")));
+        assert_eq!(parser.follow_set(&head("T")), Some(&symbols("'+' ')' #")));
+        assert_eq!(
+            parser.follow_set(&head("F")),
+            Some(&symbols("'*' '+' ')' #"))
+        );
+
+        // E' → ε 在 FOLLOW(E') 上被预测
+        let terminal = |text: &str| Element::Terminal(text.to_string());
+        assert_eq!(parser.predict(&head("E'"), &terminal("'+'")), Some(2));
+        assert_eq!(parser.predict(&head("E'"), &terminal("')'")), Some(3));
+        assert_eq!(parser.predict(&head("E'"), &terminal("#")), Some(3));
+        assert_eq!(parser.predict(&head("E'"), &terminal("x")), None);
+    }
+
+    #[test]
+    fn first_first_conflict() {
+        let grammar: Grammar = "%token a b c\nCompUnit': S ;\nS: a b | a c ;"
+            .parse()
+            .unwrap();
+        let parser = LL1Parser::new(&grammar);
+        assert_eq!(
+            parser.conflicts(),
+            [LL1Conflict {
+                head: head("S"),
+                kind: LL1ConflictKind::FirstFirst,
+                lookaheads: Element::terminals("a"),
+                productions: [
+                    (1, Element::terminals("a b")),
+                    (2, Element::terminals("a c"))
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn first_follow_conflict() {
+        let grammar: Grammar = "%token c x\nCompUnit': S ;\nS: 'if' c S E | x ;\nE: 'else' S | ;"
+            .parse()
+            .unwrap();
+        let parser = LL1Parser::new(&grammar);
+        assert!(!parser.is_ll1());
+        assert_eq!(
+            parser.conflicts(),
+            [LL1Conflict {
+                head: head("E"),
+                kind: LL1ConflictKind::FirstFollow,
+                lookaheads: Element::terminals("'else'"),
+                productions: [
+                    (3, vec![Element::Terminal("'else'".to_string()), head("S")]),
+                    (4, Vec::new())
+                ],
+            }]
+        );
+        // 冲突的格子里取靠前的候选式，else 与最近的 if 结合
+        let outcome = parser.construct_tree(&Element::terminals("'if' c 'if' c x 'else' x"));
+        assert!(outcome.errors.is_empty());
+        let inner = &outcome.tree.children.as_ref().unwrap()[2];
+        let else_part = inner.children.as_ref().unwrap().last().unwrap();
+        assert_eq!(
+            else_part.children.as_ref().unwrap()[0].element,
+            Element::Terminal("'else'".to_string())
+        );
+    }
+
+    #[test]
+    fn trees_match_lr_trees() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        let ll1 = LL1Parser::new(&grammar);
+        let tables = Arc::new(LR1Parser::from_grammar(&grammar).into_tables());
+        for sentence in ["x", "x '+' x '*' x", "'(' x '+' x ')' '*' x"] {
+            let input = Element::terminals(sentence);
+            let expected = LR1Driver::new(tables.clone()).construct_tree(&input);
+            let outcome = ll1.construct_tree(&input);
+            assert!(expected.errors.is_empty() && outcome.errors.is_empty());
+            assert_eq!(outcome.tree, expected.tree, "`{}`", sentence);
+        }
+
+        // 与 LR 分析一样，E' → ε 的节点没有子节点
+        let tree = ll1.construct_tree(&Element::terminals("x")).tree;
+        let rest = tree.children.as_ref().unwrap().last().unwrap();
+        assert_eq!(rest.element, head("E'"));
+        assert_eq!(rest.children, Some(Vec::new()));
+    }
+}
@@ -1,27 +1,33 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
-#![allow(clippy::clone_on_copy)]
+use crate::parser::driver::{Delimiters, LR1Driver, ParseOutcome};
 use crate::parser::types::Element;
 use crate::parser::{Grammar, ACTION_TABLE, DATA_PATH, GOTO_TABLE, LR1_SETS};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::fs::{create_dir_all, metadata, File};
+use std::fs::{create_dir_all, File};
 use std::hash::Hash;
+use std::sync::Arc;
 
-type State = usize;
+pub(crate) type State = usize;
+/// 产生式编号，按产生式在文法文件中的顺序从 0 开始
+pub type ProdId = usize;
+type Production = (Element, Vec<Element>);
 type GotoTable = HashMap<(State, Element), State>;
 type ActionTable = HashMap<(State, Element), Action>;
+/// 分析表每一格中的全部动作。文法有冲突时一格中有多个动作，
+/// 而 `action_table` 只保留了其中最后写入的一个
+pub type ActionSets = HashMap<(State, Element), Vec<Action>>;
 type LR1Sets = Vec<HashSet<LR1Item>>;
-type ErrorList = Vec<ParserError>;
+/// 文法中保留的错误终结符，如 `Stmt: error ';'`
+pub(crate) const ERROR_TOKEN: &str = "error";
 
 #[derive(Debug, Default)]
 pub struct LR1Parser {
-    tokens: Vec<Element>,
-    error_list: Vec<ParserError>,
-    status: Status,
-    part_table: PartitionTable,
-    pos: usize,
+    delimiters: Delimiters,
+    start_symbol: Option<Element>,
+    productions: Vec<Production>,
+    generated: HashSet<Element>,
     pub lr1_sets: LR1Sets,
     pub action_table: ActionTable,
     pub goto_table: GotoTable,
@@ -31,316 +37,56 @@ impl LR1Parser {
     pub fn new() -> Self {
         Self::default()
     }
-    fn get_last_token(&self) -> &Element {
-        &self.tokens[if self.pos == 0 { 0 } else { self.pos - 1 }]
-    }
-    fn get_current_token(&self) -> &Element {
-        &self.tokens[if self.pos >= self.tokens.len() {
-            self.tokens.len()
-        } else {
-            self.pos
-        }]
-    }
-}
-
-impl LR1Parser {
-    pub fn construct_tree(mut self, input: &[Element]) -> Self {
-        self.tokens = input.to_owned();
-        self.tokens.push(Element::Terminal("#".to_string()));
-
-        loop {
-            if self.pos >= self.tokens.len() {
-                break;
-            }
-            let state = self.status.state_stack.last().unwrap().clone();
-            let symbol = self.tokens[self.pos].clone();
-
-            let action = self
-                .action_table
-                .get(&(state.clone(), symbol.clone()))
-                .cloned();
-
-            match action {
-                Some(Action::Shift(state)) => {
-                    self.status.state_stack.push(state);
-                    self.status.node_stack.push(TreeNode {
-                        element: symbol.clone(),
-                        children: None,
-                    });
-                    self.step_forward();
-                    self.try_partition();
-                }
-                Some(Action::Reduce(prod_head, prod_body)) => {
-                    let mut children: Vec<TreeNode> = Vec::new();
-                    for _ in 0..prod_body.len() {
-                        self.status.state_stack.pop();
-                        children.push(self.status.node_stack.pop().unwrap());
-                    }
-                    children.reverse();
-
-                    let state = self.status.state_stack.last().unwrap().clone();
-                    let state = self
-                        .goto_table
-                        .get(&(state, prod_head.clone()))
-                        .unwrap()
-                        .clone();
-
-                    self.status.state_stack.push(state);
-                    self.status.node_stack.push(TreeNode {
-                        element: prod_head,
-                        children: Some(children),
-                    });
-                }
-                Some(Action::Accept) => {
-                    break;
-                }
-                None => {
-                    if let Some(Action::Shift(t)) = self
-                        .action_table
-                        .get(&(state.clone(), Element::Terminal("ε".to_string())))
-                        .cloned()
-                    {
-                        self.status.state_stack.push(t);
-                        self.status.node_stack.push(TreeNode {
-                            element: Element::Terminal("ε".to_string()),
-                            children: None,
-                        });
-                    } else {
-                        self.err_handle();
-                    }
-                }
-            }
-        }
-        self
-    }
-
-    fn err_handle(&mut self) {
-        // error_list.(format!("Unexpected symbol '{:?}' at position {}", symbol, input_pos));
-        self.error_list.push(ParserError {
-            error_type: ErrorType::Unknown(format!(
-                "Unexpected symbol '{:?}' at position {}",
-                self.tokens[self.pos], self.pos
-            )),
-            error_pos: self.tokens[self.pos].get_pos(),
-        });
-
-        // 错误处理：跳过输入直到找到一个可以接受的符号
-        let mut found_acceptable_symbol = false;
-        while !found_acceptable_symbol {
-            self.step_forward();
 
-            if self.pos >= self.tokens.len() {
-                break;
-            }
-
-            let symbol = Delimiter::from(self.get_current_token().clone());
-            if symbol.is_limiter() {
-                self.fallback();
-                // self.step_forward();
-                break;
-            }
-
-            let next_symbol = self.tokens[self.pos].clone();
-            if self
-                .action_table
-                .get(&(
-                    self.status.state_stack.last().unwrap().clone(),
-                    next_symbol.clone(),
-                ))
-                .is_some()
-            {
-                found_acceptable_symbol = true;
-            }
-        }
-    }
-    fn step_forward(&mut self) {
-        self.pos += 1;
-        // let last_token = self.get_last_token().clone();
-        // if last_token.is_left_bracket() {
-        //     self.status_stack.push((last_token, self.status.clone()));
-        // } else if last_token.is_right_bracket() {
-        //     let mut err = false;
-        //     loop {
-        //         if let Some((token, status)) = self.status_stack.pop() {
-        //             if token.is_paired(&last_token) {
-        //                 if err {
-        //                     self.status = status;
-        //                 }
-        //                 break;
-        //             } else {
-        //                 err = true;
-        //                 self.error_list.push(ParserError {
-        //                     error_type: ErrorType::Missing(token.clone().unwarp().0),
-        //                     error_pos: (token.get_pos()),
-        //                 })
-        //             }
-        //         } else {
-        //             self.status = Status::default();
-        //             break;
-        //         }
-        //     }
-        // }
-    }
-    fn fallback(&mut self) {
-        self.status = self.part_table.get_last_status()
+    /// 复制一份只读的分析表
+    pub fn tables(&self) -> ParseTables {
+        ParseTables::new(
+            self.action_table.clone(),
+            self.goto_table.clone(),
+            self.delimiters.clone(),
+            self.start_symbol.clone(),
+            self.productions.clone(),
+            self.generated.clone(),
+        )
     }
-    fn try_partition(&mut self) {
-        let symbol = Delimiter::from(self.get_last_token().clone());
-        if symbol.is_delimiter() {
-            self.part_table.update(Partition {
-                partition_head: symbol,
-                status: self.status.clone(),
-            })
-        }
+    pub fn into_tables(self) -> ParseTables {
+        ParseTables::new(
+            self.action_table,
+            self.goto_table,
+            self.delimiters,
+            self.start_symbol,
+            self.productions,
+            self.generated,
+        )
     }
-}
 
-#[derive(Debug, Default)]
-struct PartitionTable {
-    table: Vec<Partition>,
-}
-impl PartitionTable {
-    fn get_last_token(&self) -> Delimiter {
-        if let Some(v) = self.table.last() {
-            v.partition_head
-        } else {
-            Delimiter::Default
-        }
-    }
-    fn get_last_status(&self) -> Status {
-        if let Some(v) = self.table.last() {
-            v.status.clone()
-        } else {
-            Status::default()
-        }
-    }
-    fn update(&mut self, partition: Partition) {
-        let token = partition.partition_head;
-        match token {
-            Delimiter::Semi
-            | Delimiter::OpenParen
-            | Delimiter::OpenBrace
-            | Delimiter::OpenBracket => {
-                if self.get_last_token() == Delimiter::Semi {
-                    self.table.pop();
-                    self.table.push(partition);
-                } else {
-                    self.table.push(partition);
-                }
-            }
-            Delimiter::CloseParen | Delimiter::CloseBrace | Delimiter::CloseBracket => {
-                if self.get_last_token() == Delimiter::Semi {
-                    self.table.pop();
-                    self.table.pop();
-                } else {
-                    self.table.pop();
-                }
-            }
-            Delimiter::Default => (),
-        }
+    /// 只分析一次时的便捷写法；需要多次分析时用 `into_tables` 和 `LR1Driver`
+    pub fn construct_tree(self, input: &[Element]) -> ParseOutcome {
+        LR1Driver::new(Arc::new(self.into_tables())).construct_tree(input)
     }
 }
 
+/// 构造完成的分析表。构造后不再修改，可以放在 `Arc` 里被多次分析、多个线程共享
 #[derive(Debug, Clone)]
-pub struct Status {
-    state_stack: Vec<State>,
-    node_stack: Vec<TreeNode>,
-}
-impl Default for Status {
-    fn default() -> Self {
-        let state_stack = vec![0];
-        let node_stack = vec![TreeNode {
-            element: Element::NotTerminal("#".to_string()),
-            children: None,
-        }];
-        Self {
-            state_stack,
-            node_stack,
-        }
-    }
-}
-#[derive(Debug)]
-pub struct Partition {
-    partition_head: Delimiter,
-    status: Status,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Delimiter {
-    /// ";"
-    Semi,
-    /// "("
-    OpenParen,
-    /// ")"
-    CloseParen,
-    /// "{"
-    OpenBrace,
-    /// "}"
-    CloseBrace,
-    /// "["
-    OpenBracket,
-    /// "]"
-    CloseBracket,
-    Default,
-}
-impl Delimiter {
-    fn is_delimiter(&self) -> bool {
-        self != &Self::Default
-    }
-    fn is_limiter(&self) -> bool {
-        matches!(
-            self,
-            Delimiter::Semi
-                | Delimiter::CloseParen
-                | Delimiter::CloseBrace
-                | Delimiter::CloseBracket
-        )
-    }
-}
-impl From<Element> for Delimiter {
-    fn from(v: Element) -> Self {
-        match v.unwarp().0.as_str() {
-            "';'" => Delimiter::Semi,
-            "'('" => Delimiter::OpenParen,
-            "')'" => Delimiter::CloseParen,
-            "'{'" => Delimiter::OpenBrace,
-            "'}'" => Delimiter::CloseBrace,
-            "'['" => Delimiter::OpenBracket,
-            "']'" => Delimiter::CloseBracket,
-            _ => Delimiter::Default,
-        }
-    }
-}
-impl Default for Partition {
-    fn default() -> Self {
-        Self {
-            partition_head: Delimiter::Default,
-            status: Default::default(),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct ParserError {
-    pub error_type: ErrorType,
-    pub error_pos: usize,
-}
-
-#[derive(Debug)]
-pub enum ErrorType {
-    // 缺失错误
-    Missing(String),
-    // 多余错误
-    Extra(String),
-    // 未知错误
-    Unknown(String),
+pub struct ParseTables {
+    pub(crate) action_table: ActionTable,
+    pub(crate) goto_table: GotoTable,
+    pub(crate) delimiters: Delimiters,
+    pub(crate) start_symbol: Option<Element>,
+    productions: Vec<Production>,
+    prod_ids: HashMap<Production, ProdId>,
+    /// 分析表中出现的终结符（不含 ε），报告期望记号时使用
+    terminals: Vec<Element>,
+    /// EBNF 展开时生成的非终结符
+    generated: HashSet<Element>,
+    pub(crate) has_error_productions: bool,
 }
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct LR1Item {
     pub(crate) head: Element,
-    body: Vec<Element>,
-    dot: usize,
+    pub(crate) body: Vec<Element>,
+    pub(crate) dot: usize,
     pub(crate) lookahead: Element,
 }
 
@@ -351,69 +97,149 @@ pub enum Action {
     Accept,
 }
 
+/// 分析表中有多个动作的一格
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub state: State,
+    pub lookahead: Element,
+    /// 按移进、归约（产生式编号）、接受排列
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    #[default]
+    Normal,
+    /// 错误恢复时插入的记号
+    Missing,
+    /// 文法中 `error` 终结符的节点，子节点是被丢弃的内容
+    Error,
+    /// 错误恢复时跳过的输入，不对应任何文法符号
+    Skipped,
+}
+
+/// 语法树的节点。ε 产生式的节点没有子节点（`Some` 空列表，不含 ε 叶子），
+/// LR、LL(1)、GLR 和 Earley 分析得到的树都是这样
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TreeNode {
     pub element: Element,
     pub children: Option<Vec<TreeNode>>,
+    pub kind: NodeKind,
 }
-
-fn file_exists(file_path: &str) -> bool {
-    match metadata(file_path) {
-        Ok(metadata) => metadata.is_file(),
-        Err(_) => false,
+impl TreeNode {
+    pub(crate) fn leaf(element: Element) -> Self {
+        Self {
+            element,
+            children: None,
+            kind: NodeKind::Normal,
+        }
     }
-}
-
-impl Display for LR1Parser {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.error_list.is_empty() {
-            write!(f, "{}", self.status.node_stack.last().unwrap())
-        } else {
-            let mut buffer = String::new();
-            for i in self.error_list.iter() {
-                buffer.push_str(&format!("{:?}", i));
-                buffer.push('\n');
-            }
-            write!(f, "{}", buffer)
+    pub(crate) fn new(element: Element, children: Vec<TreeNode>) -> Self {
+        Self {
+            element,
+            children: Some(children),
+            kind: NodeKind::Normal,
         }
     }
 }
-#[allow(clippy::print_in_format_impl)]
+
+/// 缓存文件的路径，如 `./data/action_table-0123456789abcdef.rcp`
+fn cache_path(name: &str, grammar: &Grammar) -> String {
+    format!("{}{}-{:016x}.rcp", DATA_PATH, name, grammar.fingerprint())
+}
+
+/// 读取缓存文件，文件不存在或无法解码时为 None
+fn load<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let file = File::open(path).ok()?;
+    bincode::deserialize_from(file).ok()
+}
+
 impl Display for TreeNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fn print_tree(tree: &TreeNode, depth: usize) {
-            let mut indent = String::new();
-            for _ in 0..depth {
-                indent.push_str("  ");
-            }
-            if tree.element != Element::Terminal("ε".to_string()) {
-                println!("{}{:?}", indent, tree.element);
+        fn print_tree(
+            tree: &TreeNode,
+            depth: usize,
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> std::fmt::Result {
+            let indent = "  ".repeat(depth);
+            match tree.kind {
+                _ if tree.element == Element::Terminal("ε".to_string()) => {}
+                NodeKind::Normal => writeln!(f, "{}{:?}", indent, tree.element)?,
+                NodeKind::Missing => writeln!(f, "{}{:?} <missing>", indent, tree.element)?,
+                NodeKind::Error => writeln!(f, "{}{:?} <error>", indent, tree.element)?,
+                NodeKind::Skipped => writeln!(f, "{}<skipped>", indent)?,
             }
             if let Some(children) = &tree.children {
                 for child in children {
-                    print_tree(child, depth + 1);
+                    print_tree(child, depth + 1, f)?;
                 }
             }
+            Ok(())
         }
-        print_tree(self, 0);
+        print_tree(self, 0, f)?;
         writeln!(f)
     }
 }
 
+impl LR1Item {
+    /// 不含向前看符号的部分：`A -> α • β`
+    pub(crate) fn core(&self) -> String {
+        let mut symbols: Vec<String> = self.body.iter().map(|v| v.to_string()).collect();
+        symbols.insert(self.dot, "•".to_string());
+        format!("{} -> {}", self.head, symbols.join(" "))
+    }
+}
+
+impl Display for LR1Item {
+    /// `A -> α • β, a`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.core(), self.lookahead)
+    }
+}
+
 impl LR1Parser {
+    /// 不读写 ./data 中的缓存，直接由文法构造项集族和分析表
+    pub fn from_grammar(grammar: &Grammar) -> Self {
+        let mut parser = Self::new();
+        parser.compute_lr1_item_sets_core(grammar, &grammar.start_symbol);
+        parser.set_grammar(grammar);
+        parser.construct_parsing_table_core(grammar);
+        parser
+    }
+
+    fn set_grammar(&mut self, grammar: &Grammar) {
+        self.delimiters = Delimiters::new(grammar);
+        self.start_symbol = Some(grammar.start_symbol.clone());
+        self.productions = grammar.productions();
+        self.generated = grammar.generated();
+    }
+
     pub fn construct_parsing_table(&mut self, grammar: &Grammar) {
-        if file_exists(ACTION_TABLE) && file_exists(GOTO_TABLE) {
-            let action_file = File::open(ACTION_TABLE).expect("Unable to open action table file");
-            let goto_file = File::open(GOTO_TABLE).expect("Unable to open goto table file");
-            self.action_table = bincode::deserialize_from(action_file).unwrap();
-            self.goto_table = bincode::deserialize_from(goto_file).unwrap();
+        self.set_grammar(grammar);
+        let action_path = cache_path(ACTION_TABLE, grammar);
+        let goto_path = cache_path(GOTO_TABLE, grammar);
+        let productions: HashSet<Production> = grammar.productions().into_iter().collect();
+        // 缓存中归约用的产生式必须都在当前文法中，否则重新构造
+        let cached = load::<ActionTable>(&action_path)
+            .filter(|table| {
+                table.values().all(|action| match action {
+                    Action::Reduce(head, body) => {
+                        productions.contains(&(head.clone(), body.clone()))
+                    }
+                    _ => true,
+                })
+            })
+            .zip(load::<GotoTable>(&goto_path));
+        if let Some((action_table, goto_table)) = cached {
+            self.action_table = action_table;
+            self.goto_table = goto_table;
         } else {
             self.construct_parsing_table_core(grammar);
 
             create_dir_all(DATA_PATH).expect("Unable to create action table file");
             let mut action_file =
-                File::create(ACTION_TABLE).expect("Unable to create action table file");
-            let mut goto_file = File::create(GOTO_TABLE).expect("Unable to create goto table file");
+                File::create(&action_path).expect("Unable to create action table file");
+            let mut goto_file = File::create(&goto_path).expect("Unable to create goto table file");
             bincode::serialize_into(&mut action_file, &self.action_table)
                 .expect("Unable to serialize action table");
             bincode::serialize_into(&mut goto_file, &self.goto_table)
@@ -474,15 +300,85 @@ impl LR1Parser {
         }
     }
 
+    /// 由 LR(1) 项集族重新收集每一格的全部动作，格内按移进、归约（产生式编号）、接受排列。
+    /// 需要先构造项集族和分析表
+    pub fn action_sets(&self, grammar: &Grammar) -> ActionSets {
+        let end = Element::Terminal("#".to_string());
+        let prod_ids: HashMap<Production, ProdId> =
+            grammar.productions().into_iter().zip(0..).collect();
+        let mut targets = HashMap::new();
+        let mut action_sets = ActionSets::new();
+        for (state, item_set) in self.lr1_sets.iter().enumerate() {
+            for item in item_set {
+                let (symbol, action) = match item.body.get(item.dot) {
+                    Some(symbol @ Element::Terminal(_)) => {
+                        let target = match self.action_table.get(&(state, symbol.clone())) {
+                            Some(Action::Shift(target)) => *target,
+                            // 移进被归约覆盖了，重新求转移到的状态
+                            _ => *targets.entry((state, symbol.clone())).or_insert_with(|| {
+                                let goto_set = self.goto(grammar, item_set, symbol);
+                                self.lr1_sets.iter().position(|x| *x == goto_set).unwrap()
+                            }),
+                        };
+                        (symbol.clone(), Action::Shift(target))
+                    }
+                    Some(Element::NotTerminal(_)) => continue,
+                    None if item.head == grammar.start_symbol && item.lookahead == end => {
+                        (end.clone(), Action::Accept)
+                    }
+                    None => (
+                        item.lookahead.clone(),
+                        Action::Reduce(item.head.clone(), item.body.clone()),
+                    ),
+                };
+                let actions = action_sets.entry((state, symbol)).or_default();
+                if !actions.contains(&action) {
+                    actions.push(action);
+                }
+            }
+        }
+        for actions in action_sets.values_mut() {
+            actions.sort_by_key(|action| match action {
+                Action::Shift(target) => (0, *target),
+                Action::Reduce(head, body) => (1, prod_ids[&(head.clone(), body.clone())]),
+                Action::Accept => (2, 0),
+            });
+        }
+        action_sets
+    }
+
+    /// 分析表中的全部冲突，按状态和向前看符号排列
+    pub fn conflicts(&self, grammar: &Grammar) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = self
+            .action_sets(grammar)
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|((state, lookahead), actions)| Conflict {
+                state,
+                lookahead,
+                actions,
+            })
+            .collect();
+        conflicts.sort_by_key(|conflict| (conflict.state, conflict.lookahead.to_string()));
+        conflicts
+    }
+
     pub fn compute_lr1_item_sets(&mut self, grammar: &Grammar) {
-        if file_exists(LR1_SETS) {
-            let lr1_file = File::open(LR1_SETS).expect("Unable to open action table file");
-            self.lr1_sets = bincode::deserialize_from(lr1_file).unwrap();
+        let lr1_path = cache_path(LR1_SETS, grammar);
+        let productions: HashSet<Production> = grammar.productions().into_iter().collect();
+        // 缓存中的项目必须都来自当前文法的产生式，否则重新构造
+        let cached = load::<LR1Sets>(&lr1_path).filter(|sets| {
+            sets.iter()
+                .flatten()
+                .all(|item| productions.contains(&(item.head.clone(), item.body.clone())))
+        });
+        if let Some(lr1_sets) = cached {
+            self.lr1_sets = lr1_sets;
         } else {
             self.compute_lr1_item_sets_core(grammar, &grammar.start_symbol);
 
             create_dir_all(DATA_PATH).expect("Unable to create action table file");
-            let mut lr1_file = File::create(LR1_SETS).expect("Unable to create action table file");
+            let mut lr1_file = File::create(&lr1_path).expect("Unable to create action table file");
             bincode::serialize_into(&mut lr1_file, &self.lr1_sets)
                 .expect("Unable to serialize action table");
         }
@@ -627,14 +523,128 @@ impl LR1Parser {
     }
 }
 
-fn get_exception_symbols(action_table: &ActionTable, state: &State) -> Vec<Element> {
-    let mut exception_symbols = Vec::new();
-
-    for action in action_table {
-        if action.0 .0 == state.clone() && action.0 .1 != Element::Terminal("#".to_string()) {
-            exception_symbols.push(action.0 .1.clone());
+impl ParseTables {
+    fn new(
+        action_table: ActionTable,
+        goto_table: GotoTable,
+        delimiters: Delimiters,
+        start_symbol: Option<Element>,
+        productions: Vec<Production>,
+        generated: HashSet<Element>,
+    ) -> Self {
+        let epsilon = Element::Terminal("ε".to_string());
+        let terminals: HashSet<&Element> = action_table
+            .keys()
+            .map(|(_, symbol)| symbol)
+            .filter(|symbol| **symbol != epsilon)
+            .collect();
+        let mut terminals: Vec<Element> = terminals.into_iter().cloned().collect();
+        terminals.sort_by_key(|symbol| symbol.to_string());
+        let has_error_productions = terminals.contains(&Element::Terminal(ERROR_TOKEN.to_string()));
+        let prod_ids = productions.iter().cloned().zip(0..).collect();
+        Self {
+            action_table,
+            goto_table,
+            delimiters,
+            start_symbol,
+            productions,
+            prod_ids,
+            generated,
+            terminals,
+            has_error_productions,
         }
     }
 
-    exception_symbols
-}
\ No newline at end of file
+    pub fn action(&self, state: State, symbol: &Element) -> Option<&Action> {
+        self.action_table.get(&(state, symbol.clone()))
+    }
+    pub fn goto(&self, state: State, symbol: &Element) -> Option<State> {
+        self.goto_table.get(&(state, symbol.clone())).copied()
+    }
+
+    /// 分析表中出现的终结符，不含 ε
+    pub fn terminals(&self) -> &[Element] {
+        &self.terminals
+    }
+
+    /// 编号为 `id` 的产生式的左部和右部
+    pub fn production(&self, id: ProdId) -> (&Element, &[Element]) {
+        let (head, body) = &self.productions[id];
+        (head, body)
+    }
+    /// 是否是 EBNF 展开时生成的非终结符。构造树时它们的子节点并入父节点
+    pub fn is_generated(&self, symbol: &Element) -> bool {
+        self.generated.contains(symbol)
+    }
+    /// `head` 的第 `alternative` 个候选式（从 0 开始，按文法中的顺序）的编号
+    pub fn production_id(&self, head: &str, alternative: usize) -> Option<ProdId> {
+        let head = Element::NotTerminal(head.to_string());
+        self.productions
+            .iter()
+            .position(|(v, _)| *v == head)
+            .map(|first| first + alternative)
+            .filter(|&id| self.productions.get(id).is_some_and(|(v, _)| *v == head))
+    }
+    pub(crate) fn prod_id(&self, head: &Element, body: &[Element]) -> ProdId {
+        // 分析表来自缓存时，可能与当前加载的文法不一致
+        *self
+            .prod_ids
+            .get(&(head.clone(), body.to_vec()))
+            .expect("production not found in grammar, remove the cached tables")
+    }
+
+    /// 在当前状态栈下能被移进的终结符（会沿着 ε 移进和归约继续模拟）
+    pub(crate) fn get_exception_symbols(&self, state_stack: &[State]) -> Vec<Element> {
+        self.terminals
+            .iter()
+            .filter(|symbol| self.can_shift(state_stack, symbol))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn can_shift(&self, state_stack: &[State], symbol: &Element) -> bool {
+        self.simulate(&mut state_stack.to_vec(), symbol)
+    }
+
+    pub(crate) fn accepts<'a>(
+        &self,
+        state_stack: &[State],
+        symbols: impl IntoIterator<Item = &'a Element>,
+    ) -> bool {
+        let mut stack = state_stack.to_vec();
+        symbols
+            .into_iter()
+            .all(|symbol| self.simulate(&mut stack, symbol))
+    }
+
+    /// 只用状态栈模拟分析：移进 `symbol` 成功返回 true
+    pub(crate) fn simulate(&self, stack: &mut Vec<State>, symbol: &Element) -> bool {
+        let epsilon = Element::Terminal("ε".to_string());
+        // 防止病态文法下 ε 移进/归约死循环
+        for _ in 0..1024 {
+            let state = *stack.last().unwrap();
+            match self.action_table.get(&(state, symbol.clone())) {
+                Some(Action::Shift(next)) => {
+                    stack.push(*next);
+                    return true;
+                }
+                Some(Action::Accept) => return true,
+                Some(Action::Reduce(head, body)) => {
+                    stack.truncate(stack.len().saturating_sub(body.len()));
+                    let Some(state) = stack.last() else {
+                        return false;
+                    };
+                    match self.goto_table.get(&(*state, head.clone())) {
+                        Some(next) => stack.push(*next),
+                        None => return false,
+                    }
+                }
+                None => match self.action_table.get(&(state, epsilon.clone())) {
+                    Some(Action::Shift(next)) => stack.push(*next),
+                    _ => return false,
+                },
+            }
+        }
+        false
+    }
+}
@@ -1,12 +1,59 @@
+mod actions;
+mod ambiguity;
+mod codegen;
+mod counterexample;
+mod cst;
+mod diagnostic;
+mod driver;
+mod earley;
+mod export;
+mod forest;
+mod glr;
+#[rustfmt::skip]
 mod grammar;
+mod grammar_reader;
+mod import;
+mod lexer;
+mod ll1;
 mod lr1_parser;
+mod repair;
+mod report;
+mod simplify;
+mod transform;
 mod types;
 
+pub use actions::{Actions, Slot, TreeBuilder};
+pub use ambiguity::Ambiguity;
+pub use codegen::{generate, generate_in, PARSER_PATH};
+pub use counterexample::{Counterexample, Derivation};
+pub use cst::{
+    parse_lossless, parse_lossless_with, CstBuilder, GreenElement, GreenNode, GreenToken,
+    SyntaxElement, SyntaxNode, SyntaxToken,
+};
+pub use diagnostic::{ErrorType, ParserError};
+pub use driver::{FeedStatus, LR1Driver, ParseOutcome};
+pub use earley::EarleyParser;
+pub use export::{railroad_html, railroad_svg, to_bison, to_ebnf, to_native};
+pub use forest::{Family, Forest, ForestId, ForestNode};
+pub use glr::{GLRDriver, GLRTables};
 pub use grammar::Grammar;
-pub use lr1_parser::{LR1Parser, TreeNode};
+pub use grammar_reader::GrammarError;
+pub use import::GrammarFormat;
+pub use lexer::Lexer;
+pub use ll1::{LL1Conflict, LL1ConflictKind, LL1Parser};
+pub use lr1_parser::{
+    Action, ActionSets, Conflict, LR1Parser, NodeKind, ParseTables, ProdId, TreeNode,
+};
+pub use repair::Recovery;
+pub use simplify::TreeSimplifier;
+pub use transform::{
+    eliminate_epsilon, eliminate_left_recursion, eliminate_units, left_factor, remove_useless,
+    to_cnf, to_gnf, Transformed,
+};
 pub use types::*;
 
 const DATA_PATH: &str = "./data/";
-const ACTION_TABLE: &str = "./data/action_table.rcp";
-const GOTO_TABLE: &str = "./data/goto_table.rcp";
-const LR1_SETS: &str = "./data/lr1_sets.rcp";
+// 缓存文件名中还带有文法的指纹，换了文法不会读到旧的分析表
+const ACTION_TABLE: &str = "action_table";
+const GOTO_TABLE: &str = "goto_table";
+const LR1_SETS: &str = "lr1_sets";
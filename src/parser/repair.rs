@@ -0,0 +1,266 @@
+use crate::parser::lr1_parser::ParseTables;
+use crate::parser::types::Element;
+use std::collections::{HashSet, VecDeque};
+
+/// 出错后的恢复方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// 单记号插入/删除，不行再跳到分隔符并回退到最近的分区
+    #[default]
+    Panic,
+    /// 搜索代价最小的插入/删除/替换序列（类似 grmtools 的 CPCT+）
+    Repair,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    Insert(Element),
+    Delete,
+    Substitute(Element),
+    Shift,
+}
+
+/// 一次修复最多包含的插入/删除/替换次数
+const MAX_COST: usize = 3;
+/// 修复后需要连续移进的真实记号数
+const SHIFT_DISTANCE: usize = 3;
+/// 比较候选修复时，最多往后试分析的记号数
+const RANK_WINDOW: usize = 20;
+/// 搜索的配置数上限，防止大文法下搜索爆炸
+const MAX_EXPLORE: usize = 5000;
+/// 修复时需要看到的输入窗口
+pub(crate) const REPAIR_WINDOW: usize = MAX_COST + SHIFT_DISTANCE + RANK_WINDOW;
+
+struct Config {
+    stack: Vec<usize>,
+    pos: usize,
+    shifted: usize,
+    cost: usize,
+    repairs: Vec<Repair>,
+}
+
+/// 在 `input`（从出错记号开始的输入窗口）上搜索修复序列。
+/// 先找出所有代价最小、修复后能连续移进 `SHIFT_DISTANCE` 个记号的序列，
+/// 再选能继续分析得最远的那个。
+pub(crate) fn find_repair(
+    parser: &ParseTables,
+    stack: &[usize],
+    input: &[Element],
+) -> Option<Vec<Repair>> {
+    let end = Element::Terminal("#".to_string());
+    let mut queue = VecDeque::new();
+    queue.push_back(Config {
+        stack: stack.to_vec(),
+        pos: 0,
+        shifted: 0,
+        cost: 0,
+        repairs: Vec::new(),
+    });
+    let mut seen = HashSet::new();
+    let mut found: Vec<Config> = Vec::new();
+    let mut explored = 0;
+
+    // 0-1 BFS：移进不增加代价，放到队首
+    while let Some(config) = queue.pop_front() {
+        if found.first().is_some_and(|best| config.cost > best.cost) {
+            break;
+        }
+        explored += 1;
+        if explored > MAX_EXPLORE {
+            break;
+        }
+        if !seen.insert((config.stack.clone(), config.pos, config.shifted)) {
+            continue;
+        }
+
+        let token = input.get(config.pos);
+        if let Some(token) = token {
+            let mut stack = config.stack.clone();
+            if parser.simulate(&mut stack, token) {
+                let mut repairs = config.repairs.clone();
+                repairs.push(Repair::Shift);
+                let next = Config {
+                    stack,
+                    pos: config.pos + 1,
+                    shifted: config.shifted + 1,
+                    cost: config.cost,
+                    repairs,
+                };
+                if *token == end || next.shifted >= SHIFT_DISTANCE || next.pos == input.len() {
+                    found.push(next);
+                } else {
+                    queue.push_front(next);
+                }
+            }
+        }
+
+        if config.cost == MAX_COST {
+            continue;
+        }
+        let replaceable = token.filter(|token| **token != end);
+        for symbol in parser.get_exception_symbols(&config.stack) {
+            if symbol == end {
+                continue;
+            }
+            let mut stack = config.stack.clone();
+            if !parser.simulate(&mut stack, &symbol) {
+                continue;
+            }
+            if replaceable.is_some_and(|token| *token != symbol) {
+                let mut repairs = config.repairs.clone();
+                repairs.push(Repair::Substitute(symbol.clone()));
+                queue.push_back(Config {
+                    stack: stack.clone(),
+                    pos: config.pos + 1,
+                    shifted: 0,
+                    cost: config.cost + 1,
+                    repairs,
+                });
+            }
+            let mut repairs = config.repairs.clone();
+            repairs.push(Repair::Insert(symbol));
+            queue.push_back(Config {
+                stack,
+                pos: config.pos,
+                shifted: 0,
+                cost: config.cost + 1,
+                repairs,
+            });
+        }
+        if replaceable.is_some() {
+            let mut repairs = config.repairs.clone();
+            repairs.push(Repair::Delete);
+            queue.push_back(Config {
+                stack: config.stack.clone(),
+                pos: config.pos + 1,
+                shifted: 0,
+                cost: config.cost + 1,
+                repairs,
+            });
+        }
+    }
+
+    // 能继续分析得越远越好；一样远时取先找到的
+    found
+        .into_iter()
+        .map(|config| {
+            let mut stack = config.stack;
+            let distance = input[config.pos..]
+                .iter()
+                .take_while(|token| parser.simulate(&mut stack, token))
+                .count();
+            (distance, config.repairs)
+        })
+        .rev()
+        .max_by_key(|(distance, _)| *distance)
+        .map(|(_, repairs)| repairs)
+}
+
+pub(crate) fn describe_repairs(repairs: &[Repair], input: &[Element]) -> String {
+    let mut pos = 0;
+    let mut steps = Vec::new();
+    for repair in repairs {
+        match repair {
+            Repair::Insert(symbol) => steps.push(format!("insert {}", symbol)),
+            Repair::Delete => {
+                steps.push(format!("delete {}", input[pos]));
+                pos += 1;
+            }
+            Repair::Substitute(symbol) => {
+                steps.push(format!("replace {} with {}", input[pos], symbol));
+                pos += 1;
+            }
+            Repair::Shift => pos += 1,
+        }
+    }
+    steps.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Grammar, LR1Parser};
+    use Repair::*;
+
+    const STATEMENTS: &str =
+        "%token x y z\nCompUnit': P ;\nP: P A | A ;\nA: x '=' L ';' ;\nL: V | L ',' V ;\nV: y | z ;";
+
+    /// 读入 `prefix` 后在 `rest` 上出错时找到的修复
+    fn repair(prefix: &str, rest: &str) -> Option<Vec<Repair>> {
+        let grammar: Grammar = STATEMENTS.parse().unwrap();
+        let tables = LR1Parser::from_grammar(&grammar).into_tables();
+        let mut stack = vec![0];
+        for token in Element::terminals(prefix) {
+            assert!(
+                tables.simulate(&mut stack, &token),
+                "cannot shift {}",
+                token
+            );
+        }
+        let mut input = Element::terminals(rest);
+        assert!(!tables.can_shift(&stack, &input[0]));
+        input.push(Element::Terminal("#".to_string()));
+        find_repair(&tables, &stack, &input)
+    }
+
+    fn terminal(name: &str) -> Element {
+        Element::Terminal(name.to_string())
+    }
+
+    #[test]
+    fn inserts_missing_token() {
+        assert_eq!(
+            repair("x", "y ';' x '=' y ';'"),
+            Some(vec![Insert(terminal("'='")), Shift, Shift, Shift])
+        );
+    }
+
+    #[test]
+    fn deletes_extra_token() {
+        assert_eq!(
+            repair("x '='", "'=' y ';' x '=' y ';'"),
+            Some(vec![Delete, Shift, Shift, Shift])
+        );
+    }
+
+    #[test]
+    fn substitutes_wrong_token() {
+        assert_eq!(
+            repair("x '=' y", "':' x '=' y ';'"),
+            Some(vec![Substitute(terminal("';'")), Shift, Shift, Shift])
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_first_terminal() {
+        // 插入 y 或 z 都可以，取名字靠前的
+        assert_eq!(
+            repair("x '='", "';' x '=' y ';'"),
+            Some(vec![Insert(terminal("y")), Shift, Shift, Shift])
+        );
+    }
+
+    #[test]
+    fn ties_prefer_insertion_over_deletion() {
+        // 补上 `,` 或删掉第二个 y 都能分析到最后，先找到的是插入
+        assert_eq!(
+            repair("x '=' y", "y ';' x '=' y ';'"),
+            Some(vec![Insert(terminal("','")), Shift, Shift, Shift])
+        );
+    }
+
+    #[test]
+    fn repair_with_several_edits() {
+        // 缺了 `=` 和 y：两次插入
+        assert_eq!(
+            repair("x", "';' x '=' y ';'"),
+            Some(vec![
+                Insert(terminal("'='")),
+                Insert(terminal("y")),
+                Shift,
+                Shift,
+                Shift
+            ])
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::parser::lr1_parser::{Action, LR1Item, LR1Parser, ProdId};
+use crate::parser::types::Element;
+use crate::parser::Grammar;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+fn kind(action: &Action) -> &'static str {
+    match action {
+        Action::Shift(_) => "shift",
+        Action::Reduce(..) => "reduce",
+        Action::Accept => "accept",
+    }
+}
+
+impl LR1Parser {
+    /// 类似 `bison -v` 输出的 .output 文件：产生式编号、冲突汇总，
+    /// 以及每个状态的核心项目、闭包项目、转移、动作和冲突。需要先构造项集族和分析表。
+    /// 项集族或分析表中出现 `grammar` 没有的产生式时返回错误
+    pub fn report(&self, grammar: &Grammar) -> Result<String, String> {
+        let productions = grammar.productions();
+        let prod_ids: HashMap<(&Element, &[Element]), ProdId> = productions
+            .iter()
+            .map(|(head, body)| (head, body.as_slice()))
+            .zip(0..)
+            .collect();
+        let prod_id = |head: &Element, body: &[Element]| {
+            prod_ids.get(&(head, body)).copied().ok_or_else(|| {
+                let body: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+                format!(
+                    "production `{} -> {}` is not in the grammar",
+                    head,
+                    body.join(" ")
+                )
+            })
+        };
+        let describe = |action: &Action| -> Result<String, String> {
+            Ok(match action {
+                Action::Shift(target) => format!("shift, go to state {}", target),
+                Action::Reduce(head, body) => {
+                    let symbols: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+                    let id = prod_id(head, body)?;
+                    format!(
+                        "reduce using rule {} ({} -> {})",
+                        id,
+                        head,
+                        symbols.join(" ")
+                    )
+                }
+                Action::Accept => "accept".to_string(),
+            })
+        };
+
+        let action_sets = self.action_sets(grammar);
+        let conflicts = self.conflicts(grammar);
+        let mut out = String::new();
+
+        writeln!(out, "Grammar\n").unwrap();
+        for (id, (head, body)) in productions.iter().enumerate() {
+            let body: Vec<String> = body.iter().map(|v| v.to_string()).collect();
+            writeln!(out, "  {:>4} {} -> {}", id, head, body.join(" ")).unwrap();
+        }
+        writeln!(out).unwrap();
+
+        if !conflicts.is_empty() {
+            writeln!(out, "Conflicts\n").unwrap();
+            for conflict in &conflicts {
+                let kinds: Vec<&str> = conflict.actions.iter().map(kind).collect();
+                writeln!(
+                    out,
+                    "  State {} conflict on {}: {}",
+                    conflict.state,
+                    conflict.lookahead,
+                    kinds.join("/")
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        for (state, item_set) in self.lr1_sets.iter().enumerate() {
+            writeln!(out, "State {}\n", state).unwrap();
+
+            // 同一核心的项目合并成一行，向前看符号用 / 分隔
+            let mut items: BTreeMap<(bool, ProdId, usize), (&LR1Item, Vec<String>)> =
+                BTreeMap::new();
+            for item in item_set {
+                let kernel = item.dot > 0 || item.head == grammar.start_symbol;
+                let key = (!kernel, prod_id(&item.head, &item.body)?, item.dot);
+                let entry = items.entry(key).or_insert((item, Vec::new()));
+                entry.1.push(item.lookahead.to_string());
+            }
+            let mut closure = false;
+            writeln!(out, "  kernel").unwrap();
+            for ((is_closure, ..), (item, mut lookaheads)) in items {
+                if is_closure && !closure {
+                    writeln!(out, "  closure").unwrap();
+                    closure = true;
+                }
+                lookaheads.sort();
+                writeln!(out, "    {}, {}", item.core(), lookaheads.join(" / ")).unwrap();
+            }
+
+            let mut cells: Vec<(&Element, &Vec<Action>)> = action_sets
+                .iter()
+                .filter(|((v, _), _)| *v == state)
+                .map(|((_, symbol), actions)| (symbol, actions))
+                .collect();
+            cells.sort_by_key(|(symbol, _)| symbol.to_string());
+            let mut gotos: Vec<(&Element, &usize)> = self
+                .goto_table
+                .iter()
+                .filter(|((v, _), _)| *v == state)
+                .map(|((_, symbol), target)| (symbol, target))
+                .collect();
+            gotos.sort_by_key(|(symbol, _)| symbol.to_string());
+
+            // 移进被覆盖的转移也列出来
+            let shifts = cells.iter().flat_map(|(symbol, actions)| {
+                actions.iter().filter_map(move |action| match action {
+                    Action::Shift(target) => Some((*symbol, target)),
+                    _ => None,
+                })
+            });
+            let transitions: Vec<(&Element, &usize)> = shifts.chain(gotos).collect();
+            if !transitions.is_empty() {
+                writeln!(out, "\n  transitions").unwrap();
+                for (symbol, target) in transitions {
+                    writeln!(out, "    {:<16} go to state {}", symbol.to_string(), target).unwrap();
+                }
+            }
+
+            // 分析表实际采用的动作在前，被舍弃的动作放在方括号中
+            if !cells.is_empty() {
+                writeln!(out, "\n  actions").unwrap();
+            }
+            for (symbol, actions) in &cells {
+                let chosen = self.action_table.get(&(state, (*symbol).clone()));
+                for action in actions.iter().filter(|action| Some(*action) == chosen) {
+                    writeln!(out, "    {:<16} {}", symbol.to_string(), describe(action)?).unwrap();
+                }
+                for action in actions.iter().filter(|action| Some(*action) != chosen) {
+                    writeln!(
+                        out,
+                        "    {:<16} [{}]",
+                        symbol.to_string(),
+                        describe(action)?
+                    )
+                    .unwrap();
+                }
+            }
+
+            let state_conflicts: Vec<_> = conflicts.iter().filter(|v| v.state == state).collect();
+            if !state_conflicts.is_empty() {
+                writeln!(out, "\n  conflicts").unwrap();
+            }
+            for conflict in state_conflicts {
+                let kinds: Vec<&str> = conflict.actions.iter().map(kind).collect();
+                writeln!(out, "    {} on {}", kinds.join("/"), conflict.lookahead).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dangling_else_report() {
+        let grammar: Grammar = "%token c x\nCompUnit': S ;\nS: 'if' c S | 'if' c S 'else' S | x ;"
+            .parse()
+            .unwrap();
+        let parser = LR1Parser::from_grammar(&grammar);
+        let report = parser.report(&grammar).unwrap();
+        assert!(report.starts_with("Grammar\n\n     0 CompUnit' -> S\n     1 S -> 'if' c S\n"));
+        assert!(report.contains("     3 S -> x\n\nConflicts\n"));
+
+        let conflicts = parser.conflicts(&grammar);
+        let [conflict] = conflicts.as_slice() else {
+            panic!("{}", report)
+        };
+        let state = conflict.state;
+        assert!(report.contains(&format!(
+            "  State {} conflict on 'else': shift/reduce\n",
+            state
+        )));
+
+        // 各状态一节：先列核心项目和闭包项目，再列转移和动作
+        let section = |state: usize| {
+            let start = report.find(&format!("\nState {}\n", state)).unwrap();
+            let end = report[start + 1..]
+                .find("\nState ")
+                .map_or(report.len(), |v| start + 1 + v);
+            &report[start..end]
+        };
+        let initial = section(0);
+        assert!(initial
+            .contains("  kernel\n    CompUnit' -> • S, #\n  closure\n    S -> • 'if' c S, #\n"));
+        assert!(initial.contains("\n  transitions\n"));
+        assert!(initial.contains("\n  actions\n    'if'             shift, go to state "));
+
+        // 分析表采用的动作在前，被舍弃的动作放在方括号中
+        let conflicted = section(state);
+        assert!(conflicted.contains("    S -> 'if' c S •, # / 'else'\n"));
+        let actions: Vec<&str> = conflicted
+            .lines()
+            .skip_while(|line| *line != "  actions")
+            .filter_map(|line| line.strip_prefix("    'else'           "))
+            .collect();
+        let [chosen, discarded] = actions[..] else {
+            panic!("{}", conflicted)
+        };
+        let discarded = discarded
+            .strip_prefix('[')
+            .and_then(|v| v.strip_suffix(']'))
+            .unwrap();
+        let mut both = [chosen, discarded];
+        both.sort();
+        assert_eq!(both[0], "reduce using rule 1 (S -> 'if' c S)");
+        assert!(both[1].starts_with("shift, go to state "));
+        assert!(conflicted.ends_with("  conflicts\n    shift/reduce on 'else'\n"));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::parser::grammar::Grammar;
+use crate::parser::lr1_parser::{NodeKind, TreeNode};
+use crate::parser::types::Element;
+use std::collections::HashSet;
+
+/// 按文法中的 `%inline` 和 `%flatten` 化简语法树。
+///
+/// `%inline A`：A 节点被它的子节点替代，如 `ExpAux`、`MulExpOp`；
+/// `%flatten A`：A 节点中首尾的 A 子节点被展开，右递归的
+/// `BlockItemRepeat: BlockItem BlockItemRepeat#|#ε` 变成一个节点下的 `BlockItem` 序列。
+/// 两者同时声明时，整个列表并入父节点。错误恢复产生的节点保持原样
+#[derive(Debug, Clone, Default)]
+pub struct TreeSimplifier {
+    inline: HashSet<Element>,
+    flatten: HashSet<Element>,
+}
+
+/// 化简中的节点：已处理的子节点和还没处理的子节点
+struct Frame {
+    node: TreeNode,
+    pending: std::vec::IntoIter<TreeNode>,
+    done: Vec<TreeNode>,
+}
+
+impl TreeSimplifier {
+    pub fn new(grammar: &Grammar) -> Self {
+        let symbols = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| Element::NotTerminal(name.to_string()))
+                .collect()
+        };
+        Self {
+            inline: symbols(&grammar.inline_list),
+            flatten: symbols(&grammar.flatten_list),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inline.is_empty() && self.flatten.is_empty()
+    }
+
+    /// 化简以 `root` 为根的树。根节点本身不会被并入
+    pub fn simplify(&self, root: TreeNode) -> TreeNode {
+        if root.children.is_none() {
+            return root;
+        }
+        // 列表产生式的树可能很深，用显式的栈后序遍历
+        let mut stack = vec![Frame::open(root)];
+        loop {
+            let frame = stack.last_mut().unwrap();
+            match frame.pending.next() {
+                Some(child) if child.children.is_some() => stack.push(Frame::open(child)),
+                Some(leaf) => frame.done.push(leaf),
+                None => {
+                    let node = self.close(stack.pop().unwrap());
+                    match stack.last_mut() {
+                        Some(parent) if self.is_marked(&self.inline, &node) => {
+                            parent.done.extend(node.children.unwrap())
+                        }
+                        Some(parent) => parent.done.push(node),
+                        None => return node,
+                    }
+                }
+            }
+        }
+    }
+
+    /// 子节点都已化简，展开首尾与自身同名的列表节点
+    fn close(&self, frame: Frame) -> TreeNode {
+        let Frame {
+            mut node, mut done, ..
+        } = frame;
+        // 子节点已被取出，这里不能用 is_marked
+        if node.kind == NodeKind::Normal && self.flatten.contains(&node.element) {
+            let same = |child: Option<&TreeNode>| {
+                child.is_some_and(|child| {
+                    child.element == node.element && self.is_marked(&self.flatten, child)
+                })
+            };
+            if same(done.last()) {
+                let tail = done.pop().unwrap().children.unwrap();
+                done.extend(tail);
+            }
+            if same(done.first()) {
+                let head = done.remove(0).children.unwrap();
+                done.splice(0..0, head);
+            }
+        }
+        node.children = Some(done);
+        node
+    }
+
+    fn is_marked(&self, set: &HashSet<Element>, node: &TreeNode) -> bool {
+        node.kind == NodeKind::Normal && node.children.is_some() && set.contains(&node.element)
+    }
+}
+
+impl Frame {
+    fn open(mut node: TreeNode) -> Self {
+        let children = node.children.take().unwrap_or_default();
+        Self {
+            node,
+            pending: children.into_iter(),
+            done: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::driver::LR1Driver;
+    use crate::parser::LR1Parser;
+    use std::sync::Arc;
+
+    /// 把树写成 `A(b C(d))` 的形式，ε 叶子省略
+    fn shape(node: &TreeNode) -> String {
+        match &node.children {
+            Some(children) => {
+                let children: Vec<String> = children
+                    .iter()
+                    .filter(|child| child.element != Element::Terminal("ε".to_string()))
+                    .map(shape)
+                    .collect();
+                format!("{}({})", node.element, children.join(" "))
+            }
+            None => node.element.to_string(),
+        }
+    }
+
+    fn simplify(grammar: &str, input: &str) -> String {
+        let grammar: Grammar = grammar.parse().unwrap();
+        let tables = Arc::new(LR1Parser::from_grammar(&grammar).into_tables());
+        let outcome = LR1Driver::new(tables).construct_tree(&Element::terminals(input));
+        assert!(outcome.errors.is_empty());
+        shape(&TreeSimplifier::new(&grammar).simplify(outcome.tree))
+    }
+
+    const LIST: &str = "%token x\nCompUnit': L ;\nL: S L | ;\nS: x Op x ;\nOp: '+' | '-' ;";
+
+    #[test]
+    fn inline_and_flatten() {
+        let input = "x '+' x x '-' x";
+        assert_eq!(
+            simplify(LIST, input),
+            "L(S(x Op('+') x) L(S(x Op('-') x) L()))"
+        );
+        let grammar = format!("%flatten L\n%inline Op\n{}", LIST);
+        assert_eq!(simplify(&grammar, input), "L(S(x '+' x) S(x '-' x))");
+    }
+
+    #[test]
+    fn inlined_list_joins_its_parent() {
+        let grammar =
+            "%token x\n%flatten L\n%inline L\nCompUnit': B ;\nB: '{' L '}' ;\nL: S L | ;\nS: x ;";
+        assert_eq!(
+            simplify(grammar, "'{' x x x '}'"),
+            "B('{' S(x) S(x) S(x) '}')"
+        );
+    }
+}
@@ -0,0 +1,841 @@
+use crate::parser::grammar::Grammar;
+use crate::parser::lr1_parser::ProdId;
+use crate::parser::types::{Element, Item, PHead};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+/// 变换得到的文法。`origins[i]` 是新文法中编号为 `i` 的产生式（`Grammar::productions` 的顺序）
+/// 由原文法的哪些产生式得到；变换引入的辅助产生式，如 CNF 中的 `T@1 → '+'`，可能没有来源
+pub struct Transformed {
+    pub grammar: Grammar,
+    pub origins: Vec<Vec<ProdId>>,
+}
+
+impl Transformed {
+    /// 在结果上再做一次变换，来源仍然对应最初的文法
+    pub fn then(self, transform: impl FnOnce(&Grammar) -> Transformed) -> Transformed {
+        let next = transform(&self.grammar);
+        let origins = next
+            .origins
+            .iter()
+            .map(|ids| {
+                let ids: BTreeSet<ProdId> = ids
+                    .iter()
+                    .flat_map(|id| self.origins[*id].iter().copied())
+                    .collect();
+                ids.into_iter().collect()
+            })
+            .collect();
+        Transformed {
+            grammar: next.grammar,
+            origins,
+        }
+    }
+}
+
+/// 消除左递归（直接和间接的），`A → A α | β` 变成 `A → β A'`、`A' → α A' | ε`，
+/// 与 g2.txt 中手工改写的 `MulExp'` 相同。经过可空符号的隐藏左递归要先消除 ε 产生式
+pub fn eliminate_left_recursion(grammar: &Grammar) -> Transformed {
+    let mut rules = Rules::new(grammar);
+    rules.eliminate_left_recursion(true);
+    rules.finish()
+}
+
+/// 提取左公因子，`A → a B | a C` 变成 `A → a A'`、`A' → B | C`。只提取字面上相同的前缀
+pub fn left_factor(grammar: &Grammar) -> Transformed {
+    let mut rules = Rules::new(grammar);
+    rules.left_factor();
+    rules.finish()
+}
+
+/// 消除 ε 产生式。开始符号能推出空串时保留 `S → ε`
+pub fn eliminate_epsilon(grammar: &Grammar) -> Transformed {
+    let mut rules = Rules::new(grammar);
+    rules.eliminate_epsilon();
+    rules.remove_non_generating();
+    rules.finish()
+}
+
+/// 消除单产生式 `A → B`，A 直接得到 B 的候选式
+pub fn eliminate_units(grammar: &Grammar) -> Transformed {
+    let mut rules = Rules::new(grammar);
+    rules.eliminate_units();
+    rules.finish()
+}
+
+/// 删除推不出终结符串的符号和从开始符号不可达的符号
+pub fn remove_useless(grammar: &Grammar) -> Transformed {
+    let mut rules = Rules::new(grammar);
+    rules.remove_non_generating();
+    rules.remove_unreachable();
+    rules.finish()
+}
+
+/// 乔姆斯基范式：每个产生式是 `A → B C` 或 `A → a`，只有开始符号可以推出 ε
+pub fn to_cnf(grammar: &Grammar) -> Transformed {
+    eliminate_epsilon(grammar)
+        .then(eliminate_units)
+        .then(remove_useless)
+        .then(|grammar| {
+            let mut rules = Rules::new(grammar);
+            rules.binarize();
+            rules.finish()
+        })
+}
+
+/// 格里巴赫范式：每个产生式是 `A → a B C ...`，只有开始符号可以推出 ε
+pub fn to_gnf(grammar: &Grammar) -> Transformed {
+    eliminate_epsilon(grammar)
+        .then(eliminate_units)
+        .then(remove_useless)
+        .then(|grammar| {
+            let mut rules = Rules::new(grammar);
+            rules.eliminate_left_recursion(false);
+            rules.expand_leading();
+            rules.wrap_tails();
+            rules.finish()
+        })
+        .then(remove_useless)
+}
+
+/// 变换中的候选式，右部不含 ε
+#[derive(Debug, Clone)]
+struct Alt {
+    body: Item,
+    origin: BTreeSet<ProdId>,
+}
+
+/// 变换中的文法，每个候选式都带着它的来源
+struct Rules<'a> {
+    grammar: &'a Grammar,
+    heads: Vec<PHead>,
+    alts: HashMap<PHead, Vec<Alt>>,
+    /// CNF 和 GNF 中代替终结符的非终结符
+    wrappers: HashMap<Element, PHead>,
+}
+
+impl<'a> Rules<'a> {
+    fn new(grammar: &'a Grammar) -> Self {
+        let mut alts: HashMap<PHead, Vec<Alt>> = HashMap::new();
+        for (id, (head, body)) in grammar.productions().into_iter().enumerate() {
+            alts.entry(head).or_default().push(Alt {
+                body: body
+                    .into_iter()
+                    .filter(|symbol| !is_epsilon(symbol))
+                    .collect(),
+                origin: BTreeSet::from([id]),
+            });
+        }
+        Self {
+            grammar,
+            heads: grammar.head_list.clone(),
+            alts,
+            wrappers: HashMap::new(),
+        }
+    }
+
+    fn finish(self) -> Transformed {
+        let mut pro_list = HashMap::new();
+        let mut origins = Vec::new();
+        for head in &self.heads {
+            let alts = &self.alts[head];
+            let bodies = alts
+                .iter()
+                .map(|alt| match alt.body.is_empty() {
+                    true => vec![Element::Terminal("ε".to_string())],
+                    false => alt.body.clone(),
+                })
+                .collect();
+            origins.extend(alts.iter().map(|alt| alt.origin.iter().copied().collect()));
+            pro_list.insert(head.clone(), bodies);
+        }
+        Transformed {
+            grammar: self.grammar.with_productions(self.heads, pro_list),
+            origins,
+        }
+    }
+
+    fn start(&self) -> &PHead {
+        &self.grammar.start_symbol
+    }
+
+    /// 以 `name` 加撇号得到的新非终结符，如 `MulExp'`
+    fn primed(&self, head: &PHead) -> PHead {
+        let mut name = head.to_string();
+        loop {
+            name.push('\'');
+            if self.is_unused(&name) {
+                return Element::NotTerminal(name);
+            }
+        }
+    }
+
+    /// 以 `name` 加编号得到的新非终结符，如 `Exp@1`
+    fn numbered(&self, name: &str) -> PHead {
+        (1..)
+            .map(|n| format!("{}@{}", name, n))
+            .find(|name| self.is_unused(name))
+            .map(Element::NotTerminal)
+            .unwrap()
+    }
+
+    fn is_unused(&self, name: &str) -> bool {
+        !self
+            .alts
+            .contains_key(&Element::NotTerminal(name.to_string()))
+            && !self.grammar.token_list.iter().any(|token| token == name)
+    }
+
+    /// 在 `after` 之后加入新的非终结符
+    fn insert(&mut self, after: &PHead, head: PHead, alts: Vec<Alt>) {
+        let index = self
+            .heads
+            .iter()
+            .position(|v| v == after)
+            .map_or(self.heads.len(), |i| i + 1);
+        self.heads.insert(index, head.clone());
+        self.alts.insert(head, alts);
+    }
+
+    fn nullable(&self) -> HashSet<PHead> {
+        let mut nullable = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for head in &self.heads {
+                if !nullable.contains(head)
+                    && self.alts[head]
+                        .iter()
+                        .any(|alt| alt.body.iter().all(|symbol| nullable.contains(symbol)))
+                {
+                    nullable.insert(head.clone());
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    fn eliminate_epsilon(&mut self) {
+        let nullable = self.nullable();
+        for head in self.heads.clone() {
+            let mut alts = Vec::new();
+            let mut empty = BTreeSet::new();
+            for alt in &self.alts[&head] {
+                // 每个可空的符号都可以省略，保留全部符号的写在最前面
+                let mut bodies: Vec<Item> = vec![Vec::new()];
+                for symbol in &alt.body {
+                    let kept = bodies
+                        .iter()
+                        .map(|body| [body.as_slice(), std::slice::from_ref(symbol)].concat());
+                    bodies = match nullable.contains(symbol) {
+                        true => kept.chain(bodies.iter().cloned()).collect(),
+                        false => kept.collect(),
+                    };
+                }
+                for body in bodies {
+                    if body.is_empty() {
+                        empty.extend(alt.origin.iter().copied());
+                    } else if body != [head.clone()] {
+                        push(
+                            &mut alts,
+                            Alt {
+                                body,
+                                origin: alt.origin.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+            if head == *self.start() && nullable.contains(&head) {
+                push(
+                    &mut alts,
+                    Alt {
+                        body: Vec::new(),
+                        origin: empty,
+                    },
+                );
+            }
+            self.alts.insert(head, alts);
+        }
+    }
+
+    fn eliminate_units(&mut self) {
+        let mut result = HashMap::new();
+        for head in &self.heads {
+            // 经过单产生式能到达的非终结符，来源包括路径上的单产生式
+            let mut alts = Vec::new();
+            let mut seen = HashSet::from([head.clone()]);
+            let mut queue = VecDeque::from([(head.clone(), BTreeSet::new())]);
+            while let Some((symbol, path)) = queue.pop_front() {
+                for alt in &self.alts[&symbol] {
+                    let origin: BTreeSet<ProdId> = path.union(&alt.origin).copied().collect();
+                    match alt.body.as_slice() {
+                        [unit @ Element::NotTerminal(_)] => {
+                            if seen.insert(unit.clone()) {
+                                queue.push_back((unit.clone(), origin));
+                            }
+                        }
+                        _ => push(
+                            &mut alts,
+                            Alt {
+                                body: alt.body.clone(),
+                                origin,
+                            },
+                        ),
+                    }
+                }
+            }
+            result.insert(head.clone(), alts);
+        }
+        self.alts = result;
+    }
+
+    fn remove_non_generating(&mut self) {
+        let mut generating: HashSet<PHead> = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for head in &self.heads {
+                if !generating.contains(head)
+                    && self.alts[head]
+                        .iter()
+                        .any(|alt| is_generating(&alt.body, &generating))
+                {
+                    generating.insert(head.clone());
+                    changed = true;
+                }
+            }
+        }
+        // 开始符号推不出终结符串时语言为空，仍然保留它
+        let start = self.start().clone();
+        self.heads
+            .retain(|head| generating.contains(head) || *head == start);
+        for alts in self.alts.values_mut() {
+            alts.retain(|alt| is_generating(&alt.body, &generating));
+        }
+        self.alts
+            .retain(|head, _| generating.contains(head) || *head == start);
+    }
+
+    fn remove_unreachable(&mut self) {
+        let mut reachable = HashSet::from([self.start().clone()]);
+        let mut stack = vec![self.start().clone()];
+        while let Some(head) = stack.pop() {
+            for alt in self.alts.get(&head).into_iter().flatten() {
+                for symbol in &alt.body {
+                    if matches!(symbol, Element::NotTerminal(_)) && reachable.insert(symbol.clone())
+                    {
+                        stack.push(symbol.clone());
+                    }
+                }
+            }
+        }
+        self.heads.retain(|head| reachable.contains(head));
+        self.alts.retain(|head, _| reachable.contains(head));
+    }
+
+    /// 从 `from` 开始只看候选式的第一个符号，能否到达 `to`
+    fn left_reaches(&self, from: &PHead, to: &PHead) -> bool {
+        let mut seen = HashSet::from([from]);
+        let mut stack = vec![from];
+        while let Some(head) = stack.pop() {
+            for alt in self.alts.get(head).into_iter().flatten() {
+                match alt.body.first() {
+                    Some(first) if first == to => return true,
+                    Some(first @ Element::NotTerminal(_)) if seen.insert(first) => {
+                        stack.push(first)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        false
+    }
+
+    /// `epsilon` 为 false 时不引入 ε：`A → β | β A'`、`A' → α | α A'`
+    fn eliminate_left_recursion(&mut self, epsilon: bool) {
+        let order = self.heads.clone();
+        for (i, head) in order.iter().enumerate() {
+            // 只展开前面那些能左推导回 head 的非终结符，其余的候选式保持原样
+            loop {
+                let earlier: HashSet<&PHead> = order[..i]
+                    .iter()
+                    .filter(|earlier| self.left_reaches(earlier, head))
+                    .collect();
+                let starts_earlier = |alt: &Alt| {
+                    alt.body
+                        .first()
+                        .is_some_and(|first| earlier.contains(first))
+                };
+                if !self.alts[head].iter().any(starts_earlier) {
+                    break;
+                }
+                let mut alts = Vec::new();
+                for alt in &self.alts[head] {
+                    if !starts_earlier(alt) {
+                        push(&mut alts, alt.clone());
+                        continue;
+                    }
+                    for first in &self.alts[&alt.body[0]] {
+                        push(
+                            &mut alts,
+                            Alt {
+                                body: [first.body.as_slice(), &alt.body[1..]].concat(),
+                                origin: first.origin.union(&alt.origin).copied().collect(),
+                            },
+                        );
+                    }
+                }
+                self.alts.insert(head.clone(), alts);
+            }
+            self.eliminate_direct(head, epsilon);
+        }
+    }
+
+    fn eliminate_direct(&mut self, head: &PHead, epsilon: bool) {
+        let (recursive, others): (Vec<Alt>, Vec<Alt>) = self.alts[head]
+            .iter()
+            .cloned()
+            .partition(|alt| alt.body.first() == Some(head));
+        if recursive.is_empty() {
+            return;
+        }
+        let rest = self.primed(head);
+        let with_rest = |alt: &Alt, skip: usize| Alt {
+            body: [&alt.body[skip..], std::slice::from_ref(&rest)].concat(),
+            origin: alt.origin.clone(),
+        };
+        let mut head_alts = Vec::new();
+        let mut rest_alts = Vec::new();
+        for alt in &others {
+            if !epsilon {
+                push(&mut head_alts, alt.clone());
+            }
+            push(&mut head_alts, with_rest(alt, 0));
+        }
+        // `A → A` 没有意义，直接去掉
+        for alt in recursive.iter().filter(|alt| alt.body.len() > 1) {
+            if !epsilon {
+                push(
+                    &mut rest_alts,
+                    Alt {
+                        body: alt.body[1..].to_vec(),
+                        origin: alt.origin.clone(),
+                    },
+                );
+            }
+            push(&mut rest_alts, with_rest(alt, 1));
+        }
+        if epsilon {
+            push(
+                &mut rest_alts,
+                Alt {
+                    body: Vec::new(),
+                    origin: recursive
+                        .iter()
+                        .flat_map(|alt| alt.origin.iter().copied())
+                        .collect(),
+                },
+            );
+        }
+        self.alts.insert(head.clone(), head_alts);
+        self.insert(head, rest, rest_alts);
+    }
+
+    fn left_factor(&mut self) {
+        let mut queue: VecDeque<PHead> = self.heads.iter().cloned().collect();
+        while let Some(head) = queue.pop_front() {
+            loop {
+                let alts = &self.alts[&head];
+                // 第一组以相同符号开头的候选式
+                let Some(first) = alts.iter().enumerate().find_map(|(i, alt)| {
+                    let first = alt.body.first()?;
+                    alts[i + 1..]
+                        .iter()
+                        .any(|other| other.body.first() == Some(first))
+                        .then_some(first)
+                }) else {
+                    break;
+                };
+                let (group, others): (Vec<Alt>, Vec<Alt>) = alts
+                    .iter()
+                    .cloned()
+                    .partition(|alt| alt.body.first() == Some(first));
+                let position = alts
+                    .iter()
+                    .position(|alt| alt.body.first() == Some(first))
+                    .unwrap();
+                let prefix = (1..)
+                    .take_while(|&len| {
+                        group.iter().all(|alt| {
+                            alt.body.len() >= len && alt.body[..len] == group[0].body[..len]
+                        })
+                    })
+                    .last()
+                    .unwrap_or(1);
+
+                let rest = self.primed(&head);
+                let mut rest_alts = Vec::new();
+                for alt in &group {
+                    push(
+                        &mut rest_alts,
+                        Alt {
+                            body: alt.body[prefix..].to_vec(),
+                            origin: alt.origin.clone(),
+                        },
+                    );
+                }
+                let factored = Alt {
+                    body: [&group[0].body[..prefix], std::slice::from_ref(&rest)].concat(),
+                    origin: group
+                        .iter()
+                        .flat_map(|alt| alt.origin.iter().copied())
+                        .collect(),
+                };
+                let mut alts = others;
+                alts.insert(position, factored);
+                self.alts.insert(head.clone(), alts);
+                self.insert(&head, rest.clone(), rest_alts);
+                queue.push_back(rest);
+            }
+        }
+    }
+
+    /// 代替终结符 `terminal` 的非终结符 `T@n → terminal`
+    fn wrapper(&mut self, terminal: &Element) -> PHead {
+        if let Some(head) = self.wrappers.get(terminal) {
+            return head.clone();
+        }
+        let head = self.numbered("T");
+        let alts = vec![Alt {
+            body: vec![terminal.clone()],
+            origin: BTreeSet::new(),
+        }];
+        let last = self.heads.last().cloned().unwrap();
+        self.insert(&last, head.clone(), alts);
+        self.wrappers.insert(terminal.clone(), head.clone());
+        head
+    }
+
+    /// 把长度至少为 2 的右部中的终结符换成非终结符，再拆成 `A → X A@1`、`A@1 → Y Z` 的形式
+    fn binarize(&mut self) {
+        for head in self.heads.clone() {
+            let mut alts = Vec::new();
+            let mut after = head.clone();
+            for alt in self.alts[&head].clone() {
+                if alt.body.len() < 2 {
+                    push(&mut alts, alt);
+                    continue;
+                }
+                let mut body: Item = alt.body.iter().map(|symbol| self.wrap(symbol)).collect();
+                // 从右往左，每次把最后两个符号合成一个新的非终结符
+                let mut pieces = Vec::new();
+                while body.len() > 2 {
+                    let pair = body.split_off(body.len() - 2);
+                    let piece = self.numbered(&head.to_string());
+                    self.alts.insert(piece.clone(), Vec::new());
+                    pieces.push((piece.clone(), pair));
+                    body.push(piece);
+                }
+                push(
+                    &mut alts,
+                    Alt {
+                        body,
+                        origin: alt.origin.clone(),
+                    },
+                );
+                for (piece, pair) in pieces.into_iter().rev() {
+                    let piece_alts = vec![Alt {
+                        body: pair,
+                        origin: alt.origin.clone(),
+                    }];
+                    self.insert(&after, piece.clone(), piece_alts);
+                    after = piece;
+                }
+            }
+            self.alts.insert(head, alts);
+        }
+    }
+
+    fn wrap(&mut self, symbol: &Element) -> Element {
+        match symbol {
+            Element::Terminal(_) => self.wrapper(symbol),
+            _ => symbol.clone(),
+        }
+    }
+
+    /// 反复展开候选式开头的非终结符，直到每个候选式都以终结符开头。要求文法没有左递归
+    fn expand_leading(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for head in self.heads.clone() {
+                let mut alts = Vec::new();
+                for alt in &self.alts[&head] {
+                    match alt.body.first() {
+                        Some(first @ Element::NotTerminal(_)) => {
+                            changed = true;
+                            for expansion in &self.alts[first] {
+                                push(
+                                    &mut alts,
+                                    Alt {
+                                        body: [expansion.body.as_slice(), &alt.body[1..]].concat(),
+                                        origin: expansion
+                                            .origin
+                                            .union(&alt.origin)
+                                            .copied()
+                                            .collect(),
+                                    },
+                                );
+                            }
+                        }
+                        _ => push(&mut alts, alt.clone()),
+                    }
+                }
+                self.alts.insert(head, alts);
+            }
+        }
+    }
+
+    /// 把候选式第一个符号之后的终结符换成非终结符
+    fn wrap_tails(&mut self) {
+        for head in self.heads.clone() {
+            let mut alts = self.alts[&head].clone();
+            for alt in &mut alts {
+                for i in 1..alt.body.len() {
+                    alt.body[i] = self.wrap(&alt.body[i]);
+                }
+            }
+            self.alts.insert(head, alts);
+        }
+    }
+}
+
+/// 加入候选式，右部相同的合并来源
+fn push(alts: &mut Vec<Alt>, alt: Alt) {
+    match alts.iter_mut().find(|other| other.body == alt.body) {
+        Some(other) => other.origin.extend(alt.origin),
+        None => alts.push(alt),
+    }
+}
+
+fn is_epsilon(symbol: &Element) -> bool {
+    *symbol == Element::Terminal("ε".to_string())
+}
+
+fn is_generating(body: &[Element], generating: &HashSet<PHead>) -> bool {
+    body.iter()
+        .all(|symbol| matches!(symbol, Element::Terminal(_)) || generating.contains(symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::earley::EarleyParser;
+
+    const ARITHMETIC: &str =
+        "%token x\nCompUnit': E ;\nE: E '+' T | T ;\nT: T '*' F | F ;\nF: '(' E ')' | x ;";
+
+    fn accepts(grammar: &Grammar, sentence: &str) -> bool {
+        let outcome = EarleyParser::new(grammar).construct_forest(&Element::terminals(sentence));
+        outcome.errors.is_empty() && outcome.tree.root().is_some()
+    }
+
+    /// 变换前后的文法都接受 `accepted` 中的句子、都不接受 `rejected` 中的句子
+    fn assert_same_language(
+        before: &Grammar,
+        after: &Transformed,
+        accepted: &[&str],
+        rejected: &[&str],
+    ) {
+        for sentence in accepted {
+            assert!(accepts(before, sentence), "original rejects `{}`", sentence);
+            assert!(
+                accepts(&after.grammar, sentence),
+                "transformed rejects `{}`",
+                sentence
+            );
+        }
+        for sentence in rejected {
+            assert!(
+                !accepts(before, sentence),
+                "original accepts `{}`",
+                sentence
+            );
+            assert!(
+                !accepts(&after.grammar, sentence),
+                "transformed accepts `{}`",
+                sentence
+            );
+        }
+    }
+
+    /// 新文法中产生式 `head → body` 的来源
+    fn origin(after: &Transformed, head: &str, body: &str) -> Vec<ProdId> {
+        let body: Item = Element::terminals(body)
+            .into_iter()
+            .map(|symbol| {
+                let head = Element::NotTerminal(symbol.to_string());
+                match after.grammar.head_list.contains(&head) {
+                    true => head,
+                    false => symbol,
+                }
+            })
+            .collect();
+        let head = Element::NotTerminal(head.to_string());
+        let productions = after.grammar.productions();
+        let id = productions
+            .iter()
+            .position(|production| *production == (head.clone(), body.clone()))
+            .unwrap_or_else(|| panic!("no production {:?} -> {:?}", head, body));
+        after.origins[id].clone()
+    }
+
+    #[test]
+    fn direct_left_recursion() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        let after = eliminate_left_recursion(&grammar);
+        assert!(after
+            .grammar
+            .productions()
+            .iter()
+            .all(|(head, body)| body.first() != Some(head)));
+        assert_eq!(origin(&after, "E", "T E'"), [2]);
+        assert_eq!(origin(&after, "E'", "'+' T E'"), [1]);
+        assert_eq!(origin(&after, "E'", "ε"), [1]);
+        assert_eq!(origin(&after, "T'", "'*' F T'"), [3]);
+        assert_same_language(
+            &grammar,
+            &after,
+            &["x", "x '+' x '*' x", "'(' x '+' x ')' '*' x"],
+            &["", "x '+'", "'+' x", "'(' x"],
+        );
+    }
+
+    #[test]
+    fn indirect_left_recursion() {
+        let grammar: Grammar = "%token a b c d\nCompUnit': S ;\nS: A a | b ;\nA: S c | d ;"
+            .parse()
+            .unwrap();
+        let after = eliminate_left_recursion(&grammar);
+        // A → S c 先展开成 A → A a c | b c，再消除直接左递归
+        assert_eq!(origin(&after, "A", "b c A'"), [2, 3]);
+        assert_eq!(origin(&after, "A", "d A'"), [4]);
+        assert_eq!(origin(&after, "A'", "a c A'"), [1, 3]);
+        assert_same_language(
+            &grammar,
+            &after,
+            &["b", "d a", "b c a", "d a c a"],
+            &["a", "b c", "d"],
+        );
+    }
+
+    #[test]
+    fn left_factoring() {
+        let grammar: Grammar = "%token c x\nCompUnit': S ;\nS: 'if' c S | 'if' c S 'else' S | x ;"
+            .parse()
+            .unwrap();
+        let after = left_factor(&grammar);
+        assert_eq!(origin(&after, "S", "'if' c S S'"), [1, 2]);
+        assert_eq!(origin(&after, "S", "x"), [3]);
+        assert_eq!(origin(&after, "S'", "ε"), [1]);
+        assert_eq!(origin(&after, "S'", "'else' S"), [2]);
+        assert_same_language(
+            &grammar,
+            &after,
+            &[
+                "x",
+                "'if' c x",
+                "'if' c x 'else' x",
+                "'if' c 'if' c x 'else' x",
+            ],
+            &["'if' c", "'else' x", "x 'else' x"],
+        );
+    }
+
+    #[test]
+    fn epsilon_elimination() {
+        let grammar: Grammar = "%token a b\nCompUnit': S ;\nS: A b A ;\nA: a | ;"
+            .parse()
+            .unwrap();
+        let after = eliminate_epsilon(&grammar);
+        let epsilon = vec![Element::Terminal("ε".to_string())];
+        assert!(after
+            .grammar
+            .productions()
+            .iter()
+            .all(|(_, body)| *body != epsilon));
+        for body in ["A b A", "b A", "A b", "b"] {
+            assert_eq!(origin(&after, "S", body), [1]);
+        }
+        assert_eq!(origin(&after, "A", "a"), [2]);
+        assert_same_language(
+            &grammar,
+            &after,
+            &["b", "a b", "b a", "a b a"],
+            &["", "a", "a a b"],
+        );
+    }
+
+    #[test]
+    fn epsilon_elimination_keeps_empty_start() {
+        let grammar: Grammar = "%token a\nCompUnit': S ;\nS: a S | ;".parse().unwrap();
+        let after = eliminate_epsilon(&grammar);
+        assert_eq!(origin(&after, "CompUnit'", "ε"), [0]);
+        assert_eq!(origin(&after, "S", "a"), [1]);
+        assert_same_language(&grammar, &after, &["", "a", "a a a"], &["b"]);
+    }
+
+    #[test]
+    fn chomsky_normal_form() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        let after = to_cnf(&grammar);
+        for (id, (head, body)) in after.grammar.productions().into_iter().enumerate() {
+            match body.as_slice() {
+                [Element::NotTerminal(_), Element::NotTerminal(_)] => {
+                    assert!(!after.origins[id].is_empty())
+                }
+                [Element::Terminal(_)] => (),
+                _ => panic!("{:?} -> {:?} is not in CNF", head, body),
+            }
+        }
+        // 经过单产生式 CompUnit' → E → T → F 得到
+        assert_eq!(origin(&after, "CompUnit'", "x"), [0, 2, 4, 6]);
+        let plus = after
+            .grammar
+            .productions()
+            .iter()
+            .position(|(_, body)| body == &Element::terminals("'+'"));
+        assert_eq!(after.origins[plus.unwrap()], []);
+        assert_same_language(
+            &grammar,
+            &after,
+            &["x", "x '+' x", "x '*' x '+' x", "'(' x '+' x ')' '*' x"],
+            &["", "x x", "'(' x", "x '+'"],
+        );
+    }
+
+    #[test]
+    fn greibach_normal_form() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        let after = to_gnf(&grammar);
+        for (head, body) in after.grammar.productions() {
+            assert!(
+                matches!(body.first(), Some(Element::Terminal(_)))
+                    && body[1..]
+                        .iter()
+                        .all(|symbol| matches!(symbol, Element::NotTerminal(_))),
+                "{:?} -> {:?} is not in GNF",
+                head,
+                body
+            );
+        }
+        assert_eq!(origin(&after, "CompUnit'", "x"), [0, 2, 4, 6]);
+        assert_same_language(
+            &grammar,
+            &after,
+            &["x", "x '+' x", "x '*' x '+' x", "'(' x '+' x ')' '*' x"],
+            &["", "x x", "'(' x", "x '+'"],
+        );
+    }
+}
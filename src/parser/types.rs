@@ -1,4 +1,6 @@
+use crate::parser::lr1_parser::NodeKind;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Element {
@@ -6,43 +8,99 @@ pub enum Element {
     NotTerminal(String),
 }
 impl Element {
-    // pub(crate) fn is_semicolon(&self) -> bool {
-    //     if let Element::Terminal(token) = self {
-    //         return token.as_str() == "';'";
-    //     }
-    //     false
-    // }
-    // pub(crate) fn is_left_bracket(&self) -> bool {
-    //     if let Element::Terminal(token) = self {
-    //         return token.as_str() == "'{'" || token.as_str() == "'['" || token.as_str() == "'('";
-    //     }
-    //     false
-    // }
-    // pub(crate) fn is_right_bracket(&self) -> bool {
-    //     if let Element::Terminal(token) = self {
-    //         return token.as_str() == "'}'" || token.as_str() == "']'" || token.as_str() == "')'";
-    //     }
-    //     false
-    // }
-    // pub(crate) fn is_paired(&self, rhs: &Element) -> bool {
-    //     let left = self.clone().unwarp().0;
-    //     let right = rhs.clone().unwarp().0;
-    //     match right.as_str() {
-    //         "'}'" => left.as_str() == "'{'",
-    //         "']'" => left.as_str() == "'['",
-    //         "')'" => left.as_str() == "'('",
-    //         &_ => false,
-    //     }
-    // }
-
-    pub(crate) fn unwarp(self) -> (String, usize) {
+    /// 去掉引号后的字面文本，`'int'` -> `int`
+    pub(crate) fn lexeme(&self) -> &str {
+        let (Self::Terminal(v) | Self::NotTerminal(v)) = self;
+        v.strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .filter(|v| !v.is_empty())
+            .unwrap_or(v)
+    }
+}
+#[cfg(test)]
+impl Element {
+    /// 测试用：按空白切分的终结符序列
+    pub(crate) fn terminals(text: &str) -> Vec<Element> {
+        text.split_whitespace()
+            .map(|v| Element::Terminal(v.to_string()))
+            .collect()
+    }
+}
+impl Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::NotTerminal(v) => (v, 0),
-            Self::Terminal(v) => (v, 1),
+            Self::Terminal(v) | Self::NotTerminal(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// 源文本中的字节区间 `[start, end)`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: Element,
+    pub text: String,
+    pub span: Span,
+    /// 记号之前的空白和注释，无损语法树据此还原源文本
+    pub leading: String,
+    /// 错误恢复对该记号的处理：插入的记号为 Missing，删除的记号为 Skipped
+    pub(crate) recovery: Option<NodeKind>,
+}
+impl Token {
+    pub fn new(kind: Element, text: &str, span: Span) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+            span,
+            leading: String::new(),
+            recovery: None,
         }
     }
-    pub(crate) fn get_pos(&self) -> usize {
-        0
+    /// 错误恢复时插入的记号，没有对应的源文本
+    pub(crate) fn missing(kind: Element, span: Span) -> Self {
+        Self {
+            recovery: Some(NodeKind::Missing),
+            ..Self::new(kind, "", span)
+        }
+    }
+    /// 错误恢复时插入的、输入中并不存在的记号
+    pub fn is_missing(&self) -> bool {
+        self.recovery == Some(NodeKind::Missing)
+    }
+    pub(crate) fn eof(offset: usize) -> Self {
+        Self::new(
+            Element::Terminal("#".to_string()),
+            "",
+            Span::new(offset, offset),
+        )
+    }
+    /// 没有源文件时，用空格把终结符拼成一段源文本，便于报错时定位
+    pub fn from_elements(input: &[Element]) -> (Vec<Token>, String) {
+        let mut source = String::new();
+        let mut tokens = Vec::with_capacity(input.len());
+        for element in input {
+            if !source.is_empty() {
+                source.push(' ');
+            }
+            let start = source.len();
+            source.push_str(element.lexeme());
+            tokens.push(Token::new(
+                element.clone(),
+                element.lexeme(),
+                Span::new(start, source.len()),
+            ));
+        }
+        (tokens, source)
     }
 }
 
@@ -0,0 +1,146 @@
+// 由文法生成，不要手动修改
+#![allow(unused_imports, unused_variables, unused_braces, non_camel_case_types, clippy::let_unit_value, clippy::unit_arg)]
+use super::*;
+use ::testgrammar::parser::{Actions, LR1Driver, ParseTables, ParserError, ProdId, Slot, Token};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Token(Token),
+    Unit,
+    /// 错误恢复后无法构造的值
+    Error,
+    CompUnit(i64),
+    Exp(i64),
+    Term(i64),
+    Unit_(i64),
+}
+
+/// 每个产生式的左部，用来检查分析表是否由同一文法构造
+const HEADS: &[&str] = &[
+    "CompUnit'",
+    "CompUnit",
+    "Exp",
+    "Exp",
+    "Term",
+    "Term",
+    "Unit",
+    "Unit",
+];
+
+pub struct GrammarActions;
+
+impl Actions for GrammarActions {
+    type Value = Value;
+
+    fn shift(&mut self, token: &Token) -> Value {
+        Value::Token(token.clone())
+    }
+    fn reduce(&mut self, prod: ProdId, children: Vec<Value>) -> Value {
+        if children.iter().any(|v| matches!(v, Value::Error) || matches!(v, Value::Token(t) if t.is_missing())) {
+            return Value::Error;
+        }
+        let mut children = children.into_iter();
+        match prod {
+            // `CompUnit: Exp`
+            1 => {
+                let v1 = match children.next() {
+                    Some(Value::Exp(v)) => v,
+                    _ => unreachable!(),
+                };
+                Value::CompUnit({ v1 })
+            }
+            // `Exp: Term '+' Exp`
+            2 => {
+                let v1 = match children.next() {
+                    Some(Value::Term(v)) => v,
+                    _ => unreachable!(),
+                };
+                let v2 = token(children.next());
+                let v3 = match children.next() {
+                    Some(Value::Exp(v)) => v,
+                    _ => unreachable!(),
+                };
+                Value::Exp({ v1 + v3 })
+            }
+            // `Exp: Term`
+            3 => {
+                let v1 = match children.next() {
+                    Some(Value::Term(v)) => v,
+                    _ => unreachable!(),
+                };
+                Value::Exp({ v1 })
+            }
+            // `Term: Unit '*' Term`
+            4 => {
+                let v1 = match children.next() {
+                    Some(Value::Unit_(v)) => v,
+                    _ => unreachable!(),
+                };
+                let v2 = token(children.next());
+                let v3 = match children.next() {
+                    Some(Value::Term(v)) => v,
+                    _ => unreachable!(),
+                };
+                Value::Term({ v1 * v3 })
+            }
+            // `Term: Unit`
+            5 => {
+                let v1 = match children.next() {
+                    Some(Value::Unit_(v)) => v,
+                    _ => unreachable!(),
+                };
+                Value::Term({ v1 })
+            }
+            // `Unit: IntConst`
+            6 => {
+                let v1 = token(children.next());
+                Value::Unit_({ v1.text.parse::<i64>().unwrap() })
+            }
+            // `Unit: '(' Exp ')'`
+            7 => {
+                let v1 = token(children.next());
+                let v2 = match children.next() {
+                    Some(Value::Exp(v)) => v,
+                    _ => unreachable!(),
+                };
+                let v3 = token(children.next());
+                Value::Unit_({ v2 })
+            }
+            _ => Value::Unit,
+        }
+    }
+    fn recovered(&mut self, accepted: bool, slots: Vec<Slot<Value>>) -> Value {
+        let mut values = slots.into_iter().filter_map(|slot| match slot {
+            Slot::Value(v) => Some(v),
+            Slot::Skipped(_) => None,
+        });
+        match (accepted, values.next(), values.next()) {
+            (true, Some(v), None) => v,
+            _ => Value::Error,
+        }
+    }
+}
+
+fn token(value: Option<Value>) -> Token {
+    match value {
+        Some(Value::Token(v)) => v,
+        v => unreachable!("expected a token, found {:?}", v),
+    }
+}
+
+/// 分析 `tokens`，返回开始符号的值（分析失败时为 None）和分析中的错误
+pub fn parse(
+    tables: Arc<ParseTables>,
+    tokens: impl IntoIterator<Item = Token>,
+    source: &str,
+) -> (Option<i64>, Vec<ParserError>) {
+    for (id, head) in HEADS.iter().enumerate() {
+        assert_eq!(&tables.production(id).0.to_string(), head, "parse tables were built from a different grammar");
+    }
+    let outcome = LR1Driver::with_actions(tables, GrammarActions).construct_tree_from_tokens(tokens, source);
+    match outcome.tree {
+        Value::CompUnit(v) => (Some(v), outcome.errors),
+        _ => (None, outcome.errors),
+    }
+}
@@ -0,0 +1,40 @@
+//! 由 calc.txt 生成的分析器与检入的 calc_parser.rs 一致，并且能编译、运行。
+//! 文法或生成器改动后用 `UPDATE_GENERATED=1 cargo test --test codegen` 重新生成
+#[rustfmt::skip]
+mod calc_parser;
+
+use std::sync::Arc;
+use testgrammar::parser::{generate, Grammar, LR1Parser, Lexer, ParseTables};
+
+fn grammar() -> Grammar {
+    include_str!("calc.txt").parse().unwrap()
+}
+
+fn eval(tables: &Arc<ParseTables>, source: &str) -> Option<i64> {
+    let (tokens, _) = Lexer::new(tables).tokenize(source);
+    let (value, errors) = calc_parser::parse(tables.clone(), tokens, source);
+    assert_eq!(value.is_some(), errors.is_empty(), "{:?}", errors);
+    value
+}
+
+#[test]
+fn generated_parser_is_up_to_date() {
+    let code = generate(&grammar()).unwrap();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/codegen/calc_parser.rs");
+    if std::env::var_os("UPDATE_GENERATED").is_some() {
+        std::fs::write(path, &code).unwrap();
+    }
+    assert!(
+        code == include_str!("calc_parser.rs"),
+        "tests/codegen/calc_parser.rs is out of date, run `UPDATE_GENERATED=1 cargo test --test codegen`"
+    );
+}
+
+#[test]
+fn generated_parser_evaluates() {
+    let tables = Arc::new(LR1Parser::from_grammar(&grammar()).into_tables());
+    assert_eq!(eval(&tables, "1 + 2 * 3"), Some(7));
+    assert_eq!(eval(&tables, "(1 + 2) * 3"), Some(9));
+    // 错误恢复插入了记号时不执行语义动作
+    assert_eq!(eval(&tables, "1 + * 3"), None);
+}